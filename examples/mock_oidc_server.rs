@@ -0,0 +1,35 @@
+//! 本地模拟 OpenID Connect 登录提供方
+//!
+//! 在 `etc/config.toml` 中添加以下提供方后，即可离线测试第三方登录流程，
+//! 访问授权地址时通过 `login_hint` 参数指定登录的用户（默认为 `alice`）。
+//!
+//! ```toml
+//! [[oidc.providers]]
+//! name = "mock"
+//! issuer = "http://127.0.0.1:9091"
+//! client_id = "mock_client"
+//! client_secret = "mock_secret"
+//! redirect_uri = "http://localhost:8080/oidc/mock/callback"
+//! ```
+//!
+//! ```bash
+//! cargo run --example mock_oidc_server -- 127.0.0.1:9091
+//! curl localhost:8080/user/oidc/mock/authorize
+//! ```
+
+use axum_best::oidc::mock;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let listen = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9091".to_string());
+
+    let listener = TcpListener::bind(&listen).await?;
+    let issuer = format!("http://{}", listen);
+    println!("mock oidc provider listening on {}", issuer);
+    println!("client_id = \"{}\", client_secret = \"{}\"", mock::CLIENT_ID, mock::CLIENT_SECRET);
+    axum::serve(listener, mock::router(&issuer)).await?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! 本地模拟微信 `jscode2session` 接口
+//!
+//! 使用 `etc/config.toml` 中的 appid/secret 启动模拟服务，然后将 `[wechat] base_url`
+//! 设置为 `http://127.0.0.1:9090`，即可离线测试整个微信登录流程。
+//!
+//! ```bash
+//! cargo run --example mock_wechat_server -- etc/config.toml 127.0.0.1:9090
+//! curl -X POST localhost:8080/user/wx/login -H 'content-type: application/json' -d '{"code":"abc"}'
+//! ```
+
+use axum_best::conf::AppConf;
+use axum_best::wechat::mock;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let conf_path = args.next().unwrap_or_else(|| "etc/config.toml".to_string());
+    let listen = args.next().unwrap_or_else(|| "127.0.0.1:9090".to_string());
+
+    let cfg = AppConf::from_path(&conf_path)?;
+    let listener = TcpListener::bind(&listen).await?;
+    println!("mock wechat api listening on http://{}", listen);
+    println!("set [wechat] base_url = \"http://{}\" in {}", listen, conf_path);
+    axum::serve(listener, mock::router(&cfg.wechat.appid, &cfg.wechat.secret)).await?;
+    Ok(())
+}
@@ -4,6 +4,7 @@ fn main() {
     println!("=== Primitive 枚举使用示例 ===");
 
     // 创建不同类型的 Primitive 值
+    #[allow(clippy::approx_constant)]
     let values = vec![
         Primitive::I32(42),
         Primitive::from(3.14f64),
@@ -5,6 +5,7 @@
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
+use axum_best::models::user::ProfileField;
 use axum_best::models::user::UserInfo;
 
 /// 获取当前时间戳
@@ -32,11 +33,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         age: 25,
         phone: "13800138000".to_string(),
         wx_open_id: "wx_openid_123456789".to_string(),
+        email: Some("demo@example.com".to_string()),
+        email_verified_at: 0,
         salt: "random_salt".to_string(),
         password: "hashed_password".to_string(),
         created_at: current_timestamp(),
         updated_at: current_timestamp(),
         deleted_at: 0,
+        purge_at: 0,
+        version: 0,
     };
 
     println!("   创建前用户ID: {}", new_user.id);
@@ -65,21 +70,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         age: new_user.age,
         phone: new_user.phone.clone(),
         wx_open_id: new_user.wx_open_id.clone(),
+        email: new_user.email.clone(),
+        email_verified_at: new_user.email_verified_at,
         salt: new_user.salt.clone(),
         password: new_user.password.clone(),
         created_at: new_user.created_at,
         updated_at: current_timestamp(),
         deleted_at: new_user.deleted_at,
+        purge_at: new_user.purge_at,
+        version: new_user.version,
     };
 
-    // 在实际使用中调用: user::update(&pool, &updated_user).await?;
+    // 在实际使用中调用: let updated = user::update(&pool, &updated_user).await?;
+    // 用户已被并发修改（version 不一致）时 updated 为 false
     println!("   更新用户昵称为: {}", updated_user.nick_name);
     println!("   更新用户签名为: {}\n", updated_user.signature);
 
     // 5. 部分更新用户信息
     println!("5. 部分更新用户信息:");
-    let updates = vec![("nick_name", "部分更新昵称"), ("signature", "部分更新签名")];
-    // 在实际使用中调用: user::update_partial(&pool, new_user.id, &updates).await?;
+    let updates = vec![
+        ProfileField::NickName("部分更新昵称".to_string()),
+        ProfileField::Signature("部分更新签名".to_string()),
+    ];
+    // 在实际使用中调用:
+    // user::update_profile(&pool, new_user.id, new_user.version, &updates, current_timestamp()).await?;
     println!("   部分更新字段: {:?}\n", updates);
 
     // 6. 获取用户列表
@@ -89,8 +103,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 7. 搜索用户
     println!("7. 搜索用户:");
-    // 在实际使用中调用: let users = user::search_by_nickname(&pool, "测试", 1, 10).await?;
-    println!("   可以搜索昵称包含'测试'的用户\n");
+    // 在实际使用中调用: let hits = user::search_fulltext(&pool, "测试", 0, 10).await?;
+    // 服务层通过 UserService::search 按配置的搜索后端检索并高亮
+    println!("   可以按相关度搜索昵称或签名包含'测试'的用户\n");
 
     // 8. 获取用户总数
     println!("8. 获取用户总数:");
@@ -0,0 +1,7 @@
+-- Add migration script here
+
+-- 添加 email 字段到 user_info 表，未绑定邮箱的用户为 NULL
+ALTER TABLE user_info ADD COLUMN email VARCHAR(255) NULL DEFAULT NULL;
+
+-- 为 email 字段添加唯一索引（允许多个 NULL）
+CREATE UNIQUE INDEX uk_email ON user_info(email);
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- 手机号统一存储为 E.164 格式，已有的中国大陆手机号补上 +86 前缀
+UPDATE user_info SET phone = CONCAT('+86', phone) WHERE phone REGEXP '^1[3-9][0-9]{9}$';
@@ -0,0 +1,51 @@
+-- Add migration script here
+
+-- 角色表
+CREATE TABLE IF NOT EXISTS role(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR(64) NOT NULL DEFAULT '',
+    description VARCHAR(255) NOT NULL DEFAULT '',
+    created_at BIGINT NOT NULL DEFAULT 0,
+    updated_at BIGINT NOT NULL DEFAULT 0,
+    UNIQUE INDEX uk_name (name)
+);
+
+-- 权限表，code 形如 `user:write`
+CREATE TABLE IF NOT EXISTS permission(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    code VARCHAR(64) NOT NULL DEFAULT '',
+    description VARCHAR(255) NOT NULL DEFAULT '',
+    created_at BIGINT NOT NULL DEFAULT 0,
+    UNIQUE INDEX uk_code (code)
+);
+
+-- 角色拥有的权限
+CREATE TABLE IF NOT EXISTS role_permission(
+    role_id BIGINT NOT NULL,
+    permission_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (role_id, permission_id),
+    INDEX idx_permission_id (permission_id)
+);
+
+-- 用户拥有的角色
+CREATE TABLE IF NOT EXISTS user_role(
+    user_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_id, role_id),
+    INDEX idx_role_id (role_id)
+);
+
+-- 内置角色和权限，首个管理员需手动授予：
+-- INSERT INTO user_role (user_id, role_id, created_at) SELECT <user_id>, id, UNIX_TIMESTAMP() FROM role WHERE name = 'admin';
+INSERT IGNORE INTO role (name, description, created_at, updated_at) VALUES
+    ('admin', 'Administrator', UNIX_TIMESTAMP(), UNIX_TIMESTAMP());
+
+INSERT IGNORE INTO permission (code, description, created_at) VALUES
+    ('user:read', 'Read any user', UNIX_TIMESTAMP()),
+    ('user:write', 'Create and update users', UNIX_TIMESTAMP()),
+    ('role:admin', 'Manage roles of users', UNIX_TIMESTAMP());
+
+INSERT IGNORE INTO role_permission (role_id, permission_id, created_at)
+    SELECT r.id, p.id, UNIX_TIMESTAMP() FROM role r, permission p WHERE r.name = 'admin';
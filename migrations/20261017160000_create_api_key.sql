@@ -0,0 +1,21 @@
+-- Add migration script here
+
+-- 服务间调用使用的 API Key，只保存 SHA256 哈希
+CREATE TABLE IF NOT EXISTS api_key(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR(64) NOT NULL DEFAULT '',
+    -- 明文 key 的前缀，用于在列表中识别
+    prefix VARCHAR(16) NOT NULL DEFAULT '',
+    key_hash VARCHAR(64) NOT NULL DEFAULT '',
+    -- 空格分隔的权限编码，例如 `user:read user:write`
+    scopes VARCHAR(1024) NOT NULL DEFAULT '',
+    -- 代表的用户，0 表示纯服务调用
+    user_id BIGINT NOT NULL DEFAULT 0,
+    -- 过期时间，0 表示永不过期
+    expires_at BIGINT NOT NULL DEFAULT 0,
+    last_used_at BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    -- 吊销时间，0 表示有效
+    revoked_at BIGINT NOT NULL DEFAULT 0,
+    UNIQUE INDEX uk_key_hash (key_hash)
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+
+-- 用户申请注销后计划彻底删除的时间，0 表示未申请注销
+ALTER TABLE user_info ADD COLUMN purge_at BIGINT NOT NULL DEFAULT 0;
+
+-- 后台清理任务按 purge_at 查找到期的账号
+CREATE INDEX idx_purge_at ON user_info(purge_at);
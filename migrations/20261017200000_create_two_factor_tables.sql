@@ -0,0 +1,24 @@
+-- Add migration script here
+
+-- 用户的 TOTP 密钥，secret 使用 DataCipher 加密后保存
+CREATE TABLE IF NOT EXISTS user_totp(
+    user_id BIGINT NOT NULL PRIMARY KEY,
+    secret VARCHAR(255) NOT NULL DEFAULT '',
+    -- 启用时间，0 表示已生成密钥但尚未验证
+    enabled_at BIGINT NOT NULL DEFAULT 0,
+    -- 最近一次验证通过的时间步，防止同一验证码被重放
+    last_step BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    updated_at BIGINT NOT NULL DEFAULT 0
+);
+
+-- 一次性恢复码，只保存 SHA256 哈希
+CREATE TABLE IF NOT EXISTS user_recovery_code(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    user_id BIGINT NOT NULL DEFAULT 0,
+    code_hash VARCHAR(64) NOT NULL DEFAULT '',
+    -- 使用时间，0 表示未使用
+    used_at BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    UNIQUE INDEX uk_user_code (user_id, code_hash)
+);
@@ -0,0 +1,17 @@
+-- Add migration script here
+
+-- 第三方登录（OpenID Connect / OAuth2）账号与用户的绑定关系
+CREATE TABLE IF NOT EXISTS user_identity(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    user_id BIGINT NOT NULL DEFAULT 0,
+    -- 配置中的登录提供方名称，例如 `google`
+    provider VARCHAR(32) NOT NULL DEFAULT '',
+    -- 用户在提供方的唯一标识（`sub`）
+    subject VARCHAR(255) NOT NULL DEFAULT '',
+    -- 提供方返回的邮箱，仅用于展示
+    email VARCHAR(255) NOT NULL DEFAULT '',
+    created_at BIGINT NOT NULL DEFAULT 0,
+    last_login_at BIGINT NOT NULL DEFAULT 0,
+    UNIQUE INDEX uk_provider_subject (provider, subject),
+    INDEX idx_user_id (user_id)
+);
@@ -0,0 +1,22 @@
+-- Add migration script here
+
+-- 登录审计日志，记录每一次登录尝试（成功或失败）
+CREATE TABLE IF NOT EXISTS login_event(
+    id BIGINT PRIMARY KEY AUTO_INCREMENT,
+    -- 登录的用户，账号不存在或无法确定时为 0
+    user_id BIGINT NOT NULL DEFAULT 0,
+    -- 登录方式：wechat / email / sms / oidc / two_factor
+    method VARCHAR(16) NOT NULL DEFAULT '',
+    ip VARCHAR(64) NOT NULL DEFAULT '',
+    user_agent VARCHAR(256) NOT NULL DEFAULT '',
+    -- 设备名称，来自 `X-Device-Name` 或根据 User-Agent 推断
+    device VARCHAR(64) NOT NULL DEFAULT '',
+    success BOOLEAN NOT NULL DEFAULT FALSE,
+    -- 失败原因，或成功登录的补充说明（例如新注册、需要两步验证）
+    reason VARCHAR(64) NOT NULL DEFAULT '',
+    -- 成功登录来自该用户从未成功登录过的 IP / 设备
+    new_ip BOOLEAN NOT NULL DEFAULT FALSE,
+    new_device BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL DEFAULT 0,
+    INDEX idx_user_id_id (user_id, id)
+);
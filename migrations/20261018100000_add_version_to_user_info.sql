@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- 乐观锁版本号，每次更新用户加 1，以 ETag 返回给客户端，更新时通过 If-Match 校验
+ALTER TABLE user_info ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
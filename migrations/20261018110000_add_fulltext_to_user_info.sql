@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- 昵称和签名的全文索引，ngram 分词（默认 ngram_token_size = 2）支持中文，供用户搜索按相关度排序
+ALTER TABLE user_info ADD FULLTEXT INDEX ft_nick_name_signature (nick_name, signature) WITH PARSER ngram;
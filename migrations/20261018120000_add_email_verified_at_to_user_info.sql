@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- 邮箱通过验证码或身份提供方验证的时间，0 表示邮箱未经验证，未验证的邮箱不会关联第三方账号
+ALTER TABLE user_info ADD COLUMN email_verified_at BIGINT NOT NULL DEFAULT 0;
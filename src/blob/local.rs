@@ -0,0 +1,65 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::blob::BlobStore;
+use crate::utils;
+
+/// Backend keeping files in a local directory
+///
+/// A file is first written next to its final path and then renamed, so readers never see a
+/// partial file. Files are served by the application under `public_url`, `/blobs` by default.
+pub struct LocalBlobStore {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl LocalBlobStore {
+    /// Creates the directory if needed
+    pub fn new(dir: &str, public_url: &str) -> anyhow::Result<LocalBlobStore> {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| anyhow::anyhow!("create blob directory {} error {}", dir, err))?;
+        let public_url = match public_url.trim_end_matches('/') {
+            "" => "/blobs",
+            url => url,
+        };
+        Ok(LocalBlobStore {
+            dir: PathBuf::from(dir),
+            public_url: public_url.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(tokio::fs::try_exists(self.dir.join(key)).await?)
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> anyhow::Result<()> {
+        let path = self.dir.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let partial = path.with_extension(format!("partial-{}", utils::gen_token(4)));
+        tokio::fs::write(&partial, data).await?;
+        if let Err(err) = tokio::fs::rename(&partial, &path).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
@@ -0,0 +1,239 @@
+pub mod local;
+pub mod s3;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use derivative::Derivative;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+
+use crate::blob::local::LocalBlobStore;
+use crate::blob::s3::S3BlobStore;
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Blob storage configuration
+#[derive(Derivative, Deserialize, SmartDefault, Clone)]
+#[derivative(Debug)]
+#[serde(default)]
+pub struct BlobConf {
+    /// Storage backend, either "local" or "s3"
+    /// - local: files under `local_dir`, served by the application at `/blobs/{key}`
+    /// - s3: objects of an S3-compatible bucket (AWS S3, MinIO, ...)
+    #[default("local")]
+    pub backend: String,
+
+    /// Maximum time spent on one read or write, in seconds
+    #[default(10)]
+    pub timeout_secs: u64,
+
+    /// Directory of the local backend
+    #[default("data/blobs")]
+    pub local_dir: String,
+
+    /// Base URL of the stored files, `/blobs` (served by the application) for the local backend
+    /// and `{s3_endpoint}/{s3_bucket}` for S3 when empty
+    pub public_url: String,
+
+    /// Endpoint of the S3-compatible service, e.g. `https://s3.us-east-1.amazonaws.com` or
+    /// `http://127.0.0.1:9000` for MinIO. Buckets are addressed path-style.
+    pub s3_endpoint: String,
+
+    /// Bucket of the stored files
+    pub s3_bucket: String,
+
+    /// Region the requests are signed for
+    #[default("us-east-1")]
+    pub s3_region: String,
+
+    /// Access key ID
+    pub s3_access_key: String,
+
+    /// Secret access key
+    ///
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub s3_secret_key: String,
+}
+
+impl BlobConf {
+    /// Builds the blob store of the configured backend
+    ///
+    /// # Returns
+    /// - `Ok(BlobClient)` when the backend is known and its settings are valid
+    /// - `Err(anyhow::Error)` if the backend is unknown or misconfigured
+    pub fn build(&self) -> anyhow::Result<BlobClient> {
+        let timeout = Duration::from_secs(self.timeout_secs);
+        let store: Arc<dyn BlobStore> = match self.backend.as_str() {
+            "local" => Arc::new(LocalBlobStore::new(&self.local_dir, &self.public_url)?),
+            "s3" => Arc::new(S3BlobStore::new(self, timeout)?),
+            other => return Err(anyhow::anyhow!("unsupported blob backend {}", other)),
+        };
+        Ok(BlobClient::new(store, timeout))
+    }
+}
+
+/// Storage backend of uploaded files
+///
+/// Keys are relative paths such as `avatars/{sha256}.jpg`, checked by `BlobClient` before they
+/// reach a backend.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Whether a file is stored under `key`
+    async fn exists(&self, key: &str) -> anyhow::Result<bool>;
+
+    /// Stores a file under `key`, replacing any previous one
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> anyhow::Result<()>;
+
+    /// Reads the file stored under `key`, `None` when there is none
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Public URL of the file stored under `key`
+    fn url(&self, key: &str) -> String;
+}
+
+/// Blob store shared by the whole application
+///
+/// Rejects keys which could escape the store and bounds every call by the configured timeout.
+/// Cloning is cheap.
+#[derive(Clone)]
+pub struct BlobClient {
+    store: Arc<dyn BlobStore>,
+    timeout: Duration,
+}
+
+impl BlobClient {
+    pub fn new(store: Arc<dyn BlobStore>, timeout: Duration) -> BlobClient {
+        BlobClient { store, timeout }
+    }
+
+    /// Whether a file is stored under `key`
+    ///
+    /// # Returns
+    /// * `Result<bool, AppError>` - `ErrBlobTimeout` when the backend is too slow and
+    ///   `ErrBlobStore` for any other failure
+    pub async fn exists(&self, key: &str) -> Result<bool, AppError> {
+        check_key(key)?;
+        self.bounded("check", key, self.store.exists(key)).await
+    }
+
+    /// Stores a file under `key`, replacing any previous one
+    ///
+    /// # Returns
+    /// * `Result<(), AppError>` - `ErrBlobTimeout` when the backend is too slow and
+    ///   `ErrBlobStore` for any other failure
+    pub async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), AppError> {
+        check_key(key)?;
+        self.bounded("put", key, self.store.put(key, data, content_type))
+            .await
+    }
+
+    /// Reads the file stored under `key`
+    ///
+    /// # Returns
+    /// * `Result<Vec<u8>, AppError>` - `ErrBlobNotFound` when there is none
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>, AppError> {
+        check_key(key).map_err(|_| errors::ErrBlobNotFound.clone())?;
+        self.bounded("get", key, self.store.get(key))
+            .await?
+            .ok_or_else(|| errors::ErrBlobNotFound.clone())
+    }
+
+    /// Public URL of the file stored under `key`
+    pub fn url(&self, key: &str) -> String {
+        self.store.url(key)
+    }
+
+    async fn bounded<T>(
+        &self,
+        action: &str,
+        key: &str,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> Result<T, AppError> {
+        match tokio::time::timeout(self.timeout, call).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => {
+                error!("blob {} {} error {}", action, key, err);
+                Err(errors::ErrBlobStore.clone())
+            }
+            Err(_) => {
+                error!("blob {} {} timeout after {:?}", action, key, self.timeout);
+                Err(errors::ErrBlobTimeout.clone())
+            }
+        }
+    }
+}
+
+/// Checks a key is a relative path of plain segments, e.g. `avatars/ab12.jpg`
+///
+/// Segments hold ASCII letters, digits, `-`, `_` and `.` and never start with a dot, so a key
+/// cannot climb out of the local directory nor address another bucket.
+fn check_key(key: &str) -> Result<(), AppError> {
+    let valid = !key.is_empty()
+        && key.len() <= 512
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+        });
+    if valid {
+        Ok(())
+    } else {
+        error!("invalid blob key {:?}", key);
+        Err(errors::ErrBadRequest.clone())
+    }
+}
+
+/// MIME type of a stored file from the extension of its key
+pub fn content_type(key: &str) -> &'static str {
+    match key.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_key() {
+        assert!(check_key("avatars/ab12.jpg").is_ok());
+        assert!(check_key("../etc/passwd").is_err());
+        assert!(check_key("avatars/../../etc").is_err());
+        assert!(check_key("/avatars/ab12.jpg").is_err());
+        assert!(check_key("avatars//ab12.jpg").is_err());
+        assert!(check_key("avatars/.hidden").is_err());
+        assert!(check_key("avatars/a b.jpg").is_err());
+        assert!(check_key("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_round_trip() {
+        let dir = std::env::temp_dir().join(format!("blob_store_{}", std::process::id()));
+        let conf = BlobConf {
+            local_dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let blobs = conf.build().unwrap();
+        assert!(!blobs.exists("avatars/a.png").await.unwrap());
+        blobs
+            .put("avatars/a.png", b"png".to_vec(), "image/png")
+            .await
+            .unwrap();
+        assert!(blobs.exists("avatars/a.png").await.unwrap());
+        assert_eq!(blobs.get("avatars/a.png").await.unwrap(), b"png");
+        assert_eq!(blobs.url("avatars/a.png"), "/blobs/avatars/a.png");
+        let err = blobs.get("avatars/b.png").await.unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrBlobNotFound.err_no());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use crate::cli::app_error;
+use crate::conf::AppConf;
+use crate::core::api_key;
+use crate::models::api_key::ApiKey;
+use crate::repos;
+
+/// API key management
+#[derive(clap::Subcommand, Debug)]
+pub enum ApiKeyCommand {
+    /// Creates a key and prints it, the plain key cannot be shown again
+    Create {
+        /// Name of the caller the key is issued to
+        #[arg(long)]
+        name: String,
+        /// Permission codes granted to the key, e.g. `user:read,user:write`
+        #[arg(long, value_delimiter = ',')]
+        scopes: Vec<String>,
+        /// Days until the key expires, never when omitted
+        #[arg(long)]
+        expires_days: Option<i64>,
+        /// User the key acts for, 0 for a pure service key
+        #[arg(long, default_value_t = 0)]
+        user_id: i64,
+    },
+    /// Lists every key
+    List,
+    /// Revokes a key, requests using it are rejected immediately
+    Revoke {
+        /// Id of the key
+        #[arg(long)]
+        id: i64,
+    },
+}
+
+impl ApiKeyCommand {
+    pub async fn run(self, cfg: AppConf) -> anyhow::Result<()> {
+        let conn = cfg.mysql.init_conn().await?;
+        let now = chrono::Utc::now().timestamp();
+        match self {
+            ApiKeyCommand::Create {
+                name,
+                scopes,
+                expires_days,
+                user_id,
+            } => {
+                let generated = api_key::generate();
+                let mut key = ApiKey {
+                    name,
+                    prefix: generated.prefix,
+                    key_hash: generated.hash,
+                    scopes: normalize_scopes(&scopes),
+                    user_id,
+                    expires_at: expires_days.map_or(0, |days| now + days * 86400),
+                    created_at: now,
+                    ..Default::default()
+                };
+                repos::api_key::create(&conn, &mut key)
+                    .await
+                    .map_err(app_error)?;
+                println!("created api key {} ({})", key.id, key.name);
+                println!("{}", generated.plain);
+            }
+            ApiKeyCommand::List => {
+                println!(
+                    "{:<6} {:<24} {:<12} {:<8} {:<12} {:<12} {:<8} SCOPES",
+                    "ID", "NAME", "PREFIX", "USER", "EXPIRES", "LAST_USED", "STATUS"
+                );
+                for key in repos::api_key::list(&conn).await.map_err(app_error)? {
+                    let status = if key.revoked_at != 0 {
+                        "revoked"
+                    } else if key.is_active(now) {
+                        "active"
+                    } else {
+                        "expired"
+                    };
+                    println!(
+                        "{:<6} {:<24} {:<12} {:<8} {:<12} {:<12} {:<8} {}",
+                        key.id,
+                        key.name,
+                        key.prefix,
+                        key.user_id,
+                        key.expires_at,
+                        key.last_used_at,
+                        status,
+                        key.scopes
+                    );
+                }
+            }
+            ApiKeyCommand::Revoke { id } => {
+                if repos::api_key::revoke(&conn, id, now)
+                    .await
+                    .map_err(app_error)?
+                {
+                    println!("revoked api key {}", id);
+                } else {
+                    anyhow::bail!("api key {} not found or already revoked", id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Joins scopes the way they are stored, dropping blanks and duplicates
+fn normalize_scopes(scopes: &[String]) -> String {
+    let mut list: Vec<&str> = scopes
+        .iter()
+        .map(|scope| scope.trim())
+        .filter(|scope| !scope.is_empty())
+        .collect();
+    list.sort_unstable();
+    list.dedup();
+    list.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_scopes() {
+        let scopes = vec![
+            " user:write".to_string(),
+            "user:read".to_string(),
+            "".to_string(),
+            "user:write".to_string(),
+        ];
+        assert_eq!(normalize_scopes(&scopes), "user:read user:write");
+    }
+}
@@ -0,0 +1,33 @@
+pub mod api_key;
+pub mod users;
+
+use crate::cli::api_key::ApiKeyCommand;
+use crate::cli::users::UserCommand;
+use crate::conf::AppConf;
+use crate::core::rest::AppError;
+
+/// Administrative commands run instead of the HTTP server
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Manage API keys of backend callers
+    #[command(subcommand)]
+    ApiKey(ApiKeyCommand),
+    /// Import and export users in bulk
+    #[command(subcommand)]
+    Users(UserCommand),
+}
+
+impl Command {
+    /// Runs the command against the configured databases
+    pub async fn run(self, cfg: AppConf) -> anyhow::Result<()> {
+        match self {
+            Command::ApiKey(cmd) => cmd.run(cfg).await,
+            Command::Users(cmd) => cmd.run(cfg).await,
+        }
+    }
+}
+
+/// Converts an error of the service layer for command output
+pub(crate) fn app_error(err: AppError) -> anyhow::Error {
+    anyhow::anyhow!("{:?}", err)
+}
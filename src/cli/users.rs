@@ -0,0 +1,145 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use futures_util::StreamExt;
+use sqlx::MySqlPool;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::app_error;
+use crate::conf::AppConf;
+use crate::repos::user::UserFilter;
+use crate::services::bulk;
+use crate::services::bulk::UserImporter;
+use crate::types::bulk::BulkFormat;
+use crate::types::bulk::ExportUsersRequest;
+
+/// Bulk user import and export
+#[derive(clap::Subcommand, Debug)]
+pub enum UserCommand {
+    /// Imports users from a CSV or NDJSON file and prints the report as JSON
+    Import {
+        /// File to read, `-` for standard input
+        #[arg(long)]
+        file: PathBuf,
+        /// Format of the file, guessed from its extension when omitted
+        #[arg(long)]
+        format: Option<BulkFormat>,
+        /// Only validates the rows, nothing is inserted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Exports users as CSV or NDJSON
+    Export {
+        /// File to write, standard output when omitted
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Format of the export, guessed from the file extension when omitted, CSV otherwise
+        #[arg(long)]
+        format: Option<BulkFormat>,
+        /// Only users whose nickname contains this text
+        #[arg(long)]
+        q: Option<String>,
+        /// Only users registered at or after this time (Unix timestamp)
+        #[arg(long)]
+        created_from: Option<i64>,
+        /// Only users registered before this time (Unix timestamp)
+        #[arg(long)]
+        created_to: Option<i64>,
+        /// Also exports soft-deleted users
+        #[arg(long)]
+        include_deleted: bool,
+    },
+}
+
+impl UserCommand {
+    pub async fn run(self, cfg: AppConf) -> anyhow::Result<()> {
+        let conn = cfg.mysql.init_conn().await?;
+        match self {
+            UserCommand::Import {
+                file,
+                format,
+                dry_run,
+            } => {
+                let format = resolve_format(format, Some(&file))?;
+                let importer = UserImporter::new(&conn, &cfg.bulk, &cfg.sms.default_country_code)
+                    .dry_run(dry_run);
+                let report = if file == Path::new("-") {
+                    importer.run(tokio::io::stdin(), format).await
+                } else {
+                    importer
+                        .run(tokio::fs::File::open(&file).await?, format)
+                        .await
+                }
+                .map_err(app_error)?;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                if report.failed > 0 {
+                    eprintln!("{} of {} rows rejected", report.failed, report.total);
+                }
+            }
+            UserCommand::Export {
+                file,
+                format,
+                q,
+                created_from,
+                created_to,
+                include_deleted,
+            } => {
+                let format = resolve_format(format, file.as_deref()).unwrap_or_default();
+                let filter = bulk::export_filter(ExportUsersRequest {
+                    format,
+                    q,
+                    created_from,
+                    created_to,
+                    include_deleted,
+                });
+                match file {
+                    Some(file) => {
+                        let mut out = tokio::fs::File::create(&file).await?;
+                        export(&conn, &cfg, format, filter, &mut out).await?;
+                        eprintln!("exported users to {}", file.display());
+                    }
+                    None => export(&conn, &cfg, format, filter, &mut tokio::io::stdout()).await?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the export chunk by chunk
+async fn export<W: AsyncWrite + Unpin>(
+    conn: &MySqlPool,
+    cfg: &AppConf,
+    format: BulkFormat,
+    filter: UserFilter,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    let mut chunks = Box::pin(bulk::export_users(conn.clone(), &cfg.bulk, format, filter));
+    while let Some(chunk) = chunks.next().await {
+        out.write_all(&chunk.map_err(app_error)?).await?;
+    }
+    out.flush().await?;
+    Ok(())
+}
+
+/// The format given on the command line, else the one of the file extension
+fn resolve_format(format: Option<BulkFormat>, file: Option<&Path>) -> anyhow::Result<BulkFormat> {
+    format
+        .or_else(|| file.and_then(BulkFormat::from_path))
+        .ok_or_else(|| anyhow::anyhow!("cannot guess the format, pass --format csv or ndjson"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_format() {
+        let file = Path::new("users.JSONL");
+        assert_eq!(resolve_format(None, Some(file)).unwrap(), BulkFormat::Ndjson);
+        assert_eq!(resolve_format(Some(BulkFormat::Csv), Some(file)).unwrap(), BulkFormat::Csv);
+        assert!(resolve_format(None, Some(Path::new("-"))).is_err());
+        assert!(resolve_format(None, None).is_err());
+    }
+}
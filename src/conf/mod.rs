@@ -1,11 +1,29 @@
 use derivative::Derivative;
 use serde::Deserialize;
 
-use crate::core::state::WeChatConf;
+use crate::blob::BlobConf;
+use crate::core::client_ip::ClientIpConf;
+use crate::core::crypto::CryptoConf;
+use crate::core::cursor::CursorConf;
+use crate::core::jwt::JwtConf;
+use crate::core::lockout::LockoutConf;
+use crate::core::password::PasswordConf;
+use crate::core::reset_token::PasswordResetConf;
+use crate::core::totp::TotpConf;
+use crate::core::verify_code::VerifyCodeConf;
 use crate::data::cache::RedisConf;
 use crate::data::mysql::MysqlConf;
 use crate::logx::LogConfig;
+use crate::mail::MailConf;
+use crate::oidc::OidcConf;
+use crate::search::SearchConf;
+use crate::services::account::AccountConf;
+use crate::services::avatar::AvatarConf;
+use crate::services::bulk::BulkConf;
+use crate::services::login_event::LoginEventConf;
+use crate::sms::SmsConf;
 use crate::transport::http::HttpConf;
+use crate::wechat::WeChatConf;
 
 /// Application configuration structure
 ///
@@ -36,7 +54,100 @@ pub struct AppConf {
     /// redis config
     pub redis: RedisConf,
 
+    /// WeChat mini program configuration section
+    ///
+    /// AppID, secret and API endpoint used by the WeChat login.
     pub wechat: WeChatConf,
+
+    /// Access token configuration
+    ///
+    /// Signing algorithm, keys, issuer, audience and lifetime of the JWTs issued on login.
+    pub jwt: JwtConf,
+
+    /// Password hashing configuration
+    ///
+    /// Argon2id cost parameters used when storing email login passwords.
+    pub password: PasswordConf,
+
+    /// Encryption at rest configuration
+    ///
+    /// Key used to encrypt secrets such as WeChat session keys before they are stored.
+    pub crypto: CryptoConf,
+
+    /// Keyset pagination configuration
+    ///
+    /// Secret signing the cursors of list endpoints in cursor mode.
+    pub cursor: CursorConf,
+
+    /// Verification code configuration
+    ///
+    /// Length, lifetime, attempts and resend cooldowns of the codes sent by email or SMS.
+    pub verify_code: VerifyCodeConf,
+
+    /// Password reset configuration
+    ///
+    /// Lifetime and resend cooldown of the reset tokens, and link of the reset page.
+    pub password_reset: PasswordResetConf,
+
+    /// Two-factor authentication configuration
+    ///
+    /// Issuer shown by authenticator apps, accepted clock drift and lifetime of login challenges.
+    pub totp: TotpConf,
+
+    /// Brute-force protection configuration
+    ///
+    /// Delays and lockouts applied after failed password and code checks.
+    pub lockout: LockoutConf,
+
+    /// OpenID Connect login configuration
+    ///
+    /// Corporate SSO, Google, GitHub and other providers users can log in with.
+    pub oidc: OidcConf,
+
+    /// Outbound mail configuration
+    ///
+    /// Delivery backend (SMTP, `.eml` outbox or log) and sender of verification mails.
+    pub mail: MailConf,
+
+    /// SMS configuration
+    ///
+    /// Provider of the SMS login codes and default country code of phone numbers.
+    pub sms: SmsConf,
+
+    /// Account deletion configuration
+    ///
+    /// Grace period of self-service deletions and schedule of the task erasing accounts.
+    pub account: AccountConf,
+
+    /// Login audit configuration
+    ///
+    /// Which logins from a new IP or device are mailed to the user.
+    pub login_event: LoginEventConf,
+
+    /// Bulk user transfer configuration
+    ///
+    /// Batch sizes and limits of the user imports and exports of the admin API and CLI.
+    pub bulk: BulkConf,
+
+    /// Blob storage configuration
+    ///
+    /// Backend (local directory or S3-compatible bucket) of uploaded files such as avatars.
+    pub blob: BlobConf,
+
+    /// Avatar upload configuration
+    ///
+    /// Size limit and accepted image types of `POST /user/me/avatar`.
+    pub avatar: AvatarConf,
+
+    /// Full-text search configuration
+    ///
+    /// Backend (MySQL FULLTEXT or in-process index) of the nickname and signature search.
+    pub search: SearchConf,
+
+    /// Client IP configuration
+    ///
+    /// Reverse proxies trusted to report the address of the client in forwarded headers.
+    pub client_ip: ClientIpConf,
 }
 
 impl AppConf {
@@ -0,0 +1,120 @@
+use tracing::error;
+use tracing::info;
+
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::models::api_key::ApiKey;
+use crate::repos;
+use crate::utils::gen_token;
+use crate::utils::sha256_hex;
+
+/// Header carrying the API key of backend callers
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Prefix of every generated key, makes leaked keys easy to grep for
+const KEY_PREFIX: &str = "ak_";
+
+/// Number of characters of the plain key kept in clear for listings
+const DISPLAY_PREFIX_LEN: usize = 10;
+
+/// `last_used_at` is written at most once per interval per key, in seconds
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// Identity of a caller authenticated with an API key
+#[derive(Debug, Clone)]
+pub struct ApiKeyCaller {
+    /// Id of the key
+    pub id: i64,
+    /// Name of the key
+    pub name: String,
+    /// Permission codes granted to the key, checked instead of the owner's roles
+    pub scopes: Vec<String>,
+}
+
+/// A freshly generated key, the plain value is only available at this point
+#[derive(Debug)]
+pub struct GeneratedKey {
+    /// Plain key handed to the caller
+    pub plain: String,
+    /// First characters of the plain key
+    pub prefix: String,
+    /// SHA256 hex digest stored in MySQL
+    pub hash: String,
+}
+
+/// Generates a new random API key
+pub fn generate() -> GeneratedKey {
+    let plain = format!("{}{}", KEY_PREFIX, gen_token(32));
+    GeneratedKey {
+        prefix: plain.chars().take(DISPLAY_PREFIX_LEN).collect(),
+        hash: hash_key(&plain),
+        plain,
+    }
+}
+
+/// Hashes a plain key the way it is stored
+///
+/// Keys are 256 bits of randomness, a fast hash is enough and keeps the lookup a single indexed
+/// query.
+pub fn hash_key(plain: &str) -> String {
+    sha256_hex(plain)
+}
+
+/// Looks up an API key and checks it can be used
+///
+/// # Arguments
+/// * `state` - Application state
+/// * `plain` - Value of the `X-Api-Key` header
+///
+/// # Returns
+/// * `Result<(ApiKey, ApiKeyCaller), AppError>` - The key and its caller identity,
+///   `ErrApiKeyInvalid` for unknown or revoked keys and `ErrApiKeyExpired` for expired ones
+pub async fn authenticate(
+    state: &AppState,
+    plain: &str,
+) -> Result<(ApiKey, ApiKeyCaller), AppError> {
+    let key = repos::api_key::get_by_hash(&state.get_conn(), &hash_key(plain))
+        .await?
+        .ok_or_else(|| errors::ErrApiKeyInvalid.clone())?;
+    let now = chrono::Utc::now().timestamp();
+    if key.revoked_at != 0 {
+        info!("revoked api key {} used", key.id);
+        return Err(errors::ErrApiKeyInvalid.clone());
+    }
+    if !key.is_active(now) {
+        return Err(errors::ErrApiKeyExpired.clone());
+    }
+
+    if now - key.last_used_at >= TOUCH_INTERVAL_SECS {
+        let conn = state.get_conn();
+        let id = key.id;
+        tokio::spawn(async move {
+            if let Err(err) = repos::api_key::touch(&conn, id, now).await {
+                error!("update api key {} last used error {:?}", id, err);
+            }
+        });
+    }
+
+    let caller = ApiKeyCaller {
+        id: key.id,
+        name: key.name.clone(),
+        scopes: key.scope_list(),
+    };
+    Ok((key, caller))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let key = generate();
+        assert!(key.plain.starts_with(KEY_PREFIX));
+        assert!(key.plain.starts_with(&key.prefix));
+        assert_eq!(key.prefix.len(), DISPLAY_PREFIX_LEN);
+        assert_eq!(key.hash, hash_key(&key.plain));
+        assert_ne!(key.plain, generate().plain);
+    }
+}
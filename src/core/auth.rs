@@ -0,0 +1,150 @@
+use axum::extract::FromRequestParts;
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+
+use crate::core::api_key;
+use crate::core::api_key::API_KEY_HEADER;
+use crate::core::api_key::ApiKeyCaller;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+
+/// Authenticated caller extracted from the `Authorization: Bearer <token>` header
+///
+/// Add it to a handler's arguments to require a logged-in user:
+///
+/// ```ignore
+/// pub async fn me(auth: AuthUser, State(state): State<AppState>) -> Result<UserInfo> { .. }
+/// ```
+///
+/// API keys (`X-Api-Key`) are refused with `ErrPermissionDenied`: a key only reaches the routes
+/// guarded by `RequirePermission`, which checks its scopes and hands the caller over to this
+/// extractor with `api_key` set.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    /// Id of the user the access token was issued to
+    pub user_id: i64,
+    /// Session (refresh token family) the access token belongs to, empty for API keys
+    pub session_id: String,
+    /// Set when the caller authenticated with an API key, its scopes replace the user's roles
+    pub api_key: Option<ApiKeyCaller>,
+}
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // already verified by a route guard such as `RequirePermission`
+        if let Some(auth) = parts.extensions.get::<AuthUser>() {
+            return Ok(auth.clone());
+        }
+        if bearer_token(parts).is_none() && api_key_value(parts).is_some() {
+            return Err(errors::ErrPermissionDenied.clone());
+        }
+        authenticate(parts, state).await
+    }
+}
+
+/// Verifies the access token, or else the API key, of a request
+///
+/// Only route guards checking the scopes of API keys call it directly, handlers use the
+/// `AuthUser` extractor.
+///
+/// # Returns
+/// * `Result<AuthUser, AppError>` - The caller, `ErrAuthTokenMissing` without credentials
+pub(crate) async fn authenticate(parts: &Parts, state: &AppState) -> Result<AuthUser, AppError> {
+    let Some(token) = bearer_token(parts) else {
+        let plain = api_key_value(parts).ok_or_else(|| errors::ErrAuthTokenMissing.clone())?;
+        let (key, caller) = api_key::authenticate(state, plain).await?;
+        return Ok(AuthUser {
+            user_id: key.user_id,
+            session_id: String::new(),
+            api_key: Some(caller),
+        });
+    };
+    let claims = state.jwt.verify(token)?;
+    if state.is_session_revoked(&claims.sid)? {
+        return Err(errors::ErrAuthTokenRevoked.clone());
+    }
+    Ok(AuthUser {
+        user_id: claims.user_id()?,
+        session_id: claims.sid,
+        api_key: None,
+    })
+}
+
+/// Returns the token of an `Authorization: Bearer <token>` header, if present
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    let value = parts.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    if token.is_empty() { None } else { Some(token) }
+}
+
+/// Returns the value of the `X-Api-Key` header, if present
+fn api_key_value(parts: &Parts) -> Option<&str> {
+    let value = parts.headers.get(API_KEY_HEADER)?.to_str().ok()?.trim();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    fn parts_with(value: Option<&str>) -> Parts {
+        let mut builder = Request::builder().uri("/");
+        if let Some(value) = value {
+            builder = builder.header(AUTHORIZATION, value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token(&parts_with(Some("Bearer abc.def"))), Some("abc.def"));
+        assert_eq!(bearer_token(&parts_with(Some("bearer abc"))), Some("abc"));
+        assert_eq!(bearer_token(&parts_with(Some("Basic abc"))), None);
+        assert_eq!(bearer_token(&parts_with(Some("Bearer "))), None);
+        assert_eq!(bearer_token(&parts_with(None)), None);
+    }
+
+    #[test]
+    fn test_api_key_value() {
+        let parts = Request::builder()
+            .header(API_KEY_HEADER, " ak_123 ")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert_eq!(api_key_value(&parts), Some("ak_123"));
+        assert_eq!(api_key_value(&parts_with(Some("Bearer abc"))), None);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_refused() {
+        let state = crate::core::state::test_state();
+        let (mut parts, _) = Request::builder()
+            .header(API_KEY_HEADER, "ak_123")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let err = AuthUser::from_request_parts(&mut parts, &state)
+            .await
+            .unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrPermissionDenied.err_no());
+
+        let mut parts = parts_with(None);
+        let err = AuthUser::from_request_parts(&mut parts, &state)
+            .await
+            .unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrAuthTokenMissing.err_no());
+    }
+}
@@ -0,0 +1,230 @@
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::ConnectInfo;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+
+use crate::core::state::AppState;
+
+/// Client IP configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct ClientIpConf {
+    /// Reverse proxies allowed to report the client address, as IPs or CIDR ranges
+    ///
+    /// Only requests from these peers have their `X-Forwarded-For` and `X-Real-IP` headers read.
+    /// Defaults to the loopback and private ranges, empty to always use the TCP peer address.
+    #[default(vec![
+        "127.0.0.0/8".to_string(),
+        "::1/128".to_string(),
+        "10.0.0.0/8".to_string(),
+        "172.16.0.0/12".to_string(),
+        "192.168.0.0/16".to_string(),
+        "fc00::/7".to_string(),
+    ])]
+    pub trusted_proxies: Vec<String>,
+}
+
+impl ClientIpConf {
+    /// Parses the trusted proxy ranges
+    ///
+    /// # Returns
+    /// - `Ok(TrustedProxies)` when every range is valid
+    /// - `Err(anyhow::Error)` naming the first invalid range
+    pub fn build(&self) -> anyhow::Result<TrustedProxies> {
+        let ranges = self
+            .trusted_proxies
+            .iter()
+            .map(|range| {
+                IpRange::parse(range)
+                    .ok_or_else(|| anyhow::anyhow!("invalid trusted proxy {}", range))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(TrustedProxies {
+            ranges: Arc::new(ranges),
+        })
+    }
+}
+
+/// Address range in CIDR notation, a single address without a prefix length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    fn parse(range: &str) -> Option<IpRange> {
+        let (network, prefix) = match range.trim().split_once('/') {
+            Some((network, prefix)) => {
+                (network.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?))
+            }
+            None => (range.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(IpRange { network, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip, bits) = match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                (u32::from(network) as u128, u32::from(ip) as u128, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip), 128),
+            _ => return false,
+        };
+        let host_bits = bits - u32::from(self.prefix);
+        host_bits >= bits || (network ^ ip) >> host_bits == 0
+    }
+}
+
+/// Reverse proxies allowed to report the client address. Cloning is cheap.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Arc<Vec<IpRange>>,
+}
+
+impl TrustedProxies {
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Address of the client of a request received from `peer`
+    ///
+    /// A request from a trusted proxy is attributed to the rightmost `X-Forwarded-For` hop that
+    /// is not a trusted proxy itself: the entries on its left are written by the client and can
+    /// be anything. Without `X-Forwarded-For`, the `X-Real-IP` set by the proxy is used.
+    fn client_ip(&self, parts: &Parts, peer: Option<IpAddr>) -> String {
+        let Some(peer) = peer else {
+            return "unknown".to_string();
+        };
+        if !self.contains(peer) {
+            return peer.to_string();
+        }
+        let header = |name| {
+            parts
+                .headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+        };
+        let forwarded = header("X-Forwarded-For");
+        let hops: Vec<&str> = forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .collect();
+        let mut client = None;
+        for hop in hops.iter().rev() {
+            // an entry which is not an address cannot be walked past
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = Some(ip);
+            if !self.contains(ip) {
+                break;
+            }
+        }
+        if hops.is_empty() {
+            client = header("X-Real-IP")
+                .first()
+                .and_then(|value| value.trim().parse::<IpAddr>().ok());
+        }
+        client.unwrap_or(peer).to_string()
+    }
+}
+
+/// IP address of the caller
+///
+/// Taken from the TCP peer address, or from the headers of a trusted reverse proxy (see
+/// `TrustedProxies`). Falls back to `"unknown"` when the server was not started with connect
+/// info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIp(pub String);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(state.proxies.client_ip(parts, peer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    fn parts_with(headers: &[(&str, &str)]) -> Parts {
+        let mut builder = Request::builder().uri("/");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_ip_range() {
+        let range = IpRange::parse("10.0.0.0/8").unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(range.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(
+            IpRange::parse("0.0.0.0/0")
+                .unwrap()
+                .contains("8.8.8.8".parse().unwrap())
+        );
+        assert!(
+            IpRange::parse("::1")
+                .unwrap()
+                .contains("::1".parse().unwrap())
+        );
+        assert_eq!(IpRange::parse("10.0.0.0/33"), None);
+        assert_eq!(IpRange::parse("proxy"), None);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let proxies = ClientIpConf::default().build().unwrap();
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        let public: IpAddr = "8.8.8.8".parse().unwrap();
+
+        // the client wrote 1.2.3.4, the proxy appended the address it saw
+        let parts = parts_with(&[("X-Forwarded-For", "1.2.3.4, 9.9.9.9")]);
+        assert_eq!(proxies.client_ip(&parts, Some(proxy)), "9.9.9.9");
+        // trusted hops are walked past
+        let parts = parts_with(&[("X-Forwarded-For", "1.2.3.4, 9.9.9.9, 10.0.0.2")]);
+        assert_eq!(proxies.client_ip(&parts, Some(proxy)), "9.9.9.9");
+        let parts = parts_with(&[("X-Forwarded-For", "9.9.9.9, garbage, 10.0.0.2")]);
+        assert_eq!(proxies.client_ip(&parts, Some(proxy)), "10.0.0.2");
+        // forwarded headers from a peer which is not a trusted proxy are ignored
+        assert_eq!(proxies.client_ip(&parts, Some(public)), "8.8.8.8");
+
+        let real_ip = parts_with(&[("X-Real-IP", "5.6.7.8")]);
+        assert_eq!(proxies.client_ip(&real_ip, Some(proxy)), "5.6.7.8");
+        assert_eq!(proxies.client_ip(&real_ip, Some(public)), "8.8.8.8");
+        assert_eq!(proxies.client_ip(&parts_with(&[]), Some(proxy)), "127.0.0.1");
+        assert_eq!(proxies.client_ip(&parts, None), "unknown");
+
+        let none = ClientIpConf {
+            trusted_proxies: Vec::new(),
+        }
+        .build()
+        .unwrap();
+        assert_eq!(none.client_ip(&real_ip, Some(proxy)), "127.0.0.1");
+    }
+}
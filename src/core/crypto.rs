@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::KeyInit;
+use aes_gcm::Nonce;
+use aes_gcm::aead::Aead;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use derivative::Derivative;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Size of the AES-GCM nonce prepended to every ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Encryption at rest configuration
+#[derive(Derivative, Deserialize, Clone)]
+#[derivative(Debug)]
+pub struct CryptoConf {
+    /// Base64 encoded 32 bytes AES-256-GCM key used to encrypt secrets stored in MySQL or Redis
+    ///
+    /// Generate one with `openssl rand -base64 32`.
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub data_key: String,
+}
+
+impl CryptoConf {
+    /// Decodes the data key and builds the cipher
+    ///
+    /// # Returns
+    /// - `Ok(DataCipher)` when the key is valid base64 of exactly 32 bytes
+    /// - `Err(anyhow::Error)` otherwise
+    pub fn build(&self) -> anyhow::Result<DataCipher> {
+        let key = STANDARD
+            .decode(self.data_key.trim())
+            .map_err(|err| anyhow::anyhow!("decode data key error {}", err))?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow::anyhow!("data key must be 32 bytes, got {}", key.len()))?;
+        Ok(DataCipher {
+            cipher: Arc::new(cipher),
+        })
+    }
+}
+
+/// AES-256-GCM cipher for secrets stored at rest
+///
+/// Ciphertexts are encoded as `base64(nonce || ciphertext || tag)` so they fit in string columns.
+#[derive(Clone)]
+pub struct DataCipher {
+    cipher: Arc<Aes256Gcm>,
+}
+
+impl DataCipher {
+    /// Encrypts data with a random nonce
+    pub fn encrypt(&self, plain: &[u8]) -> Result<String, AppError> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let mut out = nonce.to_vec();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plain)
+            .map_err(|err| {
+                error!("encrypt data error {}", err);
+                errors::ErrDataCipher.clone()
+            })?;
+        out.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(out))
+    }
+
+    /// Decrypts data produced by [`DataCipher::encrypt`]
+    pub fn decrypt(&self, data: &str) -> Result<Vec<u8>, AppError> {
+        let raw = STANDARD.decode(data).map_err(|err| {
+            error!("decode encrypted data error {}", err);
+            errors::ErrDataCipher.clone()
+        })?;
+        if raw.len() < NONCE_LEN {
+            error!("encrypted data too short {}", raw.len());
+            return Err(errors::ErrDataCipher.clone());
+        }
+        let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|err| {
+                error!("decrypt data error {}", err);
+                errors::ErrDataCipher.clone()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> DataCipher {
+        CryptoConf {
+            data_key: STANDARD.encode([7u8; 32]),
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = cipher();
+        let encrypted = cipher.encrypt(b"session key").unwrap();
+        assert_ne!(encrypted, cipher.encrypt(b"session key").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"session key");
+    }
+
+    #[test]
+    fn test_tampered_data_rejected() {
+        let cipher = cipher();
+        let mut raw = STANDARD.decode(cipher.encrypt(b"secret").unwrap()).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        assert!(cipher.decrypt(&STANDARD.encode(raw)).is_err());
+        assert!(cipher.decrypt("short").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_length() {
+        let conf = CryptoConf {
+            data_key: STANDARD.encode([1u8; 16]),
+        };
+        assert!(conf.build().is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use derivative::Derivative;
+use hmac::Hmac;
+use hmac::Mac;
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::debug;
+
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Bytes of the HMAC-SHA256 tag kept in a cursor
+const TAG_LEN: usize = 16;
+
+/// Shortest accepted signing secret in bytes
+const MIN_SECRET_LEN: usize = 16;
+
+/// Keyset pagination configuration
+#[derive(Derivative, Deserialize, Clone)]
+#[derivative(Debug)]
+pub struct CursorConf {
+    /// Secret signing the cursors handed to clients, at least 16 bytes
+    ///
+    /// Changing it invalidates the cursors in use, clients start over from the first slice.
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub secret: String,
+}
+
+impl CursorConf {
+    /// Builds the cursor signer
+    ///
+    /// # Returns
+    /// - `Ok(Cursors)` when the secret is long enough
+    /// - `Err(anyhow::Error)` otherwise
+    pub fn build(&self) -> anyhow::Result<Cursors> {
+        if self.secret.len() < MIN_SECRET_LEN {
+            anyhow::bail!("cursor secret must be at least {} bytes", MIN_SECRET_LEN);
+        }
+        Ok(Cursors {
+            secret: Arc::new(self.secret.as_bytes().to_vec()),
+        })
+    }
+}
+
+/// Opaque cursors of keyset pagination
+///
+/// A cursor is `base64url(key || tag)`: the key of the last row a client received, which the
+/// next slice starts after, and a truncated HMAC-SHA256 of the key and of the listing `scope`.
+/// The key is the id of the row, or its relevance and id for a search ranked by relevance.
+/// Clients can neither forge a position nor replay a cursor against another listing or search.
+#[derive(Clone)]
+pub struct Cursors {
+    secret: Arc<Vec<u8>>,
+}
+
+impl Cursors {
+    /// Encodes the position after the row `id` of the listing `scope`
+    pub fn encode(&self, scope: &str, id: i64) -> String {
+        self.seal(scope, &id.to_be_bytes())
+    }
+
+    /// Decodes a cursor of the listing `scope` back to the id of the last row received
+    ///
+    /// # Returns
+    /// * `Result<i64, AppError>` - `ErrInvalidCursor` when the cursor was altered or belongs to
+    ///   another listing
+    pub fn decode(&self, scope: &str, cursor: &str) -> Result<i64, AppError> {
+        let key: [u8; 8] = self.open(scope, cursor)?;
+        Ok(i64::from_be_bytes(key))
+    }
+
+    /// Encodes the position after the row `id` of relevance `score` of the search `scope`
+    pub fn encode_ranked(&self, scope: &str, score: f64, id: i64) -> String {
+        let mut key = score.to_be_bytes().to_vec();
+        key.extend_from_slice(&id.to_be_bytes());
+        self.seal(scope, &key)
+    }
+
+    /// Decodes a cursor of the search `scope` back to the relevance and id of the last row
+    /// received
+    ///
+    /// # Returns
+    /// * `Result<(f64, i64), AppError>` - `ErrInvalidCursor` when the cursor was altered or
+    ///   belongs to another listing
+    pub fn decode_ranked(&self, scope: &str, cursor: &str) -> Result<(f64, i64), AppError> {
+        let key: [u8; 16] = self.open(scope, cursor)?;
+        let (score, id) = key.split_at(8);
+        let score = f64::from_be_bytes(score.try_into().unwrap_or_default());
+        let id = i64::from_be_bytes(id.try_into().unwrap_or_default());
+        Ok((score, id))
+    }
+
+    fn seal(&self, scope: &str, key: &[u8]) -> String {
+        let mut raw = key.to_vec();
+        raw.extend_from_slice(&self.mac(scope, key).finalize().into_bytes()[..TAG_LEN]);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// Checks the tag of a cursor and returns its key of `N` bytes
+    fn open<const N: usize>(&self, scope: &str, cursor: &str) -> Result<[u8; N], AppError> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| errors::ErrInvalidCursor.clone())?;
+        if raw.len() != N + TAG_LEN {
+            return Err(errors::ErrInvalidCursor.clone());
+        }
+        let (key, tag) = raw.split_at(N);
+        self.mac(scope, key)
+            .verify_truncated_left(tag)
+            .map_err(|_| {
+                debug!("cursor signature mismatch for scope {}", scope);
+                errors::ErrInvalidCursor.clone()
+            })?;
+        key.try_into().map_err(|_| errors::ErrInvalidCursor.clone())
+    }
+
+    fn mac(&self, scope: &str, key: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(scope.as_bytes());
+        mac.update(&[0]);
+        mac.update(key);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursors() -> Cursors {
+        CursorConf {
+            secret: "unit-test-cursor-secret".to_string(),
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursors = cursors();
+        let cursor = cursors.encode("users", 42);
+        assert!(!cursor.contains(['+', '/', '=']));
+        assert_eq!(cursors.decode("users", &cursor).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_cursor_rejected() {
+        let cursors = cursors();
+        let cursor = cursors.encode("users", 42);
+        // another listing or search
+        assert!(cursors.decode("users\0li", &cursor).is_err());
+        // a forged position
+        let mut raw = URL_SAFE_NO_PAD.decode(&cursor).unwrap();
+        raw[7] ^= 1;
+        assert!(
+            cursors
+                .decode("users", &URL_SAFE_NO_PAD.encode(raw))
+                .is_err()
+        );
+        assert!(cursors.decode("users", "not a cursor").is_err());
+        assert!(cursors.decode("users", "").is_err());
+    }
+
+    #[test]
+    fn test_ranked_cursor() {
+        let cursors = cursors();
+        let cursor = cursors.encode_ranked("users-search\0li", 1.25, 42);
+        assert_eq!(cursors.decode_ranked("users-search\0li", &cursor).unwrap(), (1.25, 42));
+        assert!(
+            cursors
+                .decode_ranked("users-search\0wang", &cursor)
+                .is_err()
+        );
+        // the key of an id cursor is shorter
+        assert!(cursors.decode("users-search\0li", &cursor).is_err());
+        let cursor = cursors.encode("users-search\0li", 42);
+        assert!(cursors.decode_ranked("users-search\0li", &cursor).is_err());
+    }
+
+    #[test]
+    fn test_short_secret() {
+        let conf = CursorConf {
+            secret: "short".to_string(),
+        };
+        assert!(conf.build().is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use axum::extract::FromRequestParts;
+use axum::http::HeaderValue;
+use axum::http::header::ETAG;
+use axum::http::header::IF_MATCH;
+use axum::http::request::Parts;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use serde::Serialize;
+
+use crate::core::rest::AppError;
+use crate::core::rest::AppResult;
+use crate::errors;
+
+/// Strong entity tag of a row version, e.g. `"3"`
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// `If-Match` header of a conditional update
+///
+/// Updates of versioned rows require it: a request without the header is refused with
+/// `ErrPreconditionRequired` (428), one whose tags do not name the current version with
+/// `ErrVersionConflict` (412). Weak tags never match, as RFC 9110 asks for `If-Match`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    /// `*`, whatever the current version
+    Any,
+    /// Versions the client read the row at
+    Versions(Vec<i64>),
+}
+
+impl IfMatch {
+    /// Parses the header value, `None` when it holds no tag at all
+    fn parse(value: &str) -> Option<IfMatch> {
+        if value.trim() == "*" {
+            return Some(IfMatch::Any);
+        }
+        let tags: Vec<&str> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        if tags.is_empty() {
+            return None;
+        }
+        let versions = tags
+            .into_iter()
+            .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+            .collect();
+        Some(IfMatch::Versions(versions))
+    }
+
+    /// Checks the header against the `current` version of the row
+    ///
+    /// # Returns
+    /// * `Result<i64, AppError>` - The version to update the row at, `ErrVersionConflict` when
+    ///   the client did not read the current one
+    pub fn check(&self, current: i64) -> Result<i64, AppError> {
+        match self {
+            IfMatch::Versions(versions) if !versions.contains(&current) => {
+                Err(errors::ErrVersionConflict.clone())
+            }
+            _ => Ok(current),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(IF_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(IfMatch::parse)
+            .ok_or_else(|| errors::ErrPreconditionRequired.clone())
+    }
+}
+
+/// A row answered in the `AppResult` envelope with the `ETag` of its version
+pub struct Versioned<T: Serialize> {
+    data: T,
+    version: i64,
+}
+
+impl<T: Serialize> Versioned<T> {
+    pub fn new(data: T, version: i64) -> Versioned<T> {
+        Versioned { data, version }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Versioned<T> {
+    fn into_response(self) -> Response {
+        let mut resp = AppResult(self.data).into_response();
+        if let Ok(value) = HeaderValue::from_str(&etag(self.version)) {
+            resp.headers_mut().insert(ETAG, value);
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    async fn if_match(value: Option<&str>) -> Result<IfMatch, AppError> {
+        let mut req = Request::builder();
+        if let Some(value) = value {
+            req = req.header(IF_MATCH, value);
+        }
+        let (mut parts, _) = req.body(()).unwrap().into_parts();
+        IfMatch::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_if_match_header() {
+        assert_eq!(if_match(Some("*")).await.unwrap(), IfMatch::Any);
+        assert_eq!(if_match(Some("\"3\", \"4\"")).await.unwrap(), IfMatch::Versions(vec![3, 4]));
+        // weak or foreign tags are kept as a header which matches nothing
+        assert_eq!(if_match(Some("W/\"3\"")).await.unwrap(), IfMatch::Versions(vec![]));
+        let err = if_match(None).await.unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrPreconditionRequired.err_no());
+        assert!(if_match(Some(" ")).await.is_err());
+    }
+
+    #[test]
+    fn test_if_match_check() {
+        assert_eq!(IfMatch::Any.check(5).unwrap(), 5);
+        assert_eq!(IfMatch::Versions(vec![4, 5]).check(5).unwrap(), 5);
+        let err = IfMatch::Versions(vec![4]).check(5).unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrVersionConflict.err_no());
+    }
+
+    #[test]
+    fn test_versioned_etag() {
+        let resp = Versioned::new("user", 3).into_response();
+        assert_eq!(resp.headers()[ETAG], "\"3\"");
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use image::ImageDecoder;
+use image::ImageError;
+use image::ImageFormat;
+use image::ImageReader;
+use image::Limits;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use serde::Deserialize;
+use tracing::error;
+use tracing::info;
+
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Bounds of the images accepted from clients
+///
+/// Both are checked against the dimensions in the header, before a single pixel is allocated,
+/// so a small file announcing a huge picture (a decompression bomb) is refused cheaply.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeLimits {
+    /// Largest accepted width or height in pixels
+    pub max_dimension: u32,
+    /// Largest accepted number of pixels
+    pub max_pixels: u64,
+}
+
+/// Format of generated thumbnails
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    Webp,
+    Jpeg,
+}
+
+impl ThumbnailFormat {
+    pub fn format(&self) -> ImageFormat {
+        match self {
+            ThumbnailFormat::Webp => ImageFormat::WebP,
+            ThumbnailFormat::Jpeg => ImageFormat::Jpeg,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Webp => "image/webp",
+            ThumbnailFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Decodes an untrusted image of a known format
+///
+/// The EXIF orientation is applied to the pixels, which is the only metadata worth keeping:
+/// the decoded image carries none, so encoding it again drops location, camera and the rest.
+/// Animated images keep their first frame.
+///
+/// # Arguments
+/// * `data` - Content of the file
+/// * `format` - Format recognized from the content
+/// * `limits` - Bounds of the accepted dimensions
+///
+/// # Returns
+/// * `Result<DynamicImage, AppError>` - `ErrImageDimensions` when the image exceeds `limits` and
+///   `ErrImageCorrupt` when it cannot be decoded
+pub fn decode(
+    data: &[u8],
+    format: ImageFormat,
+    limits: DecodeLimits,
+) -> Result<DynamicImage, AppError> {
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    let mut decoder_limits = Limits::default();
+    decoder_limits.max_image_width = Some(limits.max_dimension);
+    decoder_limits.max_image_height = Some(limits.max_dimension);
+    reader.limits(decoder_limits);
+
+    let mut decoder = reader.into_decoder().map_err(decode_error)?;
+    let (width, height) = decoder.dimensions();
+    if width > limits.max_dimension
+        || height > limits.max_dimension
+        || u64::from(width) * u64::from(height) > limits.max_pixels
+    {
+        info!("refuse {:?} image of {}x{} pixels", format, width, height);
+        return Err(errors::ErrImageDimensions.clone());
+    }
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Encodes an image without any metadata
+///
+/// JPEG has no alpha channel and is encoded from RGB at `jpeg_quality`, other formats from RGBA.
+/// WebP is encoded lossless.
+///
+/// # Returns
+/// * `Result<Vec<u8>, AppError>` - Content of the file, `ErrImageEncode` on failure
+pub fn encode(
+    image: &DynamicImage,
+    format: ImageFormat,
+    jpeg_quality: u8,
+) -> Result<Vec<u8>, AppError> {
+    let mut out = Cursor::new(Vec::new());
+    let encoded = match format {
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, jpeg_quality)),
+        format => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut out, format),
+    };
+    encoded.map_err(|err| {
+        error!("encode {:?} image error {}", format, err);
+        errors::ErrImageEncode.clone()
+    })?;
+    Ok(out.into_inner())
+}
+
+/// Square thumbnail of `size` pixels, the image being scaled to cover it and centered
+pub fn thumbnail(image: &DynamicImage, size: u32) -> DynamicImage {
+    image.resize_to_fill(size, size, FilterType::Lanczos3)
+}
+
+fn decode_error(err: ImageError) -> AppError {
+    info!("decode image error {}", err);
+    match err {
+        ImageError::Limits(_) => errors::ErrImageDimensions.clone(),
+        _ => errors::ErrImageCorrupt.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::GenericImageView;
+    use image::Rgb;
+    use image::RgbImage;
+
+    const LIMITS: DecodeLimits = DecodeLimits {
+        max_dimension: 64,
+        max_pixels: 64 * 64,
+    };
+
+    /// A JPEG of 8x4 pixels whose EXIF asks for a 90° clockwise rotation
+    fn rotated_jpeg() -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([200, 10, 10])));
+        let jpeg = encode(&image, ImageFormat::Jpeg, 90).unwrap();
+        // APP1 segment: "Exif\0\0", little-endian TIFF header and an IFD with Orientation = 6
+        let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(b"\x12\x01\x03\0\x01\0\0\0\x06\0\0\0\0\0\0\0");
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&exif);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    #[test]
+    fn test_decode_applies_and_strips_orientation() {
+        let data = rotated_jpeg();
+        let image = decode(&data, ImageFormat::Jpeg, LIMITS).unwrap();
+        assert_eq!(image.dimensions(), (4, 8));
+
+        let encoded = encode(&image, ImageFormat::Jpeg, 90).unwrap();
+        assert!(!encoded.windows(4).any(|window| window == b"Exif"));
+        let again = decode(&encoded, ImageFormat::Jpeg, LIMITS).unwrap();
+        assert_eq!(again.dimensions(), (4, 8));
+    }
+
+    #[test]
+    fn test_decode_refuses_large_images() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(128, 8));
+        let png = encode(&image, ImageFormat::Png, 90).unwrap();
+        let err = decode(&png, ImageFormat::Png, LIMITS).unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrImageDimensions.err_no());
+
+        let image = DynamicImage::ImageRgb8(RgbImage::new(64, 64));
+        let png = encode(&image, ImageFormat::Png, 90).unwrap();
+        let limits = DecodeLimits {
+            max_pixels: 1024,
+            ..LIMITS
+        };
+        let err = decode(&png, ImageFormat::Png, limits).unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrImageDimensions.err_no());
+    }
+
+    #[test]
+    fn test_decode_refuses_corrupt_images() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+        let png = encode(&image, ImageFormat::Png, 90).unwrap();
+        let err = decode(&png[..png.len() / 2], ImageFormat::Png, LIMITS).unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrImageCorrupt.err_no());
+        let err = decode(b"\x89PNG\r\n\x1a\ngarbage", ImageFormat::Png, LIMITS).unwrap_err();
+        assert_eq!(err.err_no(), errors::ErrImageCorrupt.err_no());
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(40, 20));
+        for format in [ThumbnailFormat::Webp, ThumbnailFormat::Jpeg] {
+            let data = encode(&thumbnail(&image, 16), format.format(), 80).unwrap();
+            let decoded = decode(&data, format.format(), LIMITS).unwrap();
+            assert_eq!(decoded.dimensions(), (16, 16));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt;
+
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Commands;
+use redis::Script;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+use tracing::info;
+
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Counts a failure and turns it into a lockout once the threshold is reached
+///
+/// Returns `{failures, 0}` with the failures counted in the window, or `{0, locks}` when this
+/// failure locks the subject, `locks` being the number of lockouts in the escalation window.
+const FAIL_SCRIPT: &str = r#"
+local failures = redis.call('INCR', KEYS[1])
+if failures == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+if tonumber(ARGV[2]) > 0 and failures >= tonumber(ARGV[2]) then
+    redis.call('DEL', KEYS[1])
+    local locks = redis.call('INCR', KEYS[2])
+    redis.call('EXPIRE', KEYS[2], ARGV[3])
+    return {0, locks}
+end
+return {failures, 0}
+"#;
+
+/// Brute-force protection configuration, shared by every password and code check
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct LockoutConf {
+    /// Failures of an account before each further attempt is delayed, 0 disables the delays
+    #[default(3)]
+    pub delay_after: i64,
+
+    /// First delay in seconds, doubled by each further failure
+    #[default(1)]
+    pub delay_secs: i64,
+
+    /// Failures of an account within the window that lock it, 0 disables the lockout
+    #[default(10)]
+    pub account_max_failures: i64,
+
+    /// Failures from a client IP within the window that lock it, 0 disables the lockout
+    #[default(50)]
+    pub ip_max_failures: i64,
+
+    /// Period failures are counted over, in seconds
+    #[default(900)]
+    pub window_secs: i64,
+
+    /// Duration of a first lockout in seconds, doubled by each further lockout
+    #[default(900)]
+    pub lockout_secs: i64,
+
+    /// Longest lockout in seconds
+    #[default(86400)]
+    pub max_lockout_secs: i64,
+
+    /// Period lockouts are remembered over to escalate the next one, in seconds
+    #[default(86400)]
+    pub escalation_window_secs: i64,
+}
+
+/// What failed attempts are counted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject<'a> {
+    /// Account of a logged-in user
+    User(i64),
+    /// Normalized email address of an account, known or not
+    Email(&'a str),
+    /// Normalized phone number of an account, known or not
+    Phone(&'a str),
+    /// Client IP
+    Ip(&'a str),
+}
+
+impl fmt::Display for Subject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::User(id) => write!(f, "user_{}", id),
+            Subject::Email(email) => write!(f, "email_{}", email),
+            Subject::Phone(phone) => write!(f, "phone_{}", phone),
+            Subject::Ip(ip) => write!(f, "ip_{}", ip),
+        }
+    }
+}
+
+/// Progressive delays and temporary lockouts after failed password and code checks
+///
+/// For every subject (an account or a client IP) it keeps:
+/// - `lockout_failures_{subject}` counting the failures of the window
+/// - `lockout_locks_{subject}` counting the lockouts of the escalation window
+/// - `lockout_blocked_{subject}` whose TTL is the time left before the next attempt
+///
+/// Accounts get a delay doubling with each failure past `delay_after`, then a lockout. Client
+/// IPs, possibly shared by many users, are only locked at their own higher threshold.
+#[derive(Debug, Clone)]
+pub struct Lockouts {
+    conf: LockoutConf,
+}
+
+impl Lockouts {
+    pub fn new(conf: LockoutConf) -> Lockouts {
+        Lockouts { conf }
+    }
+
+    /// Fails while one of the subjects is delayed or locked
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `subjects` - Account and client IP of the attempt
+    ///
+    /// # Returns
+    /// * `Result<(), AppError>` - `ErrTooManyFailedAttempts` with the seconds to wait
+    pub fn check(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        subjects: &[Subject],
+    ) -> Result<(), AppError> {
+        let mut wait = 0;
+        for subject in subjects {
+            let ttl: i64 = conn.ttl(blocked_key(subject)).map_err(redis_error)?;
+            wait = wait.max(ttl);
+        }
+        if wait > 0 {
+            return Err(errors::ErrTooManyFailedAttempts
+                .clone()
+                .with_retry_after(wait as u64));
+        }
+        Ok(())
+    }
+
+    /// Counts a failed attempt against every subject, delaying or locking them
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `subjects` - Account and client IP of the attempt
+    pub fn fail(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        subjects: &[Subject],
+    ) -> Result<(), AppError> {
+        for subject in subjects {
+            let is_ip = matches!(subject, Subject::Ip(_));
+            let max_failures = match is_ip {
+                true => self.conf.ip_max_failures,
+                false => self.conf.account_max_failures,
+            };
+            let (failures, locks): (i64, i64) = Script::new(FAIL_SCRIPT)
+                .key(failures_key(subject))
+                .key(locks_key(subject))
+                .arg(self.conf.window_secs.max(1))
+                .arg(max_failures)
+                .arg(self.conf.escalation_window_secs.max(1))
+                .invoke(&mut **conn)
+                .map_err(redis_error)?;
+            let secs = self.penalty_secs(failures, locks, !is_ip);
+            if secs > 0 {
+                let _: () = conn
+                    .set_ex(blocked_key(subject), 1, secs as u64)
+                    .map_err(redis_error)?;
+            }
+            if failures == 0 {
+                info!("{} locked for {}s after too many failures", subject, secs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the failures of an account after a successful attempt
+    ///
+    /// Lockouts are still remembered to escalate the next one.
+    pub fn succeed(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        subject: &Subject,
+    ) -> Result<(), AppError> {
+        let _: () = conn.del(failures_key(subject)).map_err(redis_error)?;
+        Ok(())
+    }
+
+    /// Lifts the delays and lockouts of the subjects and forgets their failures
+    ///
+    /// # Returns
+    /// * `Result<bool, AppError>` - Whether one of the subjects was blocked
+    pub fn unlock(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        subjects: &[Subject],
+    ) -> Result<bool, AppError> {
+        let mut unlocked = false;
+        for subject in subjects {
+            let blocked: i64 = conn.del(blocked_key(subject)).map_err(redis_error)?;
+            let _: () = conn
+                .del(&[failures_key(subject), locks_key(subject)])
+                .map_err(redis_error)?;
+            unlocked |= blocked > 0;
+        }
+        Ok(unlocked)
+    }
+
+    /// Seconds a subject is blocked after a failure
+    ///
+    /// `failures` is the number of failures in the window, or 0 when the failure locked the
+    /// subject for the `locks`th time. Only accounts get `delays` before the lockout.
+    fn penalty_secs(&self, failures: i64, locks: i64, delays: bool) -> i64 {
+        if failures == 0 {
+            let doublings = (locks - 1).clamp(0, 30) as u32;
+            return self
+                .conf
+                .lockout_secs
+                .saturating_mul(1 << doublings)
+                .min(self.conf.max_lockout_secs);
+        }
+        if !delays || self.conf.delay_after <= 0 || failures < self.conf.delay_after {
+            return 0;
+        }
+        let doublings = (failures - self.conf.delay_after).min(30) as u32;
+        self.conf
+            .delay_secs
+            .saturating_mul(1 << doublings)
+            .min(self.conf.lockout_secs)
+    }
+}
+
+fn failures_key(subject: &Subject) -> String {
+    format!("lockout_failures_{}", subject)
+}
+
+fn blocked_key(subject: &Subject) -> String {
+    format!("lockout_blocked_{}", subject)
+}
+
+fn locks_key(subject: &Subject) -> String {
+    format!("lockout_locks_{}", subject)
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("lockout redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_keys() {
+        assert_eq!(blocked_key(&Subject::User(7)), "lockout_blocked_user_7");
+        assert_eq!(
+            locks_key(&Subject::Email("li@example.com")),
+            "lockout_locks_email_li@example.com"
+        );
+        assert_eq!(Subject::Phone("+8613800138000").to_string(), "phone_+8613800138000");
+        assert_eq!(Subject::Ip("203.0.113.7").to_string(), "ip_203.0.113.7");
+    }
+
+    #[test]
+    fn test_penalty_secs() {
+        let lockouts = Lockouts::new(LockoutConf::default());
+        // delays double from the third failure and never exceed a lockout
+        assert_eq!(lockouts.penalty_secs(2, 0, true), 0);
+        assert_eq!(lockouts.penalty_secs(3, 0, true), 1);
+        assert_eq!(lockouts.penalty_secs(4, 0, true), 2);
+        assert_eq!(lockouts.penalty_secs(9, 0, true), 64);
+        assert_eq!(lockouts.penalty_secs(40, 0, true), 900);
+        // client IPs are not delayed
+        assert_eq!(lockouts.penalty_secs(9, 0, false), 0);
+        // lockouts double up to the longest one
+        assert_eq!(lockouts.penalty_secs(0, 1, true), 900);
+        assert_eq!(lockouts.penalty_secs(0, 2, false), 1800);
+        assert_eq!(lockouts.penalty_secs(0, 10, true), 86400);
+        assert_eq!(lockouts.penalty_secs(0, 100, true), 86400);
+    }
+}
@@ -1,11 +1,36 @@
+pub mod api_key;
+pub mod auth;
+pub mod client_ip;
+pub mod crypto;
+pub mod cursor;
+pub mod etag;
+pub mod image;
+pub mod jwt;
+pub mod lockout;
+pub mod page;
+pub mod password;
+pub mod patch;
+pub mod rbac;
+pub mod reset_token;
 pub mod rest;
+pub mod session;
 pub mod state;
+pub mod totp;
+pub mod verify_code;
 
+use crate::core::etag::Versioned;
+use crate::core::page::PageResponse;
 use crate::core::rest::AppError;
 use crate::core::rest::AppResult;
 
 pub type Result<T> = core::result::Result<AppResult<T>, AppError>;
 
+/// Result of list endpoints, a page with its `Link` header
+pub type PageResult<T> = core::result::Result<PageResponse<T>, AppError>;
+
+/// Result of endpoints returning a versioned row, with its `ETag` header
+pub type VersionedResult<T> = core::result::Result<Versioned<T>, AppError>;
+
 /// ok!(a) equal Ok(AppResult(a))
 #[macro_export]
 macro_rules! ok {
@@ -0,0 +1,227 @@
+use std::fmt::Display;
+
+use axum::http::HeaderValue;
+use axum::http::Uri;
+use axum::http::header::LINK;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use serde::Serialize;
+
+use crate::core::rest::AppResult;
+
+/// One page of a list endpoint
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    /// Items of the page
+    pub list: Vec<T>,
+    /// Total number of items
+    pub total: i64,
+    /// Current page number (starts from 1)
+    pub page_no: usize,
+    /// Number of items per page
+    pub page_size: usize,
+    /// Whether pages follow this one
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    pub fn new(list: Vec<T>, total: i64, page_no: usize, page_size: usize) -> Page<T> {
+        let seen = page_no.saturating_mul(page_size) as i64;
+        Page {
+            list,
+            total,
+            page_no,
+            page_size,
+            has_more: seen < total,
+        }
+    }
+
+    /// Number of the last page, 1 for an empty list
+    pub fn last_page_no(&self) -> usize {
+        (self.total.max(0) as usize)
+            .div_ceil(self.page_size.max(1))
+            .max(1)
+    }
+
+    /// RFC 8288 `Link` header value with the `first`, `prev`, `next` and `last` pages
+    ///
+    /// Links repeat the path and query of `uri` with another `page_no`, relative to the request.
+    pub fn links(&self, uri: &Uri) -> String {
+        let last = self.last_page_no();
+        let mut pages = vec![("first", 1)];
+        if self.page_no > 1 {
+            pages.push(("prev", (self.page_no - 1).min(last)));
+        }
+        if self.has_more {
+            pages.push(("next", self.page_no + 1));
+        }
+        pages.push(("last", last));
+        pages
+            .into_iter()
+            .map(|(rel, page_no)| {
+                format!("<{}>; rel=\"{}\"", with_param(uri, "page_no", page_no), rel)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// One slice of a list endpoint in cursor mode
+///
+/// Clients pass `next_cursor` back as `?cursor=` to get the following slice. Unlike page
+/// numbers, cursors stay on the same rows while new ones are inserted.
+#[derive(Debug, Serialize)]
+pub struct CursorPage<T> {
+    /// Items of the slice
+    pub list: Vec<T>,
+    /// Cursor of the next slice, `None` at the end of the list
+    pub next_cursor: Option<String>,
+    /// Whether items follow this slice
+    pub has_more: bool,
+}
+
+impl<T> CursorPage<T> {
+    /// Builds a slice from up to `limit + 1` rows, an extra row only telling that more follow
+    ///
+    /// `cursor` encodes the position after the last row of the slice.
+    pub fn new(mut rows: Vec<T>, limit: usize, cursor: impl FnOnce(&T) -> String) -> CursorPage<T> {
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+        let next_cursor = match has_more {
+            true => rows.last().map(cursor),
+            false => None,
+        };
+        CursorPage {
+            list: rows,
+            next_cursor,
+            has_more,
+        }
+    }
+
+    /// RFC 8288 `Link` header value with the `next` slice, `None` at the end of the list
+    pub fn links(&self, uri: &Uri) -> Option<String> {
+        self.next_cursor
+            .as_ref()
+            .map(|cursor| format!("<{}>; rel=\"next\"", with_param(uri, "cursor", cursor)))
+    }
+}
+
+/// Path and query of `uri` with the `name` parameter replaced
+fn with_param(uri: &Uri, name: &str, value: impl Display) -> String {
+    let mut query: Vec<String> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(name))
+        .map(str::to_string)
+        .collect();
+    query.push(format!("{}={}", name, value));
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+/// A page or a slice answered in the `AppResult` envelope with its `Link` header
+pub struct PageResponse<T: Serialize> {
+    body: PageBody<T>,
+    uri: Uri,
+}
+
+enum PageBody<T> {
+    Pages(Page<T>),
+    Cursor(CursorPage<T>),
+}
+
+impl<T: Serialize> PageResponse<T> {
+    /// Answers `page` to a request for `uri`
+    pub fn new(page: Page<T>, uri: Uri) -> PageResponse<T> {
+        PageResponse {
+            body: PageBody::Pages(page),
+            uri,
+        }
+    }
+
+    /// Answers the cursor mode `page` to a request for `uri`
+    pub fn cursor(page: CursorPage<T>, uri: Uri) -> PageResponse<T> {
+        PageResponse {
+            body: PageBody::Cursor(page),
+            uri,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for PageResponse<T> {
+    fn into_response(self) -> Response {
+        let (links, mut resp) = match self.body {
+            PageBody::Pages(page) => (Some(page.links(&self.uri)), AppResult(page).into_response()),
+            PageBody::Cursor(page) => (page.links(&self.uri), AppResult(page).into_response()),
+        };
+        if let Some(value) = links.and_then(|links| HeaderValue::from_str(&links).ok()) {
+            resp.headers_mut().insert(LINK, value);
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_has_more() {
+        assert!(Page::new(vec![1, 2], 5, 1, 2).has_more);
+        assert!(Page::new(vec![3, 4], 5, 2, 2).has_more);
+        assert!(!Page::new(vec![5], 5, 3, 2).has_more);
+        assert!(!Page::<i32>::new(vec![], 0, 1, 20).has_more);
+        assert_eq!(Page::<i32>::new(vec![], 0, 1, 20).last_page_no(), 1);
+        assert_eq!(Page::<i32>::new(vec![], 41, 1, 20).last_page_no(), 3);
+    }
+
+    #[test]
+    fn test_page_links() {
+        let uri: Uri = "/users?page_no=2&page_size=2&q=%E6%9D%8E".parse().unwrap();
+        let page = Page::new(vec![3, 4], 5, 2, 2);
+        assert_eq!(
+            page.links(&uri),
+            "</users?page_size=2&q=%E6%9D%8E&page_no=1>; rel=\"first\", \
+             </users?page_size=2&q=%E6%9D%8E&page_no=1>; rel=\"prev\", \
+             </users?page_size=2&q=%E6%9D%8E&page_no=3>; rel=\"next\", \
+             </users?page_size=2&q=%E6%9D%8E&page_no=3>; rel=\"last\""
+        );
+
+        let uri: Uri = "/users".parse().unwrap();
+        let page = Page::<i32>::new(vec![], 0, 1, 20);
+        assert_eq!(
+            page.links(&uri),
+            "</users?page_no=1>; rel=\"first\", </users?page_no=1>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn test_page_response_header() {
+        let uri: Uri = "/users?page_no=1&page_size=1".parse().unwrap();
+        let resp = PageResponse::new(Page::new(vec![1], 2, 1, 1), uri).into_response();
+        assert_eq!(
+            resp.headers()[LINK],
+            "</users?page_size=1&page_no=1>; rel=\"first\", \
+             </users?page_size=1&page_no=2>; rel=\"next\", \
+             </users?page_size=1&page_no=2>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn test_cursor_page() {
+        let page = CursorPage::new(vec![9, 8, 7], 2, |id| format!("after{}", id));
+        assert_eq!(page.list, [9, 8]);
+        assert_eq!(page.next_cursor.as_deref(), Some("after8"));
+        assert!(page.has_more);
+
+        let uri: Uri = "/users?cursor=after10&q=li".parse().unwrap();
+        let resp = PageResponse::cursor(page, uri).into_response();
+        assert_eq!(resp.headers()[LINK], "</users?q=li&cursor=after8>; rel=\"next\"");
+
+        let page = CursorPage::new(vec![9, 8], 2, |id| format!("after{}", id));
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+        let resp = PageResponse::cursor(page, "/users?cursor=".parse().unwrap()).into_response();
+        assert!(resp.headers().get(LINK).is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use argon2::Algorithm;
+use argon2::Argon2;
+use argon2::Params;
+use argon2::PasswordHash;
+use argon2::PasswordHasher;
+use argon2::PasswordVerifier;
+use argon2::Version;
+use argon2::password_hash::SaltString;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use subtle::ConstantTimeEq;
+use tracing::error;
+use tracing::warn;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::utils::HashAlgorithm;
+
+/// Argon2id password hashing configuration
+///
+/// Defaults follow the OWASP recommendation (19 MiB memory, 2 iterations, 1 lane).
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+pub struct PasswordConf {
+    /// Memory cost in KiB
+    #[default(19456)]
+    pub memory_kib: u32,
+
+    /// Number of iterations
+    #[default(2)]
+    pub iterations: u32,
+
+    /// Degree of parallelism (lanes)
+    #[default(1)]
+    pub parallelism: u32,
+}
+
+impl PasswordConf {
+    /// Validates the Argon2 parameters and builds the password hasher
+    ///
+    /// # Returns
+    /// - `Ok(Passwords)` when the parameters are accepted by Argon2
+    /// - `Err(anyhow::Error)` if any parameter is out of range
+    pub fn build(&self) -> anyhow::Result<Passwords> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|err| anyhow::anyhow!("invalid argon2 params {}", err))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        // hash used to spend the same time when the account does not exist
+        let dummy_hash = argon2
+            .hash_password(b"dummy password", &gen_salt())
+            .map_err(|err| anyhow::anyhow!("build dummy password hash error {}", err))?
+            .to_string();
+        Ok(Passwords {
+            inner: Arc::new(InnerPasswords { argon2, dummy_hash }),
+        })
+    }
+}
+
+/// Result of checking a password against a stored hash
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerdict {
+    /// The password does not match
+    Mismatch,
+    /// The password matches and the stored hash is up to date
+    Match,
+    /// The password matches but the stored hash is a legacy salted digest or uses outdated
+    /// Argon2 parameters and should be replaced
+    MatchNeedsRehash,
+}
+
+/// Argon2id password hasher shared by the whole application
+///
+/// Hashing is CPU and memory intensive, so every operation runs on the blocking thread pool.
+#[derive(Clone)]
+pub struct Passwords {
+    inner: Arc<InnerPasswords>,
+}
+
+struct InnerPasswords {
+    argon2: Argon2<'static>,
+    dummy_hash: String,
+}
+
+impl Passwords {
+    /// Hashes a password into a PHC string (`$argon2id$v=19$m=..,t=..,p=..$salt$hash`)
+    ///
+    /// # Arguments
+    /// * `password` - Plain text password
+    ///
+    /// # Returns
+    /// * `Result<String, AppError>` - PHC string embedding algorithm, parameters and salt
+    pub async fn hash(&self, password: &str) -> Result<String, AppError> {
+        let inner = self.inner.clone();
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || {
+            inner
+                .argon2
+                .hash_password(password.as_bytes(), &gen_salt())
+                .map(|hash| hash.to_string())
+                .map_err(|err| {
+                    error!("hash password error {}", err);
+                    errors::ErrPasswordHash.clone()
+                })
+        })
+        .await
+        .map_err(|err| {
+            error!("hash password task error {}", err);
+            errors::ErrPasswordHash.clone()
+        })?
+    }
+
+    /// Verifies a password in constant time
+    ///
+    /// Argon2 PHC strings are verified with Argon2. Any other non-empty value is treated as a
+    /// legacy `hex(digest(salt + password))`, the digest being chosen from the hex length (MD5,
+    /// SHA1, SHA256 or SHA512).
+    ///
+    /// # Arguments
+    /// * `password` - Plain text password sent by the client
+    /// * `stored` - Value of `user_info.password`
+    /// * `salt` - Value of `user_info.salt`, only used by legacy hashes
+    ///
+    /// # Returns
+    /// * `Result<PasswordVerdict, AppError>` - Whether the password matches and the hash should be
+    ///   upgraded
+    pub async fn verify(
+        &self,
+        password: &str,
+        stored: &str,
+        salt: &str,
+    ) -> Result<PasswordVerdict, AppError> {
+        let inner = self.inner.clone();
+        let password = password.to_string();
+        let stored = stored.to_string();
+        let salt = salt.to_string();
+        tokio::task::spawn_blocking(move || inner.verify(&password, &stored, &salt))
+            .await
+            .map_err(|err| {
+                error!("verify password task error {}", err);
+                errors::ErrPasswordHash.clone()
+            })
+    }
+
+    /// Burns the same amount of time as a real verification
+    ///
+    /// Call it when the account does not exist so response times do not reveal which emails are
+    /// registered.
+    pub async fn verify_dummy(&self, password: &str) {
+        let dummy_hash = self.inner.dummy_hash.clone();
+        let _ = self.verify(password, &dummy_hash, "").await;
+    }
+}
+
+impl InnerPasswords {
+    fn verify(&self, password: &str, stored: &str, salt: &str) -> PasswordVerdict {
+        if stored.is_empty() {
+            return PasswordVerdict::Mismatch;
+        }
+        if !stored.starts_with("$argon2") {
+            return verify_legacy(password, stored, salt);
+        }
+
+        let hash = match PasswordHash::new(stored) {
+            Ok(hash) => hash,
+            Err(err) => {
+                warn!("parse stored password hash error {}", err);
+                return PasswordVerdict::Mismatch;
+            }
+        };
+        if self
+            .argon2
+            .verify_password(password.as_bytes(), &hash)
+            .is_err()
+        {
+            return PasswordVerdict::Mismatch;
+        }
+
+        let current = self.argon2.params();
+        let outdated = hash.algorithm != argon2::ARGON2ID_IDENT
+            || Params::try_from(&hash)
+                .map(|params| {
+                    params.m_cost() != current.m_cost()
+                        || params.t_cost() != current.t_cost()
+                        || params.p_cost() != current.p_cost()
+                })
+                .unwrap_or(true);
+        if outdated {
+            PasswordVerdict::MatchNeedsRehash
+        } else {
+            PasswordVerdict::Match
+        }
+    }
+}
+
+/// Whether `stored` is a hash `Passwords::verify` understands, used to vet hashes imported from
+/// other systems: an Argon2 PHC string or the hex of a legacy MD5, SHA1, SHA256 or SHA512 digest
+pub fn is_known_hash(stored: &str) -> bool {
+    if stored.starts_with("$argon2") {
+        return PasswordHash::new(stored).is_ok_and(|hash| hash.hash.is_some());
+    }
+    matches!(stored.len(), 32 | 40 | 64 | 128) && stored.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Checks a legacy `hex(digest(salt + password))` hash
+fn verify_legacy(password: &str, stored: &str, salt: &str) -> PasswordVerdict {
+    let algorithm = match stored.len() {
+        32 => HashAlgorithm::MD5,
+        40 => HashAlgorithm::SHA1,
+        64 => HashAlgorithm::SHA256,
+        128 => HashAlgorithm::SHA512,
+        other => {
+            warn!("unknown legacy password hash length {}", other);
+            return PasswordVerdict::Mismatch;
+        }
+    };
+    let mut hasher = algorithm.hasher();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    let digest = hasher.finalize();
+
+    if bool::from(digest.as_bytes().ct_eq(stored.to_lowercase().as_bytes())) {
+        PasswordVerdict::MatchNeedsRehash
+    } else {
+        PasswordVerdict::Mismatch
+    }
+}
+
+/// Generates a random 16 bytes salt
+fn gen_salt() -> SaltString {
+    let bytes: [u8; 16] = rand::random();
+    SaltString::encode_b64(&bytes).expect("16 bytes salt is always valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_conf() -> PasswordConf {
+        PasswordConf {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hash_and_verify() {
+        let passwords = fast_conf().build().unwrap();
+        let hash = passwords.hash("correct horse").await.unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+
+        let verdict = passwords.verify("correct horse", &hash, "").await.unwrap();
+        assert_eq!(verdict, PasswordVerdict::Match);
+        let verdict = passwords.verify("wrong horse", &hash, "").await.unwrap();
+        assert_eq!(verdict, PasswordVerdict::Mismatch);
+    }
+
+    #[tokio::test]
+    async fn test_outdated_params_need_rehash() {
+        let old = fast_conf().build().unwrap();
+        let hash = old.hash("secret1").await.unwrap();
+
+        let current = PasswordConf {
+            iterations: 2,
+            ..fast_conf()
+        }
+        .build()
+        .unwrap();
+        let verdict = current.verify("secret1", &hash, "").await.unwrap();
+        assert_eq!(verdict, PasswordVerdict::MatchNeedsRehash);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_hash() {
+        let passwords = fast_conf().build().unwrap();
+        let mut hasher = HashAlgorithm::SHA256.hasher();
+        hasher.update(b"salt1234password");
+        let legacy = hasher.finalize();
+
+        let verdict = passwords
+            .verify("password", &legacy, "salt1234")
+            .await
+            .unwrap();
+        assert_eq!(verdict, PasswordVerdict::MatchNeedsRehash);
+        let verdict = passwords
+            .verify("password", &legacy, "other")
+            .await
+            .unwrap();
+        assert_eq!(verdict, PasswordVerdict::Mismatch);
+    }
+
+    #[tokio::test]
+    async fn test_known_hash() {
+        let passwords = fast_conf().build().unwrap();
+        assert!(is_known_hash(&passwords.hash("secret1").await.unwrap()));
+        assert!(is_known_hash(&"ab".repeat(16)));
+        assert!(!is_known_hash(&"zz".repeat(16)));
+        assert!(!is_known_hash("$argon2id$broken"));
+        assert!(!is_known_hash("plain password"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_hash_never_matches() {
+        let passwords = fast_conf().build().unwrap();
+        let verdict = passwords.verify("", "", "").await.unwrap();
+        assert_eq!(verdict, PasswordVerdict::Mismatch);
+    }
+}
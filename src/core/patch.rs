@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use validator::ValidateLength;
+use validator::ValidateRange;
+use validator::ValidateUrl;
+use validator::ValidationError;
+
+/// Field of a partial update request
+///
+/// Tells apart a field left out of the JSON body, which keeps its value, from an explicit
+/// `null`, which resets it. Fields must carry `#[serde(default)]` to be absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Patch<T> {
+    /// Not in the request, left unchanged
+    #[default]
+    Absent,
+    /// Explicit `null`, reset to the default value
+    Null,
+    /// New value
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    /// Whether the field is in the request
+    pub fn is_present(&self) -> bool {
+        !matches!(self, Patch::Absent)
+    }
+
+    /// The value to write, `None` when the field is left unchanged
+    ///
+    /// `null` gives `T::default()`.
+    pub fn into_update(self) -> Option<T>
+    where
+        T: Default,
+    {
+        match self {
+            Patch::Absent => None,
+            Patch::Null => Some(T::default()),
+            Patch::Value(value) => Some(value),
+        }
+    }
+
+    fn value(&self) -> Option<&T> {
+        match self {
+            Patch::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patch::Value(value),
+            None => Patch::Null,
+        })
+    }
+}
+
+/// Serialized as the optional value, e.g. in the params of validation errors
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().serialize(serializer)
+    }
+}
+
+/// Only values are checked, absent fields and `null` pass
+impl<T: ValidateLength<u64>> ValidateLength<u64> for Patch<T> {
+    fn length(&self) -> Option<u64> {
+        self.value().and_then(ValidateLength::length)
+    }
+}
+
+impl<T, U: ValidateRange<T>> ValidateRange<T> for Patch<U> {
+    fn greater_than(&self, max: T) -> Option<bool> {
+        self.value().and_then(|value| value.greater_than(max))
+    }
+
+    fn less_than(&self, min: T) -> Option<bool> {
+        self.value().and_then(|value| value.less_than(min))
+    }
+}
+
+impl<T: ValidateUrl> ValidateUrl for Patch<T> {
+    fn as_url_string(&self) -> Option<Cow<'_, str>> {
+        self.value().and_then(ValidateUrl::as_url_string)
+    }
+}
+
+/// Validator of fields which can be changed but not reset, `#[validate(custom(function = ...))]`
+pub fn not_null<T>(patch: &Patch<T>) -> Result<(), ValidationError> {
+    match patch {
+        Patch::Null => Err(ValidationError::new("not_null")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, validator::Validate)]
+    struct Body {
+        #[serde(default)]
+        #[validate(length(min = 1), custom(function = "not_null"))]
+        name: Patch<String>,
+        #[serde(default)]
+        #[validate(range(max = 150))]
+        age: Patch<u8>,
+    }
+
+    #[test]
+    fn test_patch_deserialize() {
+        let body: Body = serde_json::from_str(r#"{"name":null}"#).unwrap();
+        assert_eq!(body.name, Patch::Null);
+        assert_eq!(body.age, Patch::Absent);
+
+        let body: Body = serde_json::from_str(r#"{"name":"li","age":7}"#).unwrap();
+        assert_eq!(body.name, Patch::Value("li".to_string()));
+        assert_eq!(body.age.into_update(), Some(7));
+        assert!(serde_json::from_str::<Body>(r#"{"age":"7"}"#).is_err());
+    }
+
+    #[test]
+    fn test_patch_validate() {
+        assert_eq!(Patch::Value("li".to_string()).length(), Some(2));
+        assert_eq!(Patch::<String>::Null.length(), None);
+        assert_eq!(Patch::Value(200u8).greater_than(150), Some(true));
+        assert_eq!(Patch::<u8>::Absent.greater_than(150), None);
+        assert!(not_null(&Patch::<u8>::Absent).is_ok());
+        assert!(not_null(&Patch::<u8>::Null).is_err());
+        assert_eq!(Patch::<u8>::Null.into_update(), Some(0));
+    }
+
+    #[test]
+    fn test_patch_derive_validate() {
+        use validator::Validate;
+
+        let body: Body = serde_json::from_str(r#"{"age":null}"#).unwrap();
+        assert!(body.validate().is_ok());
+        let body: Body = serde_json::from_str(r#"{"name":null}"#).unwrap();
+        assert!(body.validate().is_err());
+        let body: Body = serde_json::from_str(r#"{"name":""}"#).unwrap();
+        assert!(body.validate().is_err());
+        let body: Body = serde_json::from_str(r#"{"age":200}"#).unwrap();
+        assert!(body.validate().is_err());
+    }
+}
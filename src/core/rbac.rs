@@ -0,0 +1,209 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use axum::extract::Request;
+use axum::http::request::Parts;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use redis::Commands;
+use tower::Layer;
+use tower::Service;
+use tracing::error;
+use tracing::info;
+
+use crate::core::auth;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::repos;
+
+/// How long the permissions of a user are cached in Redis, in seconds
+///
+/// Role changes made through the admin API invalidate the cache right away, direct SQL changes
+/// are picked up once it expires.
+const PERMISSION_CACHE_TTL_SECS: u64 = 300;
+
+/// Route guard allowing only users holding a permission
+///
+/// Attach it to a single route with `route_layer`:
+///
+/// ```ignore
+/// .route("/user/random", get(random_user).route_layer(RequirePermission("user:write")))
+/// ```
+///
+/// Callers without a valid access token or API key get the usual 401, callers lacking the
+/// permission get `ErrPermissionDenied` (403). API key callers are checked against the scopes of
+/// their key instead of the roles of its owner, this guard being the only way for a key to reach a
+/// handler. The authenticated caller is stored in the request
+/// extensions so the handler's own `AuthUser` does not verify the token again. The router must
+/// provide the `AppState` as an `Extension`.
+#[derive(Debug, Clone, Copy)]
+pub struct RequirePermission(pub &'static str);
+
+impl<S> Layer<S> for RequirePermission {
+    type Service = RequirePermissionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionService {
+            inner,
+            permission: self.0,
+        }
+    }
+}
+
+/// Service produced by [`RequirePermission`]
+#[derive(Debug, Clone)]
+pub struct RequirePermissionService<S> {
+    inner: S,
+    permission: &'static str,
+}
+
+impl<S> Service<Request> for RequirePermissionService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // the clone may not be ready, keep the service polled by `poll_ready`
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let permission = self.permission;
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            match authorize(&mut parts, permission).await {
+                Ok(()) => inner.call(Request::from_parts(parts, body)).await,
+                Err(err) => Ok(err.into_response()),
+            }
+        })
+    }
+}
+
+async fn authorize(parts: &mut Parts, permission: &str) -> Result<(), AppError> {
+    let Some(state) = parts.extensions.get::<AppState>().cloned() else {
+        error!("RequirePermission used on a router without the AppState extension");
+        return Err(errors::ErrNotImplemented.clone());
+    };
+    let auth = auth::authenticate(parts, &state).await?;
+    let permissions = match &auth.api_key {
+        Some(key) => key.scopes.clone(),
+        None => user_permissions(&state, auth.user_id).await?,
+    };
+    if !grants(&permissions, permission) {
+        info!(
+            "user {} (api key {:?}) denied, missing permission {}",
+            auth.user_id,
+            auth.api_key.as_ref().map(|key| key.id),
+            permission
+        );
+        return Err(errors::ErrPermissionDenied.clone());
+    }
+    parts.extensions.insert(auth);
+    Ok(())
+}
+
+/// Checks whether a set of permission codes grants `permission`
+///
+/// `*` grants everything and `resource:*` grants every action on `resource`.
+pub fn grants(permissions: &[String], permission: &str) -> bool {
+    let resource = permission.split_once(':').map(|(resource, _)| resource);
+    permissions.iter().any(|code| {
+        code == permission
+            || code == "*"
+            || code
+                .strip_suffix(":*")
+                .is_some_and(|prefix| Some(prefix) == resource)
+    })
+}
+
+/// Returns the permission codes of a user, read from Redis or loaded from MySQL on a miss
+///
+/// # Arguments
+/// * `state` - Application state
+/// * `user_id` - Id of the user
+///
+/// # Returns
+/// * `Result<Vec<String>, AppError>` - Codes granted through every role of the user
+pub async fn user_permissions(state: &AppState, user_id: i64) -> Result<Vec<String>, AppError> {
+    let key = permission_cache_key(user_id);
+    let cached: Option<String> = state.get_redis_client()?.get(&key).map_err(redis_error)?;
+    if let Some(permissions) = cached.and_then(|value| serde_json::from_str(&value).ok()) {
+        return Ok(permissions);
+    }
+
+    let permissions =
+        repos::rbac::list_permission_codes_by_user(&state.get_conn(), user_id).await?;
+    let value = serde_json::to_string(&permissions).map_err(|err| {
+        error!("encode permissions error {}", err);
+        errors::ErrRedisClient.clone()
+    })?;
+    let _: () = state
+        .get_redis_client()?
+        .set_ex(&key, value, PERMISSION_CACHE_TTL_SECS)
+        .map_err(redis_error)?;
+    Ok(permissions)
+}
+
+/// Drops the cached permissions of a user after its roles changed
+pub fn invalidate_permissions(state: &AppState, user_id: i64) -> Result<(), AppError> {
+    let _: () = state
+        .get_redis_client()?
+        .del(permission_cache_key(user_id))
+        .map_err(redis_error)?;
+    Ok(())
+}
+
+fn permission_cache_key(user_id: i64) -> String {
+    format!("user_permissions_{}", user_id)
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("permission cache redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::core::state::test_state;
+
+    #[test]
+    fn test_grants() {
+        let permissions = vec!["user:read".to_string(), "role:*".to_string()];
+        assert!(grants(&permissions, "user:read"));
+        assert!(!grants(&permissions, "user:write"));
+        assert!(grants(&permissions, "role:admin"));
+        assert!(!grants(&permissions, "roles:admin"));
+        assert!(grants(&["*".to_string()], "user:write"));
+        assert!(!grants(&[], "user:read"));
+    }
+
+    #[tokio::test]
+    async fn test_require_permission_rejects_anonymous() {
+        let app = Router::new()
+            .route("/guarded", get(|| async { "ok" }).route_layer(RequirePermission("user:write")))
+            .layer(axum::Extension(test_state()));
+
+        let resp = app
+            .oneshot(Request::get("/guarded").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
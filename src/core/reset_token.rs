@@ -0,0 +1,159 @@
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Script;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::utils;
+
+/// Stores a new reset token of a user unless the cooldown is running
+///
+/// Returns 0 while the cooldown is running and 1 once the token is stored. The previous token
+/// of the user is deleted, only the latest mail can be used.
+const ISSUE_SCRIPT: &str = r#"
+if tonumber(ARGV[3]) > 0 and not redis.call('SET', KEYS[1], '1', 'NX', 'EX', ARGV[3]) then
+    return 0
+end
+local previous = redis.call('GET', KEYS[2])
+if previous then
+    redis.call('DEL', ARGV[4] .. previous)
+end
+redis.call('SET', KEYS[3], ARGV[1], 'EX', ARGV[2])
+redis.call('SET', KEYS[2], ARGV[5], 'EX', ARGV[2])
+return 1
+"#;
+
+/// Deletes a reset token and returns the id of its user, nil when it does not exist
+const CONSUME_SCRIPT: &str = r#"
+local user_id = redis.call('GET', KEYS[1])
+if not user_id then
+    return false
+end
+redis.call('DEL', KEYS[1])
+redis.call('DEL', ARGV[1] .. user_id)
+return user_id
+"#;
+
+const TOKEN_KEY_PREFIX: &str = "password_reset_";
+
+const USER_KEY_PREFIX: &str = "password_reset_user_";
+
+/// Password reset configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct PasswordResetConf {
+    /// Lifetime of a reset token in seconds
+    #[default(1800)]
+    pub ttl_secs: i64,
+
+    /// Minimum delay between two reset mails sent to the same user, in seconds
+    #[default(60)]
+    pub cooldown_secs: i64,
+
+    /// Link of the reset page sent by mail, `{token}` is replaced by the token
+    #[default("http://localhost:8080/reset-password?token={token}")]
+    pub reset_url: String,
+}
+
+/// Single-use password reset tokens stored in Redis
+///
+/// Tokens are 256 random bits, only their SHA256 is stored:
+/// - `password_reset_{sha256(token)}` pointing to the user id
+/// - `password_reset_user_{user_id}` holding the hash of the latest token of the user
+/// - `password_reset_cooldown_{user_id}` blocking resends
+///
+/// A token is deleted as soon as it is used.
+#[derive(Debug, Clone)]
+pub struct ResetTokens {
+    conf: PasswordResetConf,
+}
+
+impl ResetTokens {
+    pub fn new(conf: PasswordResetConf) -> ResetTokens {
+        ResetTokens { conf }
+    }
+
+    /// Lifetime of a token in seconds
+    pub fn ttl_secs(&self) -> i64 {
+        self.conf.ttl_secs
+    }
+
+    /// Link of the reset page carrying a token
+    pub fn reset_link(&self, token: &str) -> String {
+        self.conf.reset_url.replace("{token}", token)
+    }
+
+    /// Generates and stores a new token, replacing the previous one of the user
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `user_id` - Id of the user resetting the password
+    ///
+    /// # Returns
+    /// * `Result<Option<String>, AppError>` - The plain token to send, `None` while the cooldown
+    ///   is running
+    pub fn issue(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        user_id: i64,
+    ) -> Result<Option<String>, AppError> {
+        let token = utils::gen_token(32);
+        let hash = utils::sha256_hex(&token);
+        let stored: i64 = Script::new(ISSUE_SCRIPT)
+            .key(format!("password_reset_cooldown_{}", user_id))
+            .key(format!("{}{}", USER_KEY_PREFIX, user_id))
+            .key(format!("{}{}", TOKEN_KEY_PREFIX, hash))
+            .arg(user_id)
+            .arg(self.conf.ttl_secs.max(1))
+            .arg(self.conf.cooldown_secs)
+            .arg(TOKEN_KEY_PREFIX)
+            .arg(&hash)
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        Ok((stored == 1).then_some(token))
+    }
+
+    /// Consumes a token
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `token` - Plain token received by mail
+    ///
+    /// # Returns
+    /// * `Result<i64, AppError>` - Id of the user, `ErrResetTokenInvalid` when the token is
+    ///   unknown, expired or already used
+    pub fn consume(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        token: &str,
+    ) -> Result<i64, AppError> {
+        let user_id: Option<i64> = Script::new(CONSUME_SCRIPT)
+            .key(format!("{}{}", TOKEN_KEY_PREFIX, utils::sha256_hex(token)))
+            .arg(USER_KEY_PREFIX)
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        user_id.ok_or_else(|| errors::ErrResetTokenInvalid.clone())
+    }
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("password reset redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_link() {
+        let tokens = ResetTokens::new(PasswordResetConf {
+            reset_url: "https://example.com/reset?token={token}&v=1".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(tokens.reset_link("abc"), "https://example.com/reset?token=abc&v=1");
+    }
+}
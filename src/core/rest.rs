@@ -1,5 +1,7 @@
 use axum::Json;
+use axum::http::HeaderValue;
 use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
 use axum::response::IntoResponse;
 use derivative::Derivative;
 use serde::Serialize;
@@ -12,16 +14,29 @@ pub struct AppError {
     status: StatusCode,
     err_no: i64,
     err_msg: String,
+    /// Seconds sent in the `Retry-After` header
+    retry_after: Option<u64>,
 }
 
 impl AppError {
     pub fn new(status: StatusCode, err_no: i64, err_msg: &str) -> Self {
-        let res = AppError {
+        AppError {
             status,
             err_no,
             err_msg: err_msg.to_string(),
-        };
-        res
+            retry_after: None,
+        }
+    }
+
+    /// Business error code, e.g. 20029
+    pub fn err_no(&self) -> i64 {
+        self.err_no
+    }
+
+    /// Tells the client how many seconds to wait before retrying, with a `Retry-After` header
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
     }
 }
 
@@ -41,10 +56,27 @@ impl IntoResponse for AppError {
             data: None,
         };
 
-        (self.status, Json(res)).into_response()
+        let mut resp = (self.status, Json(res)).into_response();
+        if let Some(secs) = self.retry_after {
+            resp.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        resp
     }
 }
 
+#[tokio::test]
+async fn test_app_error_retry_after() {
+    let resp = AppError::new(StatusCode::TOO_MANY_REQUESTS, 1, "error").into_response();
+    assert!(resp.headers().get(RETRY_AFTER).is_none());
+
+    let resp = AppError::new(StatusCode::TOO_MANY_REQUESTS, 1, "error")
+        .with_retry_after(30)
+        .into_response();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()[RETRY_AFTER], "30");
+}
+
 #[derive(Serialize)]
 struct InnerAppResult<T: Serialize> {
     err_no: i64,
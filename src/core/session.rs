@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use axum::extract::FromRequestParts;
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Commands;
+use redis::Script;
+use serde::Serialize;
+use tracing::error;
+
+use crate::core::client_ip::ClientIp;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+
+/// Header letting clients name the device, e.g. "Li Lei's iPhone"
+pub const DEVICE_HEADER: &str = "X-Device-Name";
+
+/// `last_seen_at` is written at most once per interval per session, in seconds
+pub const TOUCH_INTERVAL_SECS: i64 = 60;
+
+const MAX_DEVICE_LEN: usize = 64;
+
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Rejects revoked sessions and records the activity of live ones
+///
+/// Returns 0 when the session is revoked and 1 otherwise. Sessions started before session records
+/// existed have no record and are only checked against the revocation marker.
+pub(crate) const CHECK_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+local seen = redis.call('HGET', KEYS[2], 'last_seen_at')
+if seen and tonumber(ARGV[1]) - tonumber(seen) >= tonumber(ARGV[2]) then
+    redis.call('HSET', KEYS[2], 'last_seen_at', ARGV[1])
+end
+return 1
+"#;
+
+/// Device the caller logs in from, recorded with the session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientMeta {
+    /// IP address of the caller
+    pub ip: String,
+    /// `User-Agent` header, truncated
+    pub user_agent: String,
+    /// `X-Device-Name` header, or a name guessed from the user agent
+    pub device: String,
+}
+
+impl FromRequestParts<AppState> for ClientMeta {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+        let user_agent = truncate(header(USER_AGENT.as_str()), MAX_USER_AGENT_LEN);
+        let device = match header(DEVICE_HEADER) {
+            "" => guess_device(&user_agent).to_string(),
+            name => truncate(name, MAX_DEVICE_LEN),
+        };
+        Ok(ClientMeta {
+            ip,
+            user_agent,
+            device,
+        })
+    }
+}
+
+/// Guesses a device name from a user agent
+fn guess_device(user_agent: &str) -> &'static str {
+    const DEVICES: [(&str, &str); 8] = [
+        ("MicroMessenger", "WeChat"),
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("Windows", "Windows"),
+        ("Macintosh", "Mac"),
+        ("Linux", "Linux"),
+        ("curl", "curl"),
+    ];
+    DEVICES
+        .iter()
+        .find(|(pattern, _)| user_agent.contains(pattern))
+        .map_or("Unknown", |(_, device)| device)
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+/// Session record kept in Redis for every login
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SessionRecord {
+    /// Session id, the `sid` claim of its access tokens
+    pub id: String,
+    /// Owner of the session
+    pub user_id: i64,
+    /// Device name
+    pub device: String,
+    /// IP address of the login
+    pub ip: String,
+    /// User agent of the login
+    pub user_agent: String,
+    /// Timestamp of the login (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp of the last authenticated request, at most `TOUCH_INTERVAL_SECS` old
+    pub last_seen_at: i64,
+}
+
+impl SessionRecord {
+    fn from_hash(id: &str, hash: &HashMap<String, String>) -> Option<SessionRecord> {
+        let text = |field: &str| hash.get(field).cloned().unwrap_or_default();
+        let number = |field: &str| hash.get(field).and_then(|value| value.parse::<i64>().ok());
+        Some(SessionRecord {
+            id: id.to_string(),
+            user_id: number("user_id")?,
+            device: text("device"),
+            ip: text("ip"),
+            user_agent: text("user_agent"),
+            created_at: number("created_at").unwrap_or_default(),
+            last_seen_at: number("last_seen_at").unwrap_or_default(),
+        })
+    }
+}
+
+/// Stores the record of a new session and adds it to the index of its owner
+///
+/// Records live as long as the refresh token family of the session:
+/// - `session_{sid}` hash with the fields of `SessionRecord`
+/// - `user_sessions_{user_id}` sorted set of session ids scored by login time
+pub fn create(
+    conn: &mut PooledConnection<Client>,
+    session_id: &str,
+    user_id: i64,
+    client: &ClientMeta,
+    now: i64,
+    ttl: i64,
+) -> Result<(), AppError> {
+    let key = session_key(session_id);
+    let _: () = conn
+        .hset_multiple(
+            &key,
+            &[
+                ("user_id", user_id.to_string()),
+                ("device", client.device.clone()),
+                ("ip", client.ip.clone()),
+                ("user_agent", client.user_agent.clone()),
+                ("created_at", now.to_string()),
+                ("last_seen_at", now.to_string()),
+            ],
+        )
+        .map_err(redis_error)?;
+    let _: () = conn.expire(&key, ttl).map_err(redis_error)?;
+
+    let index = user_sessions_key(user_id);
+    let _: () = conn.zadd(&index, session_id, now).map_err(redis_error)?;
+    let _: () = conn.expire(&index, ttl).map_err(redis_error)?;
+    Ok(())
+}
+
+/// Extends a session record after its refresh token was rotated
+pub fn extend(
+    conn: &mut PooledConnection<Client>,
+    session_id: &str,
+    user_id: i64,
+    now: i64,
+    ttl: i64,
+) -> Result<(), AppError> {
+    let key = session_key(session_id);
+    let exists: bool = conn.exists(&key).map_err(redis_error)?;
+    if !exists {
+        return Ok(());
+    }
+    let _: () = conn.hset(&key, "last_seen_at", now).map_err(redis_error)?;
+    let _: () = conn.expire(&key, ttl).map_err(redis_error)?;
+    let _: () = conn
+        .expire(user_sessions_key(user_id), ttl)
+        .map_err(redis_error)?;
+    Ok(())
+}
+
+/// Returns a session record, `None` when it expired or was removed
+pub fn get(
+    conn: &mut PooledConnection<Client>,
+    session_id: &str,
+) -> Result<Option<SessionRecord>, AppError> {
+    let hash: HashMap<String, String> =
+        conn.hgetall(session_key(session_id)).map_err(redis_error)?;
+    Ok(SessionRecord::from_hash(session_id, &hash))
+}
+
+/// Returns the live sessions of a user, most recently active first
+///
+/// Ids of expired records are dropped from the index on the way.
+pub fn list(
+    conn: &mut PooledConnection<Client>,
+    user_id: i64,
+) -> Result<Vec<SessionRecord>, AppError> {
+    let index = user_sessions_key(user_id);
+    let ids: Vec<String> = conn.zrange(&index, 0, -1).map_err(redis_error)?;
+    let mut sessions = Vec::with_capacity(ids.len());
+    for id in ids {
+        match get(conn, &id)? {
+            Some(record) => sessions.push(record),
+            None => {
+                let _: () = conn.zrem(&index, &id).map_err(redis_error)?;
+            }
+        }
+    }
+    sessions.sort_by_key(|record| std::cmp::Reverse(record.last_seen_at));
+    Ok(sessions)
+}
+
+/// Deletes a session record and removes it from the index of its owner
+pub fn remove(conn: &mut PooledConnection<Client>, session_id: &str) -> Result<(), AppError> {
+    let key = session_key(session_id);
+    let user_id: Option<i64> = conn.hget(&key, "user_id").map_err(redis_error)?;
+    let _: () = conn.del(&key).map_err(redis_error)?;
+    if let Some(user_id) = user_id {
+        let _: () = conn
+            .zrem(user_sessions_key(user_id), session_id)
+            .map_err(redis_error)?;
+    }
+    Ok(())
+}
+
+/// Checks the revocation marker of a session and records its activity, see `CHECK_SCRIPT`
+pub(crate) fn check_and_touch(
+    conn: &mut PooledConnection<Client>,
+    revoked_key: &str,
+    session_id: &str,
+    now: i64,
+) -> Result<bool, AppError> {
+    let live: i64 = Script::new(CHECK_SCRIPT)
+        .key(revoked_key)
+        .key(session_key(session_id))
+        .arg(now)
+        .arg(TOUCH_INTERVAL_SECS)
+        .invoke(&mut **conn)
+        .map_err(redis_error)?;
+    Ok(live == 1)
+}
+
+fn session_key(session_id: &str) -> String {
+    format!("session_{}", session_id)
+}
+
+fn user_sessions_key(user_id: i64) -> String {
+    format!("user_sessions_{}", user_id)
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("session redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+    use crate::core::state::test_state;
+
+    async fn meta_with(headers: &[(&str, &str)]) -> ClientMeta {
+        let mut builder = Request::builder().uri("/");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let mut parts = builder.body(()).unwrap().into_parts().0;
+        ClientMeta::from_request_parts(&mut parts, &test_state())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_meta() {
+        let meta = meta_with(&[(
+            "User-Agent",
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) MicroMessenger/8.0",
+        )])
+        .await;
+        assert_eq!(meta.device, "WeChat");
+        assert_eq!(meta.ip, "unknown");
+
+        let meta = meta_with(&[
+            ("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64)"),
+            (DEVICE_HEADER, " Office PC "),
+        ])
+        .await;
+        assert_eq!(meta.device, "Office PC");
+
+        let meta = meta_with(&[]).await;
+        assert_eq!(meta.device, "Unknown");
+        assert_eq!(meta.user_agent, "");
+    }
+
+    #[test]
+    fn test_record_from_hash() {
+        let hash: HashMap<String, String> = [
+            ("user_id", "7"),
+            ("device", "Mac"),
+            ("created_at", "10"),
+            ("last_seen_at", "20"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let record = SessionRecord::from_hash("abc", &hash).unwrap();
+        assert_eq!(record.user_id, 7);
+        assert_eq!(record.device, "Mac");
+        assert_eq!(record.last_seen_at, 20);
+        assert!(SessionRecord::from_hash("abc", &HashMap::new()).is_none());
+    }
+}
@@ -1,36 +1,105 @@
-use derivative::Derivative;
 use r2d2::PooledConnection;
 use redis::Client;
-use serde::Deserialize;
 use sqlx::MySqlPool;
 use tracing::error;
 
+use crate::blob::BlobClient;
+use crate::core::client_ip::TrustedProxies;
+use crate::core::crypto::DataCipher;
+use crate::core::cursor::Cursors;
+use crate::core::jwt::JwtKeys;
+use crate::core::lockout::Lockouts;
+use crate::core::password::Passwords;
+use crate::core::reset_token::ResetTokens;
 use crate::core::rest::AppError;
+use crate::core::session;
+use crate::core::totp::TwoFactor;
+use crate::core::verify_code::VerifyCodes;
 use crate::data::cache::RedisPool;
 use crate::errors;
-
-#[derive(Deserialize, Derivative, Clone)]
-#[derivative(Debug)]
-pub struct WeChatConf {
-    pub appid: String,
-    #[derivative(Debug = "ignore")]
-    pub secret: String,
-}
+use crate::mail::MailClient;
+use crate::oidc::Oidc;
+use crate::search::SearchClient;
+use crate::services::account::AccountConf;
+use crate::services::avatar::AvatarConf;
+use crate::services::bulk::BulkConf;
+use crate::services::login_event::LoginEventConf;
+use crate::sms::SmsClient;
+use crate::wechat::WeChatClient;
 
 #[allow(unused)]
 #[derive(Clone)]
 pub struct AppState {
     pub db_conn: MySqlPool,
     pub redis_pool: RedisPool,
-    pub wechat: WeChatConf,
+    pub wechat: WeChatClient,
+    pub jwt: JwtKeys,
+    pub passwords: Passwords,
+    pub cipher: DataCipher,
+    pub verify_codes: VerifyCodes,
+    pub reset_tokens: ResetTokens,
+    pub two_factor: TwoFactor,
+    pub lockouts: Lockouts,
+    pub cursors: Cursors,
+    pub oidc: Oidc,
+    pub mail: MailClient,
+    pub sms: SmsClient,
+    pub account: AccountConf,
+    pub login_event: LoginEventConf,
+    pub bulk: BulkConf,
+    pub blobs: BlobClient,
+    pub avatar: AvatarConf,
+    pub search: SearchClient,
+    pub proxies: TrustedProxies,
 }
 
 impl AppState {
-    pub fn new(conn: MySqlPool, redis_pool: RedisPool, wechat: WeChatConf) -> AppState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        conn: MySqlPool,
+        redis_pool: RedisPool,
+        wechat: WeChatClient,
+        jwt: JwtKeys,
+        passwords: Passwords,
+        cipher: DataCipher,
+        verify_codes: VerifyCodes,
+        reset_tokens: ResetTokens,
+        two_factor: TwoFactor,
+        lockouts: Lockouts,
+        cursors: Cursors,
+        oidc: Oidc,
+        mail: MailClient,
+        sms: SmsClient,
+        account: AccountConf,
+        login_event: LoginEventConf,
+        bulk: BulkConf,
+        blobs: BlobClient,
+        avatar: AvatarConf,
+        search: SearchClient,
+        proxies: TrustedProxies,
+    ) -> AppState {
         AppState {
             db_conn: conn,
             redis_pool,
             wechat,
+            jwt,
+            passwords,
+            cipher,
+            verify_codes,
+            reset_tokens,
+            two_factor,
+            lockouts,
+            cursors,
+            oidc,
+            mail,
+            sms,
+            account,
+            login_event,
+            bulk,
+            blobs,
+            avatar,
+            search,
+            proxies,
         }
     }
 
@@ -58,4 +127,103 @@ impl AppState {
         })?;
         Ok(conn)
     }
+
+    /// Checks whether a session has been revoked by logout, refresh token reuse or from another
+    /// device, and records the activity of live sessions
+    ///
+    /// # Arguments
+    /// * `session_id` - The `sid` claim of an access token
+    ///
+    /// # Returns
+    /// - `Ok(true)` if access tokens of this session must be rejected
+    /// - `Err(AppError)` if Redis cannot be reached
+    pub fn is_session_revoked(&self, session_id: &str) -> core::result::Result<bool, AppError> {
+        let live = session::check_and_touch(
+            &mut self.get_redis_client()?,
+            &revoked_session_key(session_id),
+            session_id,
+            chrono::Utc::now().timestamp(),
+        )?;
+        Ok(!live)
+    }
+}
+
+/// Redis key marking a revoked session, kept as long as its access tokens stay valid
+pub fn revoked_session_key(session_id: &str) -> String {
+    format!("revoked_session_{}", session_id)
+}
+
+/// Application state for unit tests
+///
+/// MySQL and Redis point at a closed port and are only connected lazily, so it serves code paths
+/// that fail before touching them.
+#[cfg(test)]
+pub(crate) fn test_state() -> AppState {
+    use crate::blob::BlobConf;
+    use crate::core::client_ip::ClientIpConf;
+    use crate::core::crypto::CryptoConf;
+    use crate::core::cursor::CursorConf;
+    use crate::core::jwt::JwtConf;
+    use crate::core::lockout::LockoutConf;
+    use crate::core::password::PasswordConf;
+    use crate::core::reset_token::PasswordResetConf;
+    use crate::core::totp::TotpConf;
+    use crate::core::verify_code::VerifyCodeConf;
+    use crate::mail::MailConf;
+    use crate::oidc::OidcConf;
+    use crate::search::SearchConf;
+    use crate::sms::SmsConf;
+    use crate::wechat::WeChatConf;
+
+    let conn = MySqlPool::connect_lazy("mysql://root@127.0.0.1:1/test").unwrap();
+    let search = SearchConf::default().build(&conn).unwrap();
+    let redis_pool =
+        r2d2::Pool::builder().build_unchecked(Client::open("redis://127.0.0.1:1/").unwrap());
+    let jwt = JwtConf {
+        secret: "unit-test-secret".to_string(),
+        ..Default::default()
+    };
+    let passwords = PasswordConf {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+    let crypto = CryptoConf {
+        data_key: "ZGV2ZWxvcG1lbnQta2V5LWNoYW5nZS1tZS0zMmJ5dGU=".to_string(),
+    };
+    AppState::new(
+        conn,
+        redis_pool,
+        WeChatClient::new(WeChatConf::default()),
+        jwt.build_keys().unwrap(),
+        passwords.build().unwrap(),
+        crypto.build().unwrap(),
+        VerifyCodes::new(VerifyCodeConf::default()),
+        ResetTokens::new(PasswordResetConf::default()),
+        TwoFactor::new(TotpConf::default()),
+        Lockouts::new(LockoutConf::default()),
+        CursorConf {
+            secret: "unit-test-cursor-secret".to_string(),
+        }
+        .build()
+        .unwrap(),
+        Oidc::new(OidcConf::default()),
+        MailConf::default().build().unwrap(),
+        SmsConf::default().build().unwrap(),
+        AccountConf::default(),
+        LoginEventConf::default(),
+        BulkConf::default(),
+        BlobConf {
+            local_dir: std::env::temp_dir()
+                .join("unit-test-blobs")
+                .to_string_lossy()
+                .to_string(),
+            ..Default::default()
+        }
+        .build()
+        .unwrap(),
+        AvatarConf::default(),
+        search,
+        ClientIpConf::default().build().unwrap(),
+    )
 }
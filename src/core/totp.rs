@@ -0,0 +1,298 @@
+use hmac::Hmac;
+use hmac::Mac;
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Commands;
+use redis::Script;
+use serde::Deserialize;
+use sha1::Sha1;
+use smart_default::SmartDefault;
+use subtle::ConstantTimeEq;
+use tracing::error;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::utils;
+
+/// Length of a TOTP time step in seconds (RFC 6238 default)
+pub const STEP_SECS: i64 = 30;
+
+/// Number of digits of a TOTP code
+pub const DIGITS: u32 = 6;
+
+/// Size of a generated secret in bytes, the length of a SHA1 digest as advised by RFC 4226
+const SECRET_LEN: usize = 20;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Counts an attempt on a login challenge
+///
+/// Returns the user id of the challenge, or -1 when it does not exist or its attempts are used
+/// up, in which case it is deleted.
+const CHALLENGE_SCRIPT: &str = r#"
+local user_id = redis.call('HGET', KEYS[1], 'user_id')
+if not user_id then
+    return -1
+end
+local attempts = redis.call('HINCRBY', KEYS[1], 'attempts', 1)
+if attempts > tonumber(ARGV[1]) then
+    redis.call('DEL', KEYS[1])
+    return -1
+end
+return tonumber(user_id)
+"#;
+
+/// Two-factor authentication configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct TotpConf {
+    /// Issuer shown by authenticator apps
+    #[default("axum-best")]
+    pub issuer: String,
+
+    /// Time steps accepted before and after the current one, to absorb clock drift
+    #[default(1)]
+    pub skew_steps: i64,
+
+    /// Lifetime of the challenge returned by the password step, in seconds
+    #[default(300)]
+    pub challenge_ttl_secs: i64,
+
+    /// Codes that can be tried against one challenge
+    #[default(5)]
+    pub challenge_max_attempts: i64,
+
+    /// Number of recovery codes generated on activation
+    #[default(10)]
+    pub recovery_codes: usize,
+}
+
+/// TOTP (RFC 6238) codes and two-step login challenges
+///
+/// Challenges live in Redis as `two_factor_challenge_{sha256(challenge)}` hashes holding the
+/// `user_id` and the number of `attempts`.
+#[derive(Debug, Clone)]
+pub struct TwoFactor {
+    conf: TotpConf,
+}
+
+impl TwoFactor {
+    pub fn new(conf: TotpConf) -> TwoFactor {
+        TwoFactor { conf }
+    }
+
+    /// Two-factor configuration
+    pub fn conf(&self) -> &TotpConf {
+        &self.conf
+    }
+
+    /// Checks a code against a secret
+    ///
+    /// # Arguments
+    /// * `secret` - Raw secret
+    /// * `code` - Code typed by the user
+    /// * `now` - Current Unix timestamp
+    /// * `last_step` - Last step accepted for this secret, older or equal steps are rejected
+    ///
+    /// # Returns
+    /// * `Option<i64>` - The matching time step, to be stored as the new `last_step`
+    pub fn verify(&self, secret: &[u8], code: &str, now: i64, last_step: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize {
+            return None;
+        }
+        let current = now / STEP_SECS;
+        (current - self.conf.skew_steps..=current + self.conf.skew_steps)
+            .filter(|step| *step > last_step)
+            .find(|step| bool::from(code_at(secret, *step).as_bytes().ct_eq(code.as_bytes())))
+    }
+
+    /// Starts the second step of a login
+    ///
+    /// # Returns
+    /// * `Result<String, AppError>` - Challenge to send back with the TOTP code
+    pub fn issue_challenge(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        user_id: i64,
+    ) -> Result<String, AppError> {
+        let challenge = utils::gen_token(32);
+        let key = challenge_key(&challenge);
+        let _: () = conn
+            .hset_multiple(&key, &[("user_id", user_id), ("attempts", 0)])
+            .map_err(redis_error)?;
+        let _: () = conn
+            .expire(&key, self.conf.challenge_ttl_secs.max(1))
+            .map_err(redis_error)?;
+        Ok(challenge)
+    }
+
+    /// Counts an attempt on a challenge
+    ///
+    /// # Returns
+    /// * `Result<i64, AppError>` - Id of the user logging in, `ErrTwoFactorChallengeInvalid` when
+    ///   the challenge expired or too many codes were tried
+    pub fn attempt_challenge(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        challenge: &str,
+    ) -> Result<i64, AppError> {
+        let user_id: i64 = Script::new(CHALLENGE_SCRIPT)
+            .key(challenge_key(challenge))
+            .arg(self.conf.challenge_max_attempts)
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        if user_id < 0 {
+            return Err(errors::ErrTwoFactorChallengeInvalid.clone());
+        }
+        Ok(user_id)
+    }
+
+    /// Deletes a challenge once the login succeeded
+    pub fn finish_challenge(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        challenge: &str,
+    ) -> Result<(), AppError> {
+        let _: () = conn.del(challenge_key(challenge)).map_err(redis_error)?;
+        Ok(())
+    }
+}
+
+/// Generates a random secret
+pub fn generate_secret() -> Vec<u8> {
+    (0..SECRET_LEN).map(|_| rand::random::<u8>()).collect()
+}
+
+/// Computes the code of a time step (RFC 4226 HOTP with HMAC-SHA1)
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+/// Encodes a secret in unpadded base32, the format typed into authenticator apps
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+/// Builds the `otpauth://` URI rendered as a QR code for authenticator apps
+///
+/// # Arguments
+/// * `issuer` - Name of the service
+/// * `account` - Account name shown next to the issuer, usually the email
+/// * `secret` - Raw secret
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        utils::percent_encode(issuer),
+        utils::percent_encode(account),
+        base32_encode(secret),
+        utils::percent_encode(issuer),
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+/// Generates recovery codes formatted as `xxxxx-xxxxx`
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let token = utils::gen_token(5);
+            format!("{}-{}", &token[..5], &token[5..])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code the way it is stored, ignoring case, spaces and dashes
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    utils::sha256_hex(&normalized)
+}
+
+fn challenge_key(challenge: &str) -> String {
+    format!("two_factor_challenge_{}", utils::sha256_hex(challenge))
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("two factor redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_code_at_rfc6238() {
+        // RFC 6238 appendix B SHA1 vectors, truncated to 6 digits
+        assert_eq!(code_at(RFC_SECRET, 59 / STEP_SECS), "287082");
+        assert_eq!(code_at(RFC_SECRET, 1111111109 / STEP_SECS), "081804");
+        assert_eq!(code_at(RFC_SECRET, 1234567890 / STEP_SECS), "005924");
+        assert_eq!(code_at(RFC_SECRET, 20000000000 / STEP_SECS), "353130");
+    }
+
+    #[test]
+    fn test_verify() {
+        let totp = TwoFactor::new(TotpConf::default());
+        let now = 1111111109;
+        let step = now / STEP_SECS;
+        let previous = code_at(RFC_SECRET, step - 1);
+        assert_eq!(totp.verify(RFC_SECRET, "081804", now, 0), Some(step));
+        assert_eq!(totp.verify(RFC_SECRET, &previous, now, 0), Some(step - 1));
+        // replay of an accepted step
+        assert_eq!(totp.verify(RFC_SECRET, "081804", now, step), None);
+        assert_eq!(totp.verify(RFC_SECRET, &code_at(RFC_SECRET, step - 2), now, 0), None);
+        assert_eq!(totp.verify(RFC_SECRET, "12345", now, 0), None);
+    }
+
+    #[test]
+    fn test_base32_and_uri() {
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(
+            otpauth_uri("Axum Best", "li@example.com", RFC_SECRET),
+            "otpauth://totp/Axum%20Best:li%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Axum%20Best&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        assert!(
+            codes
+                .iter()
+                .all(|code| code.len() == 11 && &code[5..6] == "-")
+        );
+        assert_eq!(hash_recovery_code("ABCDE-12345"), hash_recovery_code(" abcde12345 "));
+    }
+}
@@ -0,0 +1,260 @@
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Script;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+use tracing::info;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::utils;
+
+/// Checks both cooldowns and the daily quota, then stores a new code
+///
+/// Returns the seconds left on the blocking cooldown, -1 when the daily quota of the target is
+/// used up, or 0 once the code is stored.
+const ISSUE_SCRIPT: &str = r#"
+local sent = tonumber(redis.call('GET', KEYS[4]) or '0')
+if tonumber(ARGV[5]) > 0 and sent >= tonumber(ARGV[5]) then
+    return -1
+end
+local wait = redis.call('TTL', KEYS[1])
+if wait > 0 then
+    return wait
+end
+wait = redis.call('TTL', KEYS[2])
+if wait > 0 then
+    return wait
+end
+if tonumber(ARGV[1]) > 0 then
+    redis.call('SET', KEYS[1], '1', 'EX', ARGV[1])
+end
+if tonumber(ARGV[2]) > 0 then
+    redis.call('SET', KEYS[2], '1', 'EX', ARGV[2])
+end
+redis.call('DEL', KEYS[3])
+redis.call('HSET', KEYS[3], 'code', ARGV[3], 'attempts', 0)
+redis.call('EXPIRE', KEYS[3], ARGV[4])
+if redis.call('INCR', KEYS[4]) == 1 then
+    redis.call('EXPIRE', KEYS[4], 86400)
+end
+return 0
+"#;
+
+/// Withdraws a code that could not be delivered, when it is still the pending one
+///
+/// Clears both cooldowns and gives its slot of the daily quota back.
+const REVOKE_SCRIPT: &str = r#"
+if redis.call('HGET', KEYS[3], 'code') ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1], KEYS[2], KEYS[3])
+if tonumber(redis.call('GET', KEYS[4]) or '0') > 0 then
+    redis.call('DECR', KEYS[4])
+end
+return 1
+"#;
+
+/// Counts an attempt and consumes the code when it matches
+///
+/// Returns 1 on match, 0 on mismatch, -1 when there is no code and -2 when the last allowed
+/// attempt failed.
+const VERIFY_SCRIPT: &str = r#"
+local code = redis.call('HGET', KEYS[1], 'code')
+if not code then
+    return -1
+end
+local attempts = redis.call('HINCRBY', KEYS[1], 'attempts', 1)
+if code == ARGV[1] then
+    redis.call('DEL', KEYS[1])
+    return 1
+end
+if attempts >= tonumber(ARGV[2]) then
+    redis.call('DEL', KEYS[1])
+    return -2
+end
+return 0
+"#;
+
+/// Verification code configuration, shared by every flow sending one-time codes
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct VerifyCodeConf {
+    /// Number of digits of a code
+    #[default(6)]
+    pub code_len: usize,
+
+    /// Lifetime of a code in seconds
+    #[default(600)]
+    pub ttl_secs: i64,
+
+    /// Wrong guesses allowed before the code is burnt
+    #[default(5)]
+    pub max_attempts: i64,
+
+    /// Minimum delay between two codes sent to the same target, in seconds
+    #[default(60)]
+    pub resend_cooldown_secs: i64,
+
+    /// Minimum delay between two codes requested from the same IP, in seconds
+    #[default(10)]
+    pub ip_cooldown_secs: i64,
+
+    /// Codes sent to the same target within 24 hours, 0 for no limit
+    #[default(10)]
+    pub daily_limit: i64,
+}
+
+/// A freshly issued verification code
+#[derive(Debug)]
+pub struct IssuedCode {
+    /// Plain code to deliver to the user, only its hash is stored
+    pub code: String,
+    /// Seconds until the code expires
+    pub expires_in: i64,
+    /// Seconds until another code can be requested for the same target
+    pub resend_in: i64,
+}
+
+/// One-time verification codes stored in Redis
+///
+/// For a `scene` (e.g. `bind_email`) and a `target` (e.g. the email address) it keeps:
+/// - `{scene}_{target}` hash with the SHA256 of the `code` and the number of `attempts`
+/// - `{scene}_cooldown_{target}` and `{scene}_ip_cooldown_{ip}` blocking resends
+/// - `{scene}_daily_{target}` counting the codes sent during the last 24 hours
+///
+/// A code is deleted as soon as it matches or the attempts are exhausted, so it can be used once.
+#[derive(Debug, Clone)]
+pub struct VerifyCodes {
+    conf: VerifyCodeConf,
+}
+
+impl VerifyCodes {
+    pub fn new(conf: VerifyCodeConf) -> VerifyCodes {
+        VerifyCodes { conf }
+    }
+
+    /// Generates and stores a new code, replacing any previous one for the same target
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `scene` - Flow the code belongs to, used as key prefix
+    /// * `target` - Normalized email address or phone number the code is sent to
+    /// * `ip` - Client IP requesting the code
+    ///
+    /// # Returns
+    /// * `Result<IssuedCode, AppError>` - The code to send, `ErrVerifyCodeTooFrequent` while a
+    ///   cooldown is running or once the daily quota is used up
+    pub fn issue(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        scene: &str,
+        target: &str,
+        ip: &str,
+    ) -> Result<IssuedCode, AppError> {
+        let code = utils::gen_valid_code(self.conf.code_len);
+        let wait: i64 = Script::new(ISSUE_SCRIPT)
+            .key(format!("{}_cooldown_{}", scene, target))
+            .key(format!("{}_ip_cooldown_{}", scene, ip))
+            .key(code_key(scene, target))
+            .key(format!("{}_daily_{}", scene, target))
+            .arg(self.conf.resend_cooldown_secs)
+            .arg(self.conf.ip_cooldown_secs)
+            .arg(utils::sha256_hex(&code))
+            .arg(self.conf.ttl_secs.max(1))
+            .arg(self.conf.daily_limit)
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        if wait < 0 {
+            info!("{} code for {} reached the daily limit", scene, target);
+            return Err(errors::ErrVerifyCodeTooFrequent.clone());
+        }
+        if wait > 0 {
+            info!("{} code for {} from {} blocked for {}s", scene, target, ip, wait);
+            return Err(errors::ErrVerifyCodeTooFrequent.clone());
+        }
+        Ok(IssuedCode {
+            code,
+            expires_in: self.conf.ttl_secs,
+            resend_in: self.conf.resend_cooldown_secs,
+        })
+    }
+
+    /// Withdraws an issued code after its delivery failed, so the user can ask for another one
+    /// right away
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `scene` - Flow the code belongs to
+    /// * `target` - Normalized email address or phone number the code was meant for
+    /// * `ip` - Client IP that requested the code
+    /// * `issued` - The undelivered code, a newer one for the same target is left alone
+    pub fn revoke(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        scene: &str,
+        target: &str,
+        ip: &str,
+        issued: &IssuedCode,
+    ) -> Result<(), AppError> {
+        let revoked: i64 = Script::new(REVOKE_SCRIPT)
+            .key(format!("{}_cooldown_{}", scene, target))
+            .key(format!("{}_ip_cooldown_{}", scene, ip))
+            .key(code_key(scene, target))
+            .key(format!("{}_daily_{}", scene, target))
+            .arg(utils::sha256_hex(&issued.code))
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        if revoked > 0 {
+            info!("{} code for {} revoked", scene, target);
+        }
+        Ok(())
+    }
+
+    /// Checks a code and consumes it on success
+    ///
+    /// # Arguments
+    /// * `conn` - Redis connection
+    /// * `scene` - Flow the code belongs to
+    /// * `target` - Normalized email address or phone number the code was sent to
+    /// * `code` - Code typed by the user
+    ///
+    /// # Returns
+    /// - `Ok(())` if the code matches
+    /// - `Err(ErrVerifyCodeMismatch)` on a wrong guess
+    /// - `Err(ErrVerifyCodeExpired)` if no code is pending
+    /// - `Err(ErrVerifyCodeAttempts)` once the attempts are exhausted
+    pub fn verify(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        scene: &str,
+        target: &str,
+        code: &str,
+    ) -> Result<(), AppError> {
+        let status: i64 = Script::new(VERIFY_SCRIPT)
+            .key(code_key(scene, target))
+            .arg(utils::sha256_hex(code.trim()))
+            .arg(self.conf.max_attempts.max(1))
+            .invoke(&mut **conn)
+            .map_err(redis_error)?;
+        match status {
+            1 => Ok(()),
+            0 => Err(errors::ErrVerifyCodeMismatch.clone()),
+            -2 => {
+                info!("{} code for {} burnt after too many attempts", scene, target);
+                Err(errors::ErrVerifyCodeAttempts.clone())
+            }
+            _ => Err(errors::ErrVerifyCodeExpired.clone()),
+        }
+    }
+}
+
+fn code_key(scene: &str, target: &str) -> String {
+    format!("{}_{}", scene, target)
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("verify code redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
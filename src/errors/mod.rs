@@ -11,6 +11,47 @@ lazy_static! {
     /// Bad request - invalid request parameters
     pub static ref ErrBadRequest: AppError =
         AppError::new(StatusCode::BAD_REQUEST, 14000, "Bad Request Params");
+
+    /// Invalid phone number - the number cannot be normalized to a valid E.164 number
+    pub static ref ErrPhoneInvalid: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 14001, "Invalid Phone Number");
+
+    /// Invalid cursor - the pagination cursor was altered or belongs to another listing
+    pub static ref ErrInvalidCursor: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 14002, "Invalid Cursor");
+
+    /// Precondition required - the update of a versioned resource lacks the `If-Match` header
+    pub static ref ErrPreconditionRequired: AppError =
+        AppError::new(StatusCode::PRECONDITION_REQUIRED, 14003, "If-Match Header Required");
+
+    /// Version conflict - the resource changed since the client read it, `If-Match` is stale
+    pub static ref ErrVersionConflict: AppError =
+        AppError::new(StatusCode::PRECONDITION_FAILED, 14004, "Resource Changed, Reload And Retry");
+
+    /// Malformed import - the bulk import file cannot be read as CSV or NDJSON
+    pub static ref ErrImportMalformed: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 14005, "Malformed Import File");
+
+    /// Avatar too large - the uploaded file exceeds the configured size limit
+    pub static ref ErrAvatarTooLarge: AppError =
+        AppError::new(StatusCode::PAYLOAD_TOO_LARGE, 14006, "Avatar Too Large");
+
+    /// Unsupported avatar type - the uploaded file is not an image of an accepted type
+    pub static ref ErrAvatarType: AppError =
+        AppError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, 14007, "Unsupported Avatar Type");
+
+    /// Blob not found - no stored file under the requested key
+    pub static ref ErrBlobNotFound: AppError =
+        AppError::new(StatusCode::NOT_FOUND, 14008, "File Not Found");
+
+    /// Invalid image - the uploaded file claims an image type but cannot be decoded
+    pub static ref ErrImageCorrupt: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 14009, "Invalid Image");
+
+    /// Image too large - the dimensions of the image exceed the configured limits, which also
+    /// refuses decompression bombs before their pixels are allocated
+    pub static ref ErrImageDimensions: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 14010, "Image Dimensions Too Large");
 }
 
 lazy_static! {
@@ -22,12 +63,157 @@ lazy_static! {
 lazy_static! {
     /// Invalid user ID - unauthorized access attempt
     pub static ref ErrInvalidUserId: AppError = AppError::new(StatusCode::UNAUTHORIZED, 20000, "");
+
+    /// Missing access token - the `Authorization: Bearer` header is absent or malformed
+    pub static ref ErrAuthTokenMissing: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20001, "Missing Access Token");
+
+    /// Invalid access token - bad signature, issuer, audience or subject
+    pub static ref ErrAuthTokenInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20002, "Invalid Access Token");
+
+    /// Expired access token - the client should log in again
+    pub static ref ErrAuthTokenExpired: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20003, "Access Token Expired");
+
+    /// Invalid email or password - email login failed
+    pub static ref ErrEmailOrPasswordInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20004, "Invalid Email Or Password");
+
+    /// Email already registered - registration with an email bound to another account
+    pub static ref ErrEmailRegistered: AppError =
+        AppError::new(StatusCode::CONFLICT, 20005, "Email Already Registered");
+
+    /// Invalid refresh token - unknown, expired or revoked refresh token
+    pub static ref ErrRefreshTokenInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20006, "Invalid Refresh Token");
+
+    /// Refresh token reused - an already rotated refresh token was replayed, the whole session is
+    /// revoked
+    pub static ref ErrRefreshTokenReused: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20007, "Refresh Token Reused");
+
+    /// Revoked access token - the session was logged out or revoked
+    pub static ref ErrAuthTokenRevoked: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20008, "Access Token Revoked");
+
+    /// Wrong verification code - the code does not match, the user may try again
+    pub static ref ErrVerifyCodeMismatch: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20009, "Invalid Verification Code");
+
+    /// Expired verification code - no code is pending, it expired or was already used
+    pub static ref ErrVerifyCodeExpired: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20010, "Verification Code Expired");
+
+    /// Too many verification attempts - the code is burnt and a new one must be requested
+    pub static ref ErrVerifyCodeAttempts: AppError =
+        AppError::new(StatusCode::TOO_MANY_REQUESTS, 20011, "Too Many Verification Attempts");
+
+    /// Verification code requested too often - resend cooldown for the target or IP is running
+    pub static ref ErrVerifyCodeTooFrequent: AppError =
+        AppError::new(StatusCode::TOO_MANY_REQUESTS, 20012, "Verification Code Requested Too Frequently");
+
+    /// Permission denied - the caller lacks the permission required by the route
+    pub static ref ErrPermissionDenied: AppError =
+        AppError::new(StatusCode::FORBIDDEN, 20013, "Permission Denied");
+
+    /// Role not found - the role name does not exist
+    pub static ref ErrRoleNotFound: AppError =
+        AppError::new(StatusCode::NOT_FOUND, 20014, "Role Not Found");
+
+    /// Invalid API key - the `X-Api-Key` header does not match any active key
+    pub static ref ErrApiKeyInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20015, "Invalid API Key");
+
+    /// API key expired - the key is past its expiry time
+    pub static ref ErrApiKeyExpired: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20016, "API Key Expired");
+
+    /// Session not found - the session expired, was revoked or belongs to another user
+    pub static ref ErrSessionNotFound: AppError =
+        AppError::new(StatusCode::NOT_FOUND, 20017, "Session Not Found");
+
+    /// Invalid reset token - the password reset token is unknown, expired or already used
+    pub static ref ErrResetTokenInvalid: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20018, "Invalid Reset Token");
+
+    /// Two-factor authentication not enrolled - no authenticator is being enrolled or enabled
+    pub static ref ErrTotpNotEnrolled: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20019, "Two-Factor Authentication Not Enrolled");
+
+    /// Two-factor authentication already enabled - disable it before enrolling again
+    pub static ref ErrTotpAlreadyEnabled: AppError =
+        AppError::new(StatusCode::CONFLICT, 20020, "Two-Factor Authentication Already Enabled");
+
+    /// Invalid two-factor code - the TOTP or recovery code does not match or was already used
+    pub static ref ErrTotpCodeInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20021, "Invalid Two-Factor Code");
+
+    /// Invalid challenge - the login challenge expired or too many codes were tried
+    pub static ref ErrTwoFactorChallengeInvalid: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20022, "Invalid Two-Factor Challenge");
+
+    /// Unknown login provider - no OpenID Connect provider is configured under this name
+    pub static ref ErrOidcProviderUnknown: AppError =
+        AppError::new(StatusCode::NOT_FOUND, 20023, "Unknown Login Provider");
+
+    /// Invalid login state - the `state` of the callback is unknown, expired or already used
+    pub static ref ErrOidcStateInvalid: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20024, "Invalid Login State");
+
+    /// Provider login failed - the provider rejected the code or returned an invalid ID token
+    pub static ref ErrOidcLoginFailed: AppError =
+        AppError::new(StatusCode::UNAUTHORIZED, 20025, "Provider Login Failed");
+
+    /// Identity already linked - the provider account belongs to another user
+    pub static ref ErrOidcIdentityLinked: AppError =
+        AppError::new(StatusCode::CONFLICT, 20026, "Identity Linked To Another Account");
+
+    /// Last login method - unlinking the identity would leave the account without a way to log in
+    pub static ref ErrOidcLastLoginMethod: AppError =
+        AppError::new(StatusCode::CONFLICT, 20027, "Cannot Unlink The Last Login Method");
+
+    /// Identity not found - the identity does not exist or belongs to another user
+    pub static ref ErrIdentityNotFound: AppError =
+        AppError::new(StatusCode::NOT_FOUND, 20028, "Identity Not Found");
+
+    /// Too many failed attempts - the account or the client IP is blocked for a while, the
+    /// `Retry-After` header tells for how long
+    pub static ref ErrTooManyFailedAttempts: AppError =
+        AppError::new(StatusCode::TOO_MANY_REQUESTS, 20029, "Too Many Failed Attempts, Try Again Later");
+
+    /// Account deleted - the account was deleted by an administrator and cannot log in
+    pub static ref ErrAccountDeleted: AppError =
+        AppError::new(StatusCode::FORBIDDEN, 20030, "Account Deleted");
+}
+
+// WeChat login errors caused by the client
+lazy_static! {
+    /// Invalid WeChat login code - the code is invalid, expired or already used
+    pub static ref ErrWechatInvalidCode: AppError =
+        AppError::new(StatusCode::BAD_REQUEST, 20100, "Invalid WeChat Login Code");
+
+    /// WeChat login rate limited - WeChat rejected the call because of its minute quota
+    pub static ref ErrWechatRateLimited: AppError =
+        AppError::new(StatusCode::TOO_MANY_REQUESTS, 20101, "WeChat Login Too Frequent");
+
+    /// WeChat risky user - WeChat refused to log in a high risk user
+    pub static ref ErrWechatRiskUser: AppError =
+        AppError::new(StatusCode::FORBIDDEN, 20102, "WeChat Login Blocked");
 }
 
 lazy_static! {
     /// Not implemented - requested feature is not implemented
     pub static ref ErrNotImplemented: AppError =
         AppError::new(StatusCode::NOT_IMPLEMENTED, 50000, "Not Implemented");
+
+    /// Export error - the personal data export could not be encoded
+    pub static ref ErrExportEncode: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50001, "Server Internal Error");
+
+    /// Image encode error - a decoded image could not be encoded again
+    pub static ref ErrImageEncode: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50002, "Server Internal Error");
 }
 
 // Database specific errors
@@ -145,9 +331,81 @@ lazy_static! {
         AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50227, "Server Internal Error");
 }
 
+lazy_static! {
+    /// Token issue error - internal server error when an access token cannot be signed
+    pub static ref ErrAuthTokenIssue: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50300, "Server Internal Error");
+
+    /// Password hash error - internal server error when hashing or verifying a password fails
+    pub static ref ErrPasswordHash: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50301, "Server Internal Error");
+
+    /// Data cipher error - internal server error when encrypting or decrypting data at rest
+    pub static ref ErrDataCipher: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50302, "Server Internal Error");
+}
+
 lazy_static! {
     pub static ref ErrWechatLogin: AppError =
         AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50500, "Server Internal Error");
     pub static ref ErrUnmarshalJSON: AppError =
         AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50501, "Server Internal Error");
+
+    /// WeChat busy - WeChat answered with errcode -1
+    pub static ref ErrWechatBusy: AppError =
+        AppError::new(StatusCode::SERVICE_UNAVAILABLE, 50502, "Server Internal Error");
+
+    /// WeChat configuration error - WeChat rejected the configured appid or secret
+    pub static ref ErrWechatConfig: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50503, "Server Internal Error");
+}
+
+// Outbound mail errors
+lazy_static! {
+    /// Mail delivery error - the mail backend rejected or failed to deliver the message
+    pub static ref ErrMailDelivery: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50600, "Server Internal Error");
+
+    /// Mail timeout - the mail backend did not answer in time
+    pub static ref ErrMailTimeout: AppError =
+        AppError::new(StatusCode::SERVICE_UNAVAILABLE, 50601, "Server Internal Error");
+}
+
+// SMS errors
+lazy_static! {
+    /// SMS delivery error - the SMS provider rejected or failed to send the message
+    pub static ref ErrSmsDelivery: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50700, "Server Internal Error");
+
+    /// SMS timeout - the SMS provider did not answer in time
+    pub static ref ErrSmsTimeout: AppError =
+        AppError::new(StatusCode::SERVICE_UNAVAILABLE, 50701, "Server Internal Error");
+}
+
+// OpenID Connect provider errors
+lazy_static! {
+    /// Provider unavailable - discovery, JWKS, token or userinfo endpoint failed or is misconfigured
+    pub static ref ErrOidcProvider: AppError =
+        AppError::new(StatusCode::BAD_GATEWAY, 50800, "Server Internal Error");
+}
+
+// Blob storage errors
+lazy_static! {
+    /// Blob store error - the storage backend failed to read or write a file
+    pub static ref ErrBlobStore: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 50900, "Server Internal Error");
+
+    /// Blob store timeout - the storage backend did not answer in time
+    pub static ref ErrBlobTimeout: AppError =
+        AppError::new(StatusCode::SERVICE_UNAVAILABLE, 50901, "Server Internal Error");
+}
+
+lazy_static! {
+    /// Search index error - the search backend failed to query or update its index
+    pub static ref ErrSearchIndex: AppError =
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, 51000, "Server Internal Error");
+
+    /// Search index timeout - the search backend did not answer in time
+    pub static ref ErrSearchTimeout: AppError =
+        AppError::new(StatusCode::SERVICE_UNAVAILABLE, 51001, "Server Internal Error");
 }
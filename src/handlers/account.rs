@@ -0,0 +1,79 @@
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use tracing::error;
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::services::account::AccountService;
+use crate::services::account::export_zip;
+use crate::types::account::DeleteAccountResponse;
+use crate::types::account::ExportFormat;
+use crate::types::account::ExportRequest;
+
+/// Requests the deletion of the caller's account after the grace period
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<DeleteAccountResponse>` - When the account will be erased
+pub async fn delete_me(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<DeleteAccountResponse> {
+    info!("user {} request account deletion", auth.user_id);
+    AccountService::delete_me(state, auth).await
+}
+
+/// Downloads everything stored about the caller as a JSON document or a ZIP archive
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the format
+///
+/// # Returns
+/// * `Result<Response, AppError>` - The archive as an attachment
+pub async fn export_me(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Query(req): Query<ExportRequest>,
+) -> core::result::Result<Response, AppError> {
+    let user_id = auth.user_id;
+    let export = AccountService::export(state, auth).await?;
+    let (content_type, extension, body) = match req.format {
+        ExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_vec_pretty(&export).map_err(|err| {
+                error!("encode export of user {} error {}", user_id, err);
+                errors::ErrExportEncode.clone()
+            })?,
+        ),
+        ExportFormat::Zip => (
+            "application/zip",
+            "zip",
+            export_zip(&export).map_err(|err| {
+                error!("zip export of user {} error {}", user_id, err);
+                errors::ErrExportEncode.clone()
+            })?,
+        ),
+    };
+    let disposition = format!("attachment; filename=\"user-{}-export.{}\"", user_id, extension);
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,35 @@
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+
+use crate::blob;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+
+/// Serves a stored file, e.g. an uploaded avatar of the local blob backend
+///
+/// Files are named after their content, so they never change and may be cached forever.
+///
+/// # Arguments
+/// * `state` - Application state containing the blob store
+/// * `key` - Key of the file, e.g. `avatars/{sha256}.jpg`
+///
+/// # Returns
+/// * `Result<Response, AppError>` - The file, `ErrBlobNotFound` when there is none
+pub async fn get_blob(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> core::result::Result<Response, AppError> {
+    let data = state.blobs.get(&key).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, blob::content_type(&key)),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        data,
+    )
+        .into_response())
+}
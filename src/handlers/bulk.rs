@@ -0,0 +1,74 @@
+use std::io;
+
+use axum::body::Body;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum_valid::Valid;
+use futures_util::TryStreamExt;
+use tokio_util::io::StreamReader;
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::services::bulk::BulkService;
+use crate::types::bulk::ExportUsersRequest;
+use crate::types::bulk::ImportReport;
+use crate::types::bulk::ImportUsersRequest;
+
+/// Imports the users of a CSV or NDJSON body, requires `user:write`
+///
+/// The body is read as a stream and inserted batch by batch, rows failing validation are listed
+/// in the report with their line instead of failing the request.
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the format and the dry run flag
+/// * `body` - CSV with a header line or NDJSON
+///
+/// # Returns
+/// * `Result<ImportReport>` - Rows imported and rejected
+pub async fn import_users(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Query(req): Query<ImportUsersRequest>,
+    body: Body,
+) -> Result<ImportReport> {
+    info!("user {} import users format {:?} dry run {}", auth.user_id, req.format, req.dry_run);
+    let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    BulkService::import_users(state, req, reader).await
+}
+
+/// Streams the users matching a filter as a CSV or NDJSON attachment, requires `user:read`
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the format and the filter
+///
+/// # Returns
+/// * `Result<Response, AppError>` - The export, encoded while it is sent
+pub async fn export_users(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Valid(Query(req)): Valid<Query<ExportUsersRequest>>,
+) -> core::result::Result<Response, AppError> {
+    info!("user {} export users {:?}", auth.user_id, req);
+    let format = req.format;
+    let chunks =
+        BulkService::export_users(state, req).map_err(|err| io::Error::other(format!("{:?}", err)));
+    let disposition = format!("attachment; filename=\"users.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response())
+}
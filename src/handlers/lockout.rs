@@ -0,0 +1,33 @@
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::state::AppState;
+use crate::services::lockout::LockoutService;
+use crate::types::lockout::UnlockRequest;
+use crate::types::lockout::UnlockResponse;
+use crate::types::rbac::UserIdPath;
+
+/// Lifts the brute-force lockout of a user, requires `user:write`
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the user ID
+/// * `req` - Query parameters containing an optional client IP to unlock
+///
+/// # Returns
+/// * `Result<UnlockResponse>` - Whether the user was locked
+pub async fn unlock_user(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<UserIdPath>,
+    Valid(Query(req)): Valid<Query<UnlockRequest>>,
+) -> Result<UnlockResponse> {
+    info!("admin {} unlock user {} {:?}", auth.user_id, path.id, req);
+    LockoutService::unlock(state, path.id, req).await
+}
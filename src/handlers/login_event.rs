@@ -0,0 +1,36 @@
+use axum::extract::OriginalUri;
+use axum::extract::Query;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::debug;
+
+use crate::core::PageResult;
+use crate::core::auth::AuthUser;
+use crate::core::page::PageResponse;
+use crate::core::rest::AppResult;
+use crate::core::state::AppState;
+use crate::models::login_event::LoginEvent;
+use crate::services::login_event::LoginEventService;
+use crate::types::login_event::LoginEventListRequest;
+
+/// Lists the login history of the caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `uri` - Request URI, repeated by the `Link` header
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the page number and size
+///
+/// # Returns
+/// * `PageResult<LoginEvent>` - One page of login attempts, most recent first, with its `Link`
+///   header
+pub async fn list_logins(
+    auth: AuthUser,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+    Valid(Query(req)): Valid<Query<LoginEventListRequest>>,
+) -> PageResult<LoginEvent> {
+    debug!("user {} list logins {:?}", auth.user_id, req);
+    let AppResult(page) = LoginEventService::list(state, auth, req).await?;
+    Ok(PageResponse::new(page, uri))
+}
@@ -1,3 +1,13 @@
+pub mod account;
+pub mod blob;
+pub mod bulk;
 pub mod foo;
 pub mod health;
+pub mod lockout;
+pub mod login_event;
+pub mod oidc;
+pub mod rbac;
+pub mod session;
+pub mod token;
+pub mod two_factor;
 pub mod user;
@@ -0,0 +1,118 @@
+use axum::Json;
+use axum::extract::Path;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::debug;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::session::ClientMeta;
+use crate::core::state::AppState;
+use crate::services::oidc::OidcService;
+use crate::types::oidc::IdentityIdPath;
+use crate::types::oidc::IdentityListResponse;
+use crate::types::oidc::OidcAuthorizeResponse;
+use crate::types::oidc::OidcCallbackRequest;
+use crate::types::oidc::OidcCallbackResponse;
+use crate::types::oidc::OidcProvidersResponse;
+use crate::types::oidc::ProviderPath;
+use crate::types::oidc::UnlinkIdentityResponse;
+
+/// Lists the configured login providers
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<OidcProvidersResponse>` - Names of the providers
+pub async fn providers(State(state): State<AppState>) -> Result<OidcProvidersResponse> {
+    OidcService::providers(state).await
+}
+
+/// Starts a login through a provider
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the provider name
+///
+/// # Returns
+/// * `Result<OidcAuthorizeResponse>` - URL of the provider login page
+pub async fn authorize(
+    State(state): State<AppState>,
+    Path(path): Path<ProviderPath>,
+) -> Result<OidcAuthorizeResponse> {
+    debug!("oidc authorize {}", path.provider);
+    OidcService::authorize(state, &path.provider).await
+}
+
+/// Starts linking a provider account to the caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the provider name
+///
+/// # Returns
+/// * `Result<OidcAuthorizeResponse>` - URL of the provider login page
+pub async fn link(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<ProviderPath>,
+) -> Result<OidcAuthorizeResponse> {
+    debug!("user {} link {}", auth.user_id, path.provider);
+    OidcService::link(state, auth, &path.provider).await
+}
+
+/// Finishes a login or link with the code received by the redirect URI
+///
+/// # Arguments
+/// * `client` - Device the user logs in from
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the provider name
+/// * `req` - Code and state received by the redirect URI
+///
+/// # Returns
+/// * `Result<OidcCallbackResponse>` - Login response and the identity
+pub async fn callback(
+    client: ClientMeta,
+    State(state): State<AppState>,
+    Path(path): Path<ProviderPath>,
+    Valid(Json(req)): Valid<Json<OidcCallbackRequest>>,
+) -> Result<OidcCallbackResponse> {
+    debug!("oidc callback {}", path.provider);
+    OidcService::callback(state, client, &path.provider, req).await
+}
+
+/// Lists the provider accounts linked to the caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<IdentityListResponse>` - Linked identities
+pub async fn list_identities(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<IdentityListResponse> {
+    debug!("user {} list identities", auth.user_id);
+    OidcService::identities(state, auth).await
+}
+
+/// Unlinks a provider account from the caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the identity ID
+///
+/// # Returns
+/// * `Result<UnlinkIdentityResponse>` - Response indicating success
+pub async fn unlink_identity(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<IdentityIdPath>,
+) -> Result<UnlinkIdentityResponse> {
+    debug!("user {} unlink identity {}", auth.user_id, path.id);
+    OidcService::unlink(state, auth, path.id).await
+}
@@ -0,0 +1,80 @@
+use axum::Json;
+use axum::extract::Path;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::state::AppState;
+use crate::services::rbac::RbacService;
+use crate::types::rbac::AssignRoleRequest;
+use crate::types::rbac::RoleListResponse;
+use crate::types::rbac::UpdateRoleResponse;
+use crate::types::rbac::UserIdPath;
+use crate::types::rbac::UserRolePath;
+use crate::types::rbac::UserRolesResponse;
+
+/// Lists every role, requires `role:admin`
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<RoleListResponse>` - All roles
+pub async fn list_roles(State(state): State<AppState>) -> Result<RoleListResponse> {
+    RbacService::list_roles(state).await
+}
+
+/// Lists the roles of a user, requires `role:admin`
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the user ID
+///
+/// # Returns
+/// * `Result<UserRolesResponse>` - Roles of the user
+pub async fn user_roles(
+    State(state): State<AppState>,
+    Path(path): Path<UserIdPath>,
+) -> Result<UserRolesResponse> {
+    RbacService::user_roles(state, path.id).await
+}
+
+/// Assigns a role to a user, requires `role:admin`
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the user ID
+/// * `req` - Request containing the role name
+///
+/// # Returns
+/// * `Result<UpdateRoleResponse>` - Assignment result
+pub async fn assign_role(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<UserIdPath>,
+    Valid(Json(req)): Valid<Json<AssignRoleRequest>>,
+) -> Result<UpdateRoleResponse> {
+    info!("admin {} assign role {} to user {}", auth.user_id, req.role, path.id);
+    RbacService::assign_role(state, path.id, req).await
+}
+
+/// Removes a role from a user, requires `role:admin`
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the user ID and the role name
+///
+/// # Returns
+/// * `Result<UpdateRoleResponse>` - Removal result
+pub async fn remove_role(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<UserRolePath>,
+) -> Result<UpdateRoleResponse> {
+    info!("admin {} remove role {} from user {}", auth.user_id, path.role, path.id);
+    RbacService::remove_role(state, path.id, &path.role).await
+}
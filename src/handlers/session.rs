@@ -0,0 +1,45 @@
+use axum::extract::Path;
+use axum::extract::State;
+use tracing::debug;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::state::AppState;
+use crate::services::session::SessionService;
+use crate::types::session::RevokeSessionResponse;
+use crate::types::session::SessionIdPath;
+use crate::types::session::SessionListResponse;
+
+/// Lists the logged-in devices of the caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<SessionListResponse>` - Live sessions of the caller
+pub async fn list_sessions(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<SessionListResponse> {
+    debug!("user {} list sessions", auth.user_id);
+    SessionService::list(state, auth).await
+}
+
+/// Signs out one of the caller's devices
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `path` - Path parameters containing the session ID
+///
+/// # Returns
+/// * `Result<RevokeSessionResponse>` - Response indicating success
+pub async fn revoke_session(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(path): Path<SessionIdPath>,
+) -> Result<RevokeSessionResponse> {
+    debug!("user {} revoke session {}", auth.user_id, path.id);
+    SessionService::revoke(state, auth, &path.id).await
+}
@@ -0,0 +1,41 @@
+use axum::Json;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::debug;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::state::AppState;
+use crate::services::token::TokenService;
+use crate::types::token::LogoutResponse;
+use crate::types::token::RefreshTokenRequest;
+use crate::types::token::RefreshTokenResponse;
+
+/// Exchanges a refresh token for a new access and refresh token pair
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+/// * `req` - Refresh request containing the refresh token
+///
+/// # Returns
+/// * `Result<RefreshTokenResponse>` - The rotated token pair
+pub async fn refresh(
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<RefreshTokenRequest>>,
+) -> Result<RefreshTokenResponse> {
+    debug!("refresh token");
+    TokenService::refresh(state, req).await
+}
+
+/// Logs out the session of the current access token
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<LogoutResponse>` - Response indicating success
+pub async fn logout(auth: AuthUser, State(state): State<AppState>) -> Result<LogoutResponse> {
+    debug!("logout user {}", auth.user_id);
+    TokenService::logout(state, auth).await
+}
@@ -0,0 +1,104 @@
+use axum::Json;
+use axum::extract::State;
+use axum_valid::Valid;
+use tracing::debug;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::session::ClientMeta;
+use crate::core::state::AppState;
+use crate::services::two_factor::TwoFactorService;
+use crate::types::two_factor::DisableTotpResponse;
+use crate::types::two_factor::EnrollTotpResponse;
+use crate::types::two_factor::RecoveryCodesResponse;
+use crate::types::two_factor::TwoFactorCodeRequest;
+use crate::types::two_factor::TwoFactorLoginRequest;
+use crate::types::two_factor::TwoFactorLoginResponse;
+
+/// Starts the enrollment of a TOTP authenticator
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+///
+/// # Returns
+/// * `Result<EnrollTotpResponse>` - Secret and `otpauth://` URI to scan
+pub async fn enroll_totp(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<EnrollTotpResponse> {
+    debug!("user {} enroll totp", auth.user_id);
+    TwoFactorService::enroll(state, auth).await
+}
+
+/// Enables the enrolled authenticator with a first code
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing a TOTP code
+///
+/// # Returns
+/// * `Result<RecoveryCodesResponse>` - Recovery codes, shown only once
+pub async fn activate_totp(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<TwoFactorCodeRequest>>,
+) -> Result<RecoveryCodesResponse> {
+    debug!("user {} activate totp", auth.user_id);
+    TwoFactorService::activate(state, auth, req).await
+}
+
+/// Disables two-factor authentication
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing a TOTP or recovery code
+///
+/// # Returns
+/// * `Result<DisableTotpResponse>` - Response indicating success
+pub async fn disable_totp(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<TwoFactorCodeRequest>>,
+) -> Result<DisableTotpResponse> {
+    debug!("user {} disable totp", auth.user_id);
+    TwoFactorService::disable(state, auth, req).await
+}
+
+/// Replaces the recovery codes
+///
+/// # Arguments
+/// * `auth` - Authenticated caller
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing a TOTP code
+///
+/// # Returns
+/// * `Result<RecoveryCodesResponse>` - New recovery codes
+pub async fn regenerate_recovery_codes(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<TwoFactorCodeRequest>>,
+) -> Result<RecoveryCodesResponse> {
+    debug!("user {} regenerate recovery codes", auth.user_id);
+    TwoFactorService::regenerate_recovery_codes(state, auth, req).await
+}
+
+/// Completes an email login with the second factor
+///
+/// # Arguments
+/// * `client` - Device the user logs in from
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the challenge of the password step and a code
+///
+/// # Returns
+/// * `Result<TwoFactorLoginResponse>` - Access and refresh tokens
+pub async fn two_factor_login(
+    client: ClientMeta,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<TwoFactorLoginRequest>>,
+) -> Result<TwoFactorLoginResponse> {
+    debug!("two factor login");
+    TwoFactorService::login(state, client, req).await
+}
@@ -1,78 +1,268 @@
+use std::io;
+
 use axum::Json;
+use axum::extract::Multipart;
+use axum::extract::OriginalUri;
 use axum::extract::Path;
 use axum::extract::Query;
 use axum::extract::State;
+use axum::extract::multipart::MultipartError;
+use axum::http::StatusCode;
 use axum_valid::Valid;
+use futures_util::TryStreamExt;
+use tokio_util::io::StreamReader;
 use tracing::debug;
 use tracing::info;
 
+use crate::core::PageResult;
 use crate::core::Result;
+use crate::core::VersionedResult;
+use crate::core::auth::AuthUser;
+use crate::core::client_ip::ClientIp;
+use crate::core::etag::IfMatch;
+use crate::core::etag::Versioned;
+use crate::core::page::PageResponse;
+use crate::core::rest::AppResult;
+use crate::core::session::ClientMeta;
 use crate::core::state::AppState;
+use crate::errors;
 use crate::models::user::UserInfo;
+use crate::services::avatar::AvatarService;
 use crate::services::user::UserService;
+use crate::types::avatar::UploadAvatarResponse;
 use crate::types::user::BindEmailRequest;
 use crate::types::user::BindEmailResponse;
 use crate::types::user::ByUserIdRequest;
+use crate::types::user::DeletedUsersListRequest;
+use crate::types::user::EmailLoginRequest;
+use crate::types::user::EmailLoginResponse;
+use crate::types::user::EmailRegisterRequest;
+use crate::types::user::EmailRegisterResponse;
+use crate::types::user::ForgotPasswordRequest;
+use crate::types::user::ForgotPasswordResponse;
 use crate::types::user::PreBindEmailRequest;
 use crate::types::user::PreBindEmailResponse;
+use crate::types::user::PreEmailRegisterRequest;
+use crate::types::user::PreEmailRegisterResponse;
 use crate::types::user::RandomUserRequest;
 use crate::types::user::RandomUserResponse;
+use crate::types::user::ResetPasswordRequest;
+use crate::types::user::ResetPasswordResponse;
+use crate::types::user::RestoreUserResponse;
+use crate::types::user::SearchUsersRequest;
+use crate::types::user::SmsLoginRequest;
+use crate::types::user::SmsLoginResponse;
+use crate::types::user::SmsPreRequest;
+use crate::types::user::SmsPreResponse;
+use crate::types::user::UpdateProfileRequest;
+use crate::types::user::UpdateProfileResponse;
+use crate::types::user::UserSearchHit;
+use crate::types::user::UsersListRequest;
 use crate::types::user::WxMiniLoginRequest;
 use crate::types::user::WxMiniLoginResponse;
 
-/// Binds an email address to a user account
+/// Binds an email address to the caller's account
 ///
 /// # Arguments
+/// * `auth` - Authenticated caller taken from the access token
+/// * `ip` - Client IP, locked after too many wrong codes
 /// * `state` - Application state containing shared resources
-/// * `req` - Email binding request containing user email
+/// * `req` - Email binding request containing user email and validation code
 ///
 /// # Returns
 /// * `Result<BindEmailResponse>` - Binding operation result
 pub async fn bind_email(
+    auth: AuthUser,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Valid(Json(req)): Valid<Json<BindEmailRequest>>,
 ) -> Result<BindEmailResponse> {
-    info!("user email: {req:?}");
-    UserService::bind_email(state, req).await
+    info!("user {} bind email {} from {}", auth.user_id, req.email, ip);
+    UserService::bind_email(state, auth, &ip, req).await
 }
 
 /// Handles WeChat mini-program login
 ///
 /// # Arguments
+/// * `client` - Device the user logs in from
 /// * `state` - Application state containing shared resources
 /// * `req` - WeChat mini-program login request containing authorization code
 ///
 /// # Returns
 /// * `Result<WxMiniLoginResponse>` - Login response with user information
 pub async fn wechat_login(
+    client: ClientMeta,
     State(state): State<AppState>,
     Json(req): Json<WxMiniLoginRequest>,
 ) -> Result<WxMiniLoginResponse> {
     debug!("code {}", req.code);
-    UserService::wx_login(state, req).await
+    UserService::wx_login(state, client, req).await
+}
+
+/// Handles email + password login
+///
+/// # Arguments
+/// * `client` - Device the user logs in from
+/// * `state` - Application state containing shared resources
+/// * `req` - Email login request containing email and password
+///
+/// # Returns
+/// * `Result<EmailLoginResponse>` - Login response with the access token
+pub async fn email_login(
+    client: ClientMeta,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<EmailLoginRequest>>,
+) -> Result<EmailLoginResponse> {
+    debug!("email login {}", req.email);
+    UserService::email_login(state, client, req).await
+}
+
+/// Sends the validation code required to register with an email
+///
+/// # Arguments
+/// * `ip` - Client IP, rate limited alongside the email
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the email to register
+///
+/// # Returns
+/// * `Result<PreEmailRegisterResponse>` - Lifetime of the code and delay before a resend
+pub async fn pre_email_register(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<PreEmailRegisterRequest>>,
+) -> Result<PreEmailRegisterResponse> {
+    info!("pre email register {} from {}", req.email, ip);
+    UserService::pre_email_register(state, &ip, req).await
 }
 
+/// Registers a new account with email + password and the mailed validation code
+///
+/// # Arguments
+/// * `client` - Device the user logs in from
+/// * `state` - Application state containing shared resources
+/// * `req` - Email registration request containing email, validation code, password and
+///   optional nickname
+///
+/// # Returns
+/// * `Result<EmailRegisterResponse>` - Login response with the access token of the new user
+pub async fn email_register(
+    client: ClientMeta,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<EmailRegisterRequest>>,
+) -> Result<EmailRegisterResponse> {
+    info!("email register {}", req.email);
+    UserService::email_register(state, client, req).await
+}
+
+/// Sends an SMS login code
+///
+/// # Arguments
+/// * `ip` - Client IP, rate limited alongside the phone number
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the phone number
+///
+/// # Returns
+/// * `Result<SmsPreResponse>` - Lifetime of the code and delay before a resend
+pub async fn sms_pre(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<SmsPreRequest>>,
+) -> Result<SmsPreResponse> {
+    info!("sms pre {} from {}", req.phone, ip);
+    UserService::sms_pre(state, &ip, req).await
+}
+
+/// Handles phone number + SMS code login
+///
+/// # Arguments
+/// * `client` - Device the user logs in from
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the phone number and the code
+///
+/// # Returns
+/// * `Result<SmsLoginResponse>` - Login response with the access token, or a two-factor
+///   challenge
+pub async fn sms_login(
+    client: ClientMeta,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<SmsLoginRequest>>,
+) -> Result<SmsLoginResponse> {
+    debug!("sms login {}", req.phone);
+    UserService::sms_login(state, client, req).await
+}
+
+/// Mails a password reset link
+///
+/// # Arguments
+/// * `ip` - Client IP, logged with the request
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the email address
+///
+/// # Returns
+/// * `Result<ForgotPasswordResponse>` - Lifetime of the link
+pub async fn forgot_password(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<ForgotPasswordRequest>>,
+) -> Result<ForgotPasswordResponse> {
+    debug!("forgot password {} from {}", req.email, ip);
+    UserService::forgot_password(state, &ip, req).await
+}
+
+/// Sets a new password with the token of a reset link
+///
+/// # Arguments
+/// * `state` - Application state containing shared resources
+/// * `req` - Request containing the token and the new password
+///
+/// # Returns
+/// * `Result<ResetPasswordResponse>` - Response indicating success
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<ResetPasswordRequest>>,
+) -> Result<ResetPasswordResponse> {
+    debug!("reset password");
+    UserService::reset_password(state, req).await
+}
+
+/// Retrieves a user by ID, requires a logged-in caller
+///
+/// # Arguments
+/// * `auth` - Authenticated caller taken from the access token
+/// * `state` - Application state containing shared resources
+/// * `req` - Path parameters containing the user ID
+///
+/// # Returns
+/// * `VersionedResult<UserInfo>` - User information, with the `ETag` of its version
 pub async fn user_by_id(
+    auth: AuthUser,
     State(state): State<AppState>,
     Path(req): Path<ByUserIdRequest>,
-) -> Result<UserInfo> {
-    info!("user by id {:?}", req);
-    UserService::by_id(state, req).await
+) -> VersionedResult<UserInfo> {
+    info!("user {} query user by id {:?}", auth.user_id, req);
+    let AppResult(user) = UserService::by_id(state, req).await?;
+    let version = user.version;
+    Ok(Versioned::new(user, version))
 }
 
-/// Pre-validates email for binding operation
+/// Sends a validation code to the email the caller wants to bind
 ///
 /// # Arguments
+/// * `auth` - Authenticated caller taken from the access token
+/// * `ip` - Client IP, rate limited alongside the email
+/// * `state` - Application state containing shared resources
 /// * `req` - Pre-binding email request containing email to validate
 ///
 /// # Returns
-/// * `Result<PreBindEmailResponse>` - Pre-validation result
+/// * `Result<PreBindEmailResponse>` - Lifetime of the code and delay before a resend
 pub async fn pre_bind_email(
+    auth: AuthUser,
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
-    Json(req): Json<PreBindEmailRequest>,
+    Valid(Json(req)): Valid<Json<PreBindEmailRequest>>,
 ) -> Result<PreBindEmailResponse> {
-    info!("user {}", req.email);
-    UserService::pre_bind_email(state, req).await
+    info!("user {} pre bind email {} from {}", auth.user_id, req.email, ip);
+    UserService::pre_bind_email(state, auth, &ip, req).await
 }
 
 pub async fn random_user(
@@ -82,3 +272,154 @@ pub async fn random_user(
     info!("create random user");
     UserService::random_user(state, req).await
 }
+
+/// Changes the caller's profile, fields left out of the body are unchanged
+///
+/// The `If-Match` header must carry the `ETag` the profile was read with.
+///
+/// # Arguments
+/// * `auth` - Authenticated caller taken from the access token
+/// * `if_match` - `ETag` of the profile the change was made on
+/// * `state` - Application state containing shared resources
+/// * `req` - Fields to change, `null` resets a field
+///
+/// # Returns
+/// * `VersionedResult<UpdateProfileResponse>` - The updated user, with the `ETag` of its new
+///   version
+pub async fn update_me(
+    auth: AuthUser,
+    if_match: IfMatch,
+    State(state): State<AppState>,
+    Valid(Json(req)): Valid<Json<UpdateProfileRequest>>,
+) -> VersionedResult<UpdateProfileResponse> {
+    info!("user {} update profile if match {:?}", auth.user_id, if_match);
+    let AppResult(user) = UserService::update_me(state, auth, if_match, req).await?;
+    let version = user.version;
+    Ok(Versioned::new(user, version))
+}
+
+/// Uploads a new avatar for the caller
+///
+/// The picture is sent as the `file` field of a `multipart/form-data` body. Its type is
+/// recognized from its content and its URL written to the `avatar` of the profile.
+///
+/// Unlike `PATCH /user/me` it takes no `If-Match`: the upload sets the avatar whatever the
+/// profile looked like, there is no change of the client to lose. It still moves the profile to
+/// a new version, so an edit based on the previous `ETag` gets a conflict.
+///
+/// # Arguments
+/// * `auth` - Authenticated caller taken from the access token
+/// * `state` - Application state containing shared resources
+/// * `multipart` - Multipart body holding the picture
+///
+/// # Returns
+/// * `VersionedResult<UploadAvatarResponse>` - URL of the stored picture, with the `ETag` of the
+///   updated profile
+pub async fn upload_avatar(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> VersionedResult<UploadAvatarResponse> {
+    let multipart_error = |err: MultipartError| {
+        debug!("read avatar multipart error {}", err);
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            errors::ErrAvatarTooLarge.clone()
+        } else {
+            errors::ErrBadRequest.clone()
+        }
+    };
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        info!("user {} upload avatar {:?}", auth.user_id, field.file_name());
+        let file = StreamReader::new(field.map_err(io::Error::other));
+        let AppResult(resp) = AvatarService::upload(state, auth, file).await?;
+        let version = resp.version;
+        return Ok(Versioned::new(resp, version));
+    }
+    Err(errors::ErrBadRequest.clone())
+}
+
+/// Lists users page by page, optionally searching nicknames and signatures, requires `user:read`
+///
+/// Requests with a `cursor` parameter are answered in cursor mode, slice after slice.
+///
+/// # Arguments
+/// * `uri` - Request URI, repeated by the `Link` header
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the page and the search text
+///
+/// # Returns
+/// * `PageResult<UserInfo>` - One page or slice of users with its `Link` header
+pub async fn list_users(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+    Valid(Query(req)): Valid<Query<UsersListRequest>>,
+) -> PageResult<UserInfo> {
+    debug!("list users {:?}", req);
+    if req.cursor.is_some() {
+        let AppResult(page) = UserService::list_by_cursor(state, req).await?;
+        return Ok(PageResponse::cursor(page, uri));
+    }
+    let AppResult(page) = UserService::list(state, req).await?;
+    Ok(PageResponse::new(page, uri))
+}
+
+/// Searches the nicknames and signatures of the users, requires `user:read`
+///
+/// Users come most relevant first with the matching parts of their fields highlighted.
+///
+/// # Arguments
+/// * `uri` - Request URI, repeated by the `Link` header
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the search text and the page
+///
+/// # Returns
+/// * `PageResult<UserSearchHit>` - One page of matching users with its `Link` header
+pub async fn search_users(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+    Valid(Query(req)): Valid<Query<SearchUsersRequest>>,
+) -> PageResult<UserSearchHit> {
+    debug!("search users {:?}", req);
+    let AppResult(page) = UserService::search(state, req).await?;
+    Ok(PageResponse::new(page, uri))
+}
+
+/// Lists the soft-deleted users page by page, requires `user:read`
+///
+/// # Arguments
+/// * `uri` - Request URI, repeated by the `Link` header
+/// * `state` - Application state containing shared resources
+/// * `req` - Query parameters containing the page
+///
+/// # Returns
+/// * `PageResult<UserInfo>` - One page of deleted users with its `Link` header
+pub async fn list_deleted_users(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+    Valid(Query(req)): Valid<Query<DeletedUsersListRequest>>,
+) -> PageResult<UserInfo> {
+    debug!("list deleted users {:?}", req);
+    let AppResult(page) = UserService::list_deleted(state, req).await?;
+    Ok(PageResponse::new(page, uri))
+}
+
+/// Restores a soft-deleted user, requires `user:write`
+///
+/// # Arguments
+/// * `auth` - Authenticated administrator
+/// * `state` - Application state containing shared resources
+/// * `req` - Path parameters containing the user ID
+///
+/// # Returns
+/// * `Result<RestoreUserResponse>` - The restored user
+pub async fn restore_user(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(req): Path<ByUserIdRequest>,
+) -> Result<RestoreUserResponse> {
+    info!("user {} restore user {}", auth.user_id, req.id);
+    UserService::restore(state, auth, req).await
+}
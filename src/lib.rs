@@ -1,15 +1,22 @@
+pub mod blob;
+pub mod cli;
 pub mod conf;
 pub mod core;
 pub mod data;
 pub mod errors;
 pub mod handlers;
 pub mod logx;
+pub mod mail;
 pub mod models;
+pub mod oidc;
 pub mod repos;
 pub mod routers;
+pub mod search;
 pub mod services;
+pub mod sms;
 #[allow(non_snake_case)]
 pub mod srvCtx;
 pub mod transport;
 pub mod types;
 pub mod utils;
+pub mod wechat;
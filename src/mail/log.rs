@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use lettre::Message;
+use tracing::info;
+
+use crate::mail::Mailer;
+
+/// Development backend writing messages to the application log instead of delivering them
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        info!(
+            "mail to {:?}\n{}",
+            message.envelope().to(),
+            String::from_utf8_lossy(&message.formatted())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+pub mod log;
+pub mod outbox;
+pub mod smtp;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use derivative::Derivative;
+use lettre::Message;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::mail::log::LogMailer;
+use crate::mail::outbox::OutboxMailer;
+use crate::mail::smtp::SmtpMailer;
+
+/// Outbound mail configuration
+#[derive(Derivative, Deserialize, SmartDefault, Clone)]
+#[derivative(Debug)]
+#[serde(default)]
+pub struct MailConf {
+    /// Delivery backend, one of "log", "file" or "smtp"
+    /// - log: only writes the message to the application log, for development
+    /// - file: writes every message as an `.eml` file into `outbox_dir`
+    /// - smtp: delivers through the configured SMTP relay
+    #[default("log")]
+    pub backend: String,
+
+    /// Sender of every message, e.g. `Axum Best <no-reply@example.com>`
+    #[default("Axum Best <no-reply@example.com>")]
+    pub from: String,
+
+    /// Maximum time spent delivering a message, in seconds
+    #[default(10)]
+    pub timeout_secs: u64,
+
+    /// Directory receiving the `.eml` files of the file backend
+    #[default("outbox")]
+    pub outbox_dir: String,
+
+    /// SMTP relay host name
+    pub smtp_host: String,
+
+    /// SMTP relay port
+    #[default(587)]
+    pub smtp_port: u16,
+
+    /// SMTP connection security, one of "starttls", "tls" or "none"
+    #[default("starttls")]
+    pub smtp_tls: String,
+
+    /// SMTP user name, authentication is skipped when empty
+    pub smtp_username: String,
+
+    /// SMTP password
+    ///
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub smtp_password: String,
+}
+
+impl MailConf {
+    /// Builds the mail client of the configured backend
+    ///
+    /// # Returns
+    /// - `Ok(MailClient)` when the backend is known and its settings are valid
+    /// - `Err(anyhow::Error)` if the backend is unknown, the sender cannot be parsed or the backend
+    ///   cannot be set up
+    pub fn build(&self) -> anyhow::Result<MailClient> {
+        let from = self
+            .from
+            .parse::<Mailbox>()
+            .map_err(|err| anyhow::anyhow!("invalid mail sender {} {}", self.from, err))?;
+        let timeout = Duration::from_secs(self.timeout_secs);
+        let mailer: Arc<dyn Mailer> = match self.backend.as_str() {
+            "log" => Arc::new(LogMailer),
+            "file" => Arc::new(OutboxMailer::new(&self.outbox_dir)?),
+            "smtp" => Arc::new(SmtpMailer::new(self, timeout)?),
+            other => return Err(anyhow::anyhow!("unsupported mail backend {}", other)),
+        };
+        Ok(MailClient::new(mailer, from, timeout))
+    }
+}
+
+/// Delivery backend of outbound mail
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    /// Delivers a fully built message
+    async fn send(&self, message: Message) -> anyhow::Result<()>;
+}
+
+/// Mail client shared by the whole application
+///
+/// Builds messages from the configured sender and bounds every delivery by the configured
+/// timeout, so a slow mail server cannot hold a request forever. Cloning is cheap.
+#[derive(Clone)]
+pub struct MailClient {
+    mailer: Arc<dyn Mailer>,
+    from: Mailbox,
+    timeout: Duration,
+}
+
+impl MailClient {
+    pub fn new(mailer: Arc<dyn Mailer>, from: Mailbox, timeout: Duration) -> MailClient {
+        MailClient {
+            mailer,
+            from,
+            timeout,
+        }
+    }
+
+    /// Sends a plain text message
+    ///
+    /// # Arguments
+    /// * `to` - Recipient address
+    /// * `subject` - Subject line
+    /// * `body` - Plain text body
+    ///
+    /// # Returns
+    /// * `Result<(), AppError>` - `ErrBadRequest` for an invalid recipient, `ErrMailTimeout` when
+    ///   the backend is too slow and `ErrMailDelivery` for any other failure
+    pub async fn send_text(&self, to: &str, subject: &str, body: &str) -> Result<(), AppError> {
+        let to = to.parse::<Mailbox>().map_err(|err| {
+            error!("invalid mail recipient {} {}", to, err);
+            errors::ErrBadRequest.clone()
+        })?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())
+            .map_err(|err| {
+                error!("build mail error {}", err);
+                errors::ErrMailDelivery.clone()
+            })?;
+
+        match tokio::time::timeout(self.timeout, self.mailer.send(message)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => {
+                error!("send mail error {}", err);
+                Err(errors::ErrMailDelivery.clone())
+            }
+            Err(_) => {
+                error!("send mail timeout after {:?}", self.timeout);
+                Err(errors::ErrMailTimeout.clone())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    use super::*;
+
+    struct SlowMailer;
+
+    #[async_trait]
+    impl Mailer for SlowMailer {
+        async fn send(&self, _message: Message) -> anyhow::Result<()> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_writes_outbox() {
+        let dir = std::env::temp_dir().join(format!("mail_outbox_{}", std::process::id()));
+        let conf = MailConf {
+            backend: "file".to_string(),
+            outbox_dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let client = conf.build().unwrap();
+        client
+            .send_text("alice@example.com", "Verify your email", "code 123456")
+            .await
+            .unwrap();
+
+        let messages = outbox::read_outbox(&dir).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("To: alice@example.com"));
+        assert!(messages[0].contains("Subject: Verify your email"));
+        assert!(messages[0].contains("code 123456"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_timeout() {
+        let client = MailClient::new(
+            Arc::new(SlowMailer),
+            "sender@example.com".parse().unwrap(),
+            Duration::from_millis(50),
+        );
+        let err = client
+            .send_text("alice@example.com", "subject", "body")
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_unknown_backend_rejected() {
+        let conf = MailConf {
+            backend: "pigeon".to_string(),
+            ..Default::default()
+        };
+        assert!(conf.build().is_err());
+    }
+}
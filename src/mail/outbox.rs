@@ -0,0 +1,61 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use lettre::Message;
+
+use crate::mail::Mailer;
+use crate::utils;
+
+/// Backend writing every message as an `.eml` file into a directory
+///
+/// Files are named `{unix millis}_{random}.eml` so they sort in sending order. Useful to inspect
+/// messages locally and to assert on them in tests with [`read_outbox`].
+pub struct OutboxMailer {
+    dir: PathBuf,
+}
+
+impl OutboxMailer {
+    /// Creates the outbox directory if needed
+    pub fn new(dir: &str) -> anyhow::Result<OutboxMailer> {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| anyhow::anyhow!("create mail outbox {} error {}", dir, err))?;
+        Ok(OutboxMailer {
+            dir: PathBuf::from(dir),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        let name = format!("{}_{}.eml", chrono::Utc::now().timestamp_millis(), utils::gen_token(4));
+        tokio::fs::write(self.dir.join(name), message.formatted()).await?;
+        Ok(())
+    }
+}
+
+/// Reads the raw messages of an outbox directory, oldest first
+///
+/// # Arguments
+/// * `dir` - Outbox directory of the file backend
+///
+/// # Returns
+/// * `std::io::Result<Vec<String>>` - Content of every `.eml` file
+pub async fn read_outbox(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "eml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut messages = Vec::with_capacity(paths.len());
+    for path in paths {
+        messages.push(tokio::fs::read_to_string(path).await?);
+    }
+    Ok(messages)
+}
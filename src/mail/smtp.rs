@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lettre::AsyncSmtpTransport;
+use lettre::AsyncTransport;
+use lettre::Message;
+use lettre::Tokio1Executor;
+use lettre::transport::smtp::authentication::Credentials;
+
+use crate::mail::MailConf;
+use crate::mail::Mailer;
+
+/// Backend delivering messages through an SMTP relay, connections are pooled
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// Builds the SMTP transport described by the `smtp_*` settings
+    ///
+    /// # Arguments
+    /// * `conf` - Mail configuration
+    /// * `timeout` - Timeout of every SMTP command
+    pub fn new(conf: &MailConf, timeout: Duration) -> anyhow::Result<SmtpMailer> {
+        let host = conf.smtp_host.as_str();
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("smtp_host must not be empty for the smtp backend"));
+        }
+        let mut builder = match conf.smtp_tls.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            other => return Err(anyhow::anyhow!("unsupported smtp_tls {}", other)),
+        }
+        .port(conf.smtp_port)
+        .timeout(Some(timeout));
+        if !conf.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                conf.smtp_username.clone(),
+                conf.smtp_password.clone(),
+            ));
+        }
+        Ok(SmtpMailer {
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use axum_best::cli::Command;
 use axum_best::conf;
 use axum_best::srvCtx::ServeContext;
 use clap::Parser;
@@ -15,6 +16,10 @@ struct Args {
     /// config path
     #[arg(long, default_value = "etc/config.toml", short)]
     conf: String,
+
+    /// run an administrative command instead of the server
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -25,6 +30,10 @@ async fn main() -> anyhow::Result<()> {
     let cfg = conf::AppConf::from_path(&args.conf)
         .map_err(|err| anyhow::anyhow!("parser conf file error {:?}", err))?;
 
+    if let Some(command) = args.command {
+        return command.run(cfg).await;
+    }
+
     let mut serve_context = ServeContext::new(cfg)
         .await
         .map_err(|err| anyhow::anyhow!("build server context error {}", err))?;
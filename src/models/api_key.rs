@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use sqlx::FromRow;
+
+/// API key used by backend jobs and other services, only the hash of the key is stored
+#[derive(FromRow, Debug, SmartDefault, Deserialize, Serialize)]
+pub struct ApiKey {
+    /// Unique identifier for the key
+    pub id: i64,
+    /// Name of the caller the key was issued to
+    pub name: String,
+    /// First characters of the plain key, to recognize it in listings
+    pub prefix: String,
+    /// SHA256 hex digest of the plain key
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    /// Space separated permission codes granted to the key
+    pub scopes: String,
+    /// User the key acts for, 0 for a pure service key
+    pub user_id: i64,
+    /// Timestamp when the key expires (Unix timestamp, 0 if it never expires)
+    pub expires_at: i64,
+    /// Timestamp of the last authenticated request (Unix timestamp)
+    pub last_used_at: i64,
+    /// Timestamp when the key was created (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp when the key was revoked (Unix timestamp, 0 if active)
+    pub revoked_at: i64,
+}
+
+impl ApiKey {
+    /// Permission codes granted to the key
+    pub fn scope_list(&self) -> Vec<String> {
+        self.scopes.split_whitespace().map(str::to_string).collect()
+    }
+
+    /// Whether the key can still be used at `now`
+    pub fn is_active(&self, now: i64) -> bool {
+        self.revoked_at == 0 && (self.expires_at == 0 || self.expires_at > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_state() {
+        let key = ApiKey {
+            scopes: "user:read  user:write".to_string(),
+            expires_at: 100,
+            ..Default::default()
+        };
+        assert_eq!(key.scope_list(), vec!["user:read", "user:write"]);
+        assert!(key.is_active(99));
+        assert!(!key.is_active(100));
+
+        let revoked = ApiKey {
+            revoked_at: 1,
+            ..Default::default()
+        };
+        assert!(!revoked.is_active(0));
+    }
+}
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use sqlx::FromRow;
+
+/// Account of an OpenID Connect or OAuth2 provider linked to a user
+#[derive(FromRow, Debug, Clone, SmartDefault, Deserialize, Serialize)]
+pub struct UserIdentity {
+    /// Unique identifier for the link
+    pub id: i64,
+    /// Linked user
+    pub user_id: i64,
+    /// Name of the provider in the configuration
+    pub provider: String,
+    /// Id of the user at the provider, the `sub` claim
+    pub subject: String,
+    /// Email reported by the provider at the last login
+    pub email: String,
+    /// Timestamp when the identity was linked (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp of the last login through the provider (Unix timestamp)
+    pub last_login_at: i64,
+}
@@ -0,0 +1,106 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use sqlx::FromRow;
+
+use crate::core::session::ClientMeta;
+
+/// Way a user logged in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginMethod {
+    /// WeChat mini program code
+    Wechat,
+    /// Email and password
+    Email,
+    /// Phone number and SMS code
+    Sms,
+    /// OpenID Connect or OAuth2 provider
+    Oidc,
+    /// TOTP or recovery code completing an email login
+    TwoFactor,
+}
+
+impl LoginMethod {
+    /// Value stored in the `method` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginMethod::Wechat => "wechat",
+            LoginMethod::Email => "email",
+            LoginMethod::Sms => "sms",
+            LoginMethod::Oidc => "oidc",
+            LoginMethod::TwoFactor => "two_factor",
+        }
+    }
+}
+
+/// Audit record of a login attempt, successful or not
+#[derive(FromRow, Debug, Clone, SmartDefault, Deserialize, Serialize)]
+pub struct LoginEvent {
+    /// Unique identifier for the event
+    pub id: i64,
+    /// User who logged in, 0 when the account is unknown
+    pub user_id: i64,
+    /// Login method, see `LoginMethod`
+    pub method: String,
+    /// IP address of the caller
+    pub ip: String,
+    /// User agent of the caller
+    pub user_agent: String,
+    /// Device name of the caller
+    pub device: String,
+    /// Whether the attempt ended with a new session
+    pub success: bool,
+    /// Reason of a failure such as `wrong_password`, or a note such as `registered`
+    pub reason: String,
+    /// Successful login from an IP the user never logged in from before
+    pub new_ip: bool,
+    /// Successful login from a device the user never logged in from before
+    pub new_device: bool,
+    /// Timestamp of the attempt (Unix timestamp)
+    pub created_at: i64,
+}
+
+impl LoginEvent {
+    /// Login attempt of `user_id` (0 when the account is unknown) from `client`
+    pub fn new(
+        user_id: i64,
+        method: LoginMethod,
+        client: &ClientMeta,
+        success: bool,
+        reason: &str,
+    ) -> LoginEvent {
+        LoginEvent {
+            user_id,
+            method: method.as_str().to_string(),
+            ip: client.ip.clone(),
+            user_agent: client.user_agent.clone(),
+            device: client.device.clone(),
+            success,
+            reason: reason.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_event_new() {
+        let client = ClientMeta {
+            ip: "203.0.113.7".to_string(),
+            user_agent: "Mozilla/5.0 (iPhone)".to_string(),
+            device: "iPhone".to_string(),
+        };
+        let event = LoginEvent::new(7, LoginMethod::TwoFactor, &client, false, "invalid_code");
+        assert_eq!(event.method, "two_factor");
+        assert_eq!(event.ip, "203.0.113.7");
+        assert_eq!(event.device, "iPhone");
+        assert!(!event.success);
+        assert_eq!(event.reason, "invalid_code");
+        assert!(!event.new_ip && !event.new_device);
+        assert!(event.created_at > 0);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod api_key;
+pub mod identity;
+pub mod login_event;
 pub mod primitive;
+pub mod rbac;
+pub mod totp;
 pub mod user;
@@ -65,6 +65,7 @@ impl Primitive {
     }
 
     /// 转换为字符串表示
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         match self {
             Primitive::I8(v) => v.to_string(),
@@ -249,6 +250,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_primitive_creation() {
         let p1 = Primitive::I32(42);
         let p2 = Primitive::from(42i32);
@@ -263,6 +265,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_type_checks() {
         let int_val = Primitive::I32(42);
         let float_val = Primitive::F32(3.14);
@@ -0,0 +1,32 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use sqlx::FromRow;
+
+/// Role entity, a named set of permissions granted to users
+#[derive(FromRow, Debug, SmartDefault, Deserialize, Serialize)]
+pub struct Role {
+    /// Unique identifier for the role
+    pub id: i64,
+    /// Unique name of the role, e.g. `admin`
+    pub name: String,
+    /// Human readable description
+    pub description: String,
+    /// Timestamp when the role was created (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp when the role was last updated (Unix timestamp)
+    pub updated_at: i64,
+}
+
+/// Permission entity, checked by `RequirePermission`
+#[derive(FromRow, Debug, SmartDefault, Deserialize, Serialize)]
+pub struct Permission {
+    /// Unique identifier for the permission
+    pub id: i64,
+    /// Unique code of the permission, `resource:action` e.g. `user:write`
+    pub code: String,
+    /// Human readable description
+    pub description: String,
+    /// Timestamp when the permission was created (Unix timestamp)
+    pub created_at: i64,
+}
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use sqlx::FromRow;
+
+/// TOTP authenticator enrolled by a user
+#[derive(FromRow, Debug, SmartDefault, Deserialize, Serialize)]
+pub struct UserTotp {
+    /// Id of the user
+    pub user_id: i64,
+    /// Secret encrypted with `DataCipher`
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Timestamp when the first code was verified (Unix timestamp, 0 while enrolling)
+    pub enabled_at: i64,
+    /// Last accepted time step, codes of older steps are rejected
+    pub last_step: i64,
+    /// Timestamp when the secret was generated (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp when the record was last updated (Unix timestamp)
+    pub updated_at: i64,
+}
+
+impl UserTotp {
+    /// Whether logins of the user require a second step
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at > 0
+    }
+}
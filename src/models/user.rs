@@ -24,9 +24,15 @@ pub struct UserInfo {
     pub phone: String,
     /// WeChat Open ID for authentication
     pub wx_open_id: String,
-    /// Salt used for password hashing
+    /// Bound email address, `None` until the user binds one
+    pub email: Option<String>,
+    /// Timestamp when the email was proven to belong to the user (Unix timestamp, 0 if never)
+    pub email_verified_at: i64,
+    /// Salt used by legacy password hashes, empty for Argon2 hashes
+    #[serde(skip_serializing)]
     pub salt: String,
-    /// Hashed password
+    /// Hashed password (Argon2id PHC string or legacy salted digest)
+    #[serde(skip_serializing)]
     pub password: String,
     /// Timestamp when the user was created (Unix timestamp)
     pub created_at: i64,
@@ -34,6 +40,10 @@ pub struct UserInfo {
     pub updated_at: i64,
     /// Timestamp when the user was deleted (Unix timestamp, 0 if not deleted)
     pub deleted_at: i64,
+    /// Timestamp when a self-service deletion becomes final (Unix timestamp, 0 if not requested)
+    pub purge_at: i64,
+    /// Version incremented by every update, sent as the `ETag` of the user
+    pub version: i64,
     // .... other fields
 }
 
@@ -93,6 +103,16 @@ impl UserInfo {
         self
     }
 
+    pub fn set_email(&mut self, email: Option<String>) -> &mut Self {
+        self.email = email;
+        self
+    }
+
+    pub fn set_email_verified_at(&mut self, email_verified_at: i64) -> &mut Self {
+        self.email_verified_at = email_verified_at;
+        self
+    }
+
     /// 生成一个随机的 UserInfo 实例
     ///
     /// # 示例
@@ -214,8 +234,8 @@ impl UserInfo {
 
         // 生成唯一性更高的签名，结合随机前缀或后缀
         let base_signature = signatures[rng.random_range(0..signatures.len())];
-        let signature_variants = vec![
-            format!("{}", base_signature),
+        let signature_variants = [
+            base_signature.to_string(),
             format!("{}的{}", nick_name, base_signature),
             format!("{} - {}", base_signature, chrono::Utc::now().format("%Y")),
             format!("{} | {}", base_signature, rng.random_range(1000..9999)),
@@ -281,11 +301,15 @@ impl UserInfo {
             age: rng.random_range(18..60),
             phone,
             wx_open_id,
+            email: None,
+            email_verified_at: 0,
             salt,
             password,
             created_at: timestamp - rng.random_range(0..31536000), // 一年内的随机时间
             updated_at: timestamp,
             deleted_at: 0,
+            purge_at: 0,
+            version: 0,
         }
     }
 }
@@ -308,10 +332,60 @@ impl Display for P {
         }
     }
 }
+/// Profile column a user may change, with its new value
+///
+/// The variants are the whitelist of `repos::user::update_profile`: column names never come from
+/// the request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileField {
+    NickName(String),
+    Avatar(String),
+    Signature(String),
+    Age(u8),
+}
+
+impl ProfileField {
+    /// Column of `user_info` holding the field
+    pub fn column(&self) -> &'static str {
+        match self {
+            ProfileField::NickName(_) => "nick_name",
+            ProfileField::Avatar(_) => "avatar",
+            ProfileField::Signature(_) => "signature",
+            ProfileField::Age(_) => "age",
+        }
+    }
+
+    /// Applies the change to a loaded user
+    pub fn apply(&self, user: &mut UserInfo) {
+        match self {
+            ProfileField::NickName(name) => user.set_name(name.clone()),
+            ProfileField::Avatar(avatar) => user.set_avatar(avatar.clone()),
+            ProfileField::Signature(signature) => user.set_signature(signature.clone()),
+            ProfileField::Age(age) => user.set_age(*age),
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::ProfileField;
     use super::UserInfo;
 
+    #[test]
+    fn test_profile_field() {
+        let mut user = UserInfo::default();
+        let fields = [
+            ProfileField::NickName("李四".to_string()),
+            ProfileField::Age(30),
+        ];
+        for field in &fields {
+            field.apply(&mut user);
+        }
+        assert_eq!(user.nick_name, "李四");
+        assert_eq!(user.age, 30);
+        assert_eq!(fields.map(|field| field.column()), ["nick_name", "age"]);
+    }
+
     #[test]
     fn test_chain_setters() {
         let mut user = UserInfo {
@@ -322,11 +396,15 @@ mod tests {
             age: 0,
             phone: "".to_string(),
             wx_open_id: "".to_string(),
+            email: None,
+            email_verified_at: 0,
             salt: "".to_string(),
             password: "".to_string(),
             created_at: 0,
             updated_at: 0,
             deleted_at: 0,
+            purge_at: 0,
+            version: 0,
         };
 
         // 测试链式调用
@@ -364,11 +442,15 @@ mod tests {
             age: 0,
             phone: "".to_string(),
             wx_open_id: "".to_string(),
+            email: None,
+            email_verified_at: 0,
             salt: "".to_string(),
             password: "".to_string(),
             created_at: 0,
             updated_at: 0,
             deleted_at: 0,
+            purge_at: 0,
+            version: 0,
         };
 
         // 测试部分链式调用
@@ -0,0 +1,874 @@
+pub mod mock;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use derivative::Derivative;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::Validation;
+use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::jwk::JwkSet;
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Commands;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use smart_default::SmartDefault;
+use subtle::ConstantTimeEq;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::utils;
+
+/// Signature algorithms accepted on ID tokens, symmetric and `none` algorithms are never trusted
+const ID_TOKEN_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// An ID token signed by an unknown key refreshes the JWKS at most once per interval, in seconds
+const JWKS_MIN_REFRESH_SECS: u64 = 60;
+
+/// OpenID Connect login configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct OidcConf {
+    /// Lifetime of a pending login (state, nonce and PKCE verifier), in seconds
+    #[default(600)]
+    pub state_ttl_secs: i64,
+
+    /// Login providers, addressed by name in `/user/oidc/{provider}/...`
+    pub providers: Vec<OidcProviderConf>,
+}
+
+/// One OpenID Connect or plain OAuth2 login provider
+///
+/// Providers with an `issuer` are discovered from `{issuer}/.well-known/openid-configuration`,
+/// the endpoints below override the discovered ones. Providers whose scopes contain `openid` must
+/// return an ID token, the others are identified through their userinfo endpoint.
+#[derive(Deserialize, Derivative, SmartDefault, Clone)]
+#[derivative(Debug)]
+#[serde(default)]
+pub struct OidcProviderConf {
+    /// Name of the provider in routes and linked identities, e.g. `google`
+    pub name: String,
+
+    /// Issuer URL, empty for plain OAuth2 providers without discovery
+    pub issuer: String,
+
+    /// Client id registered with the provider
+    pub client_id: String,
+
+    /// Client secret registered with the provider
+    ///
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub client_secret: String,
+
+    /// Redirect URI registered with the provider, the page receiving `code` and `state`
+    pub redirect_uri: String,
+
+    /// Requested scopes
+    #[default(vec!["openid".to_string(), "email".to_string(), "profile".to_string()])]
+    pub scopes: Vec<String>,
+
+    /// Authorization endpoint, overrides discovery
+    pub authorization_endpoint: String,
+
+    /// Token endpoint, overrides discovery
+    pub token_endpoint: String,
+
+    /// Userinfo endpoint, overrides discovery
+    pub userinfo_endpoint: String,
+
+    /// JWKS endpoint, overrides discovery
+    pub jwks_uri: String,
+
+    /// Claim holding the user id in userinfo responses, `id` for GitHub
+    #[default("sub")]
+    pub subject_claim: String,
+
+    /// Links the first login to the account with the same email when the provider verified it and
+    /// the account proved owning it
+    pub link_by_email: bool,
+
+    /// Timeout of a call to the provider in seconds
+    #[default(5)]
+    pub timeout_secs: u64,
+
+    /// How long the discovery document and signing keys are cached, in seconds
+    #[default(3600)]
+    pub cache_ttl_secs: u64,
+
+    /// Clock skew tolerated on the `exp` and `iat` claims of ID tokens, in seconds
+    #[default(60)]
+    pub leeway_secs: u64,
+}
+
+/// Endpoints of a provider, discovered or configured
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProviderMetadata {
+    #[serde(default)]
+    pub issuer: String,
+    #[serde(default)]
+    pub authorization_endpoint: String,
+    #[serde(default)]
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub userinfo_endpoint: String,
+    #[serde(default)]
+    pub jwks_uri: String,
+}
+
+/// Token endpoint response, GitHub answers HTTP 200 with an `error` on failure
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    access_token: String,
+    id_token: Option<String>,
+    error: Option<String>,
+}
+
+/// User authenticated by a provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcIdentity {
+    /// Stable id of the user at the provider, the `sub` claim
+    pub subject: String,
+    /// Email, lower cased
+    pub email: Option<String>,
+    /// Whether the provider verified the email
+    pub email_verified: bool,
+    /// Display name
+    pub name: Option<String>,
+}
+
+/// Login started by `/authorize` and finished by `/callback`, kept in Redis under its `state`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingLogin {
+    /// Name of the provider
+    pub provider: String,
+    /// Nonce expected in the ID token
+    pub nonce: String,
+    /// PKCE code verifier
+    pub verifier: String,
+    /// User linking the identity to the account, 0 for a login
+    pub link_user_id: i64,
+}
+
+impl PendingLogin {
+    /// Starts a login, or the linking of an identity to `link_user_id` when it is not 0
+    pub fn new(provider: &str, link_user_id: i64) -> PendingLogin {
+        PendingLogin {
+            provider: provider.to_string(),
+            nonce: utils::gen_token(16),
+            verifier: utils::gen_token(32),
+            link_user_id,
+        }
+    }
+}
+
+/// PKCE `S256` code challenge of a verifier (RFC 7636)
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[derive(Clone)]
+struct Cached<T> {
+    value: Arc<T>,
+    fetched_at: Instant,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T) -> Self {
+        Cached {
+            value: Arc::new(value),
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn younger_than(&self, secs: u64) -> bool {
+        self.fetched_at.elapsed() < Duration::from_secs(secs)
+    }
+}
+
+#[derive(Default)]
+struct ProviderCache {
+    metadata: RwLock<Option<Cached<ProviderMetadata>>>,
+    jwks: RwLock<Option<Cached<JwkSet>>>,
+}
+
+/// Client of one provider
+///
+/// Calls are made with a blocking HTTP agent on the blocking thread pool, like the WeChat client.
+/// The discovery document and the JWKS are cached for `cache_ttl_secs`. Cloning is cheap.
+#[derive(Clone)]
+pub struct OidcProvider {
+    conf: Arc<OidcProviderConf>,
+    agent: ureq::Agent,
+    cache: Arc<ProviderCache>,
+}
+
+impl OidcProvider {
+    /// Creates a client with the configured timeout
+    pub fn new(conf: OidcProviderConf) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(conf.timeout_secs)))
+            .http_status_as_error(false)
+            .build()
+            .into();
+        OidcProvider {
+            conf: Arc::new(conf),
+            agent,
+            cache: Arc::new(ProviderCache::default()),
+        }
+    }
+
+    /// Returns the configuration of this provider
+    pub fn conf(&self) -> &OidcProviderConf {
+        &self.conf
+    }
+
+    /// Whether the provider speaks OpenID Connect and must return an ID token
+    pub fn is_oidc(&self) -> bool {
+        self.conf.scopes.iter().any(|scope| scope == "openid")
+    }
+
+    /// Returns the endpoints of the provider, from the cache or the discovery document
+    pub async fn metadata(&self) -> Result<Arc<ProviderMetadata>, AppError> {
+        let cached = self
+            .cache
+            .metadata
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(cached) = cached.filter(|cached| cached.younger_than(self.conf.cache_ttl_secs))
+        {
+            return Ok(cached.value);
+        }
+
+        let issuer = self.conf.issuer.trim_end_matches('/');
+        let mut metadata = if issuer.is_empty() {
+            ProviderMetadata::default()
+        } else {
+            let url = format!("{}/.well-known/openid-configuration", issuer);
+            let metadata: ProviderMetadata = self
+                .fetch_json(get_request(&url, None), &errors::ErrOidcProvider)
+                .await?;
+            if metadata.issuer.trim_end_matches('/') != issuer {
+                error!(
+                    "oidc provider {} discovery issuer {} does not match {}",
+                    self.conf.name, metadata.issuer, issuer
+                );
+                return Err(errors::ErrOidcProvider.clone());
+            }
+            metadata
+        };
+        let overrides = [
+            (&mut metadata.authorization_endpoint, &self.conf.authorization_endpoint),
+            (&mut metadata.token_endpoint, &self.conf.token_endpoint),
+            (&mut metadata.userinfo_endpoint, &self.conf.userinfo_endpoint),
+            (&mut metadata.jwks_uri, &self.conf.jwks_uri),
+        ];
+        for (endpoint, configured) in overrides {
+            if !configured.is_empty() {
+                endpoint.clone_from(configured);
+            }
+        }
+        let identity_endpoint = if self.is_oidc() {
+            &metadata.jwks_uri
+        } else {
+            &metadata.userinfo_endpoint
+        };
+        if metadata.authorization_endpoint.is_empty()
+            || metadata.token_endpoint.is_empty()
+            || identity_endpoint.is_empty()
+        {
+            error!("oidc provider {} is missing endpoints {:?}", self.conf.name, metadata);
+            return Err(errors::ErrOidcProvider.clone());
+        }
+
+        let cached = Cached::new(metadata);
+        *self
+            .cache
+            .metadata
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(cached.clone());
+        Ok(cached.value)
+    }
+
+    /// Builds the authorization URL the browser is sent to
+    ///
+    /// # Arguments
+    /// * `state` - Opaque value returned to the callback, identifies the pending login
+    /// * `pending` - Nonce and PKCE verifier of the login
+    pub async fn authorize_url(
+        &self,
+        state: &str,
+        pending: &PendingLogin,
+    ) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+        let challenge = code_challenge(&pending.verifier);
+        let scope = self.conf.scopes.join(" ");
+        let mut params = vec![
+            ("response_type", "code"),
+            ("client_id", self.conf.client_id.as_str()),
+            ("redirect_uri", self.conf.redirect_uri.as_str()),
+            ("scope", scope.as_str()),
+            ("state", state),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ];
+        if self.is_oidc() {
+            params.push(("nonce", pending.nonce.as_str()));
+        }
+        let query = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, utils::percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let separator = if metadata.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        Ok(format!("{}{}{}", metadata.authorization_endpoint, separator, query))
+    }
+
+    /// Exchanges an authorization code for the identity of the user
+    ///
+    /// # Arguments
+    /// * `code` - Code received by the redirect URI
+    /// * `pending` - The pending login matching the `state` received with the code
+    ///
+    /// # Returns
+    /// * `Result<OidcIdentity, AppError>` - The user, `ErrOidcLoginFailed` when the provider
+    ///   rejects the code or the ID token is invalid
+    pub async fn exchange(
+        &self,
+        code: &str,
+        pending: &PendingLogin,
+    ) -> Result<OidcIdentity, AppError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type".to_string(), "authorization_code".to_string()),
+            ("code".to_string(), code.to_string()),
+            ("redirect_uri".to_string(), self.conf.redirect_uri.clone()),
+            ("client_id".to_string(), self.conf.client_id.clone()),
+            ("code_verifier".to_string(), pending.verifier.clone()),
+        ];
+        if !self.conf.client_secret.is_empty() {
+            form.push(("client_secret".to_string(), self.conf.client_secret.clone()));
+        }
+        let url = metadata.token_endpoint.clone();
+        let tokens: TokenResponse = self
+            .fetch_json(
+                move |agent: &ureq::Agent| {
+                    agent
+                        .post(&url)
+                        .header("Accept", "application/json")
+                        .send_form(form)
+                },
+                &errors::ErrOidcLoginFailed,
+            )
+            .await?;
+        if let Some(err) = tokens.error {
+            warn!("oidc provider {} rejected the code: {}", self.conf.name, err);
+            return Err(errors::ErrOidcLoginFailed.clone());
+        }
+
+        let (claims, subject_claim) = if self.is_oidc() {
+            let Some(id_token) = tokens.id_token else {
+                warn!("oidc provider {} returned no id token", self.conf.name);
+                return Err(errors::ErrOidcLoginFailed.clone());
+            };
+            (
+                self.validate_id_token(&metadata, &id_token, &pending.nonce)
+                    .await?,
+                "sub",
+            )
+        } else {
+            let claims: Value = self
+                .fetch_json(
+                    get_request(&metadata.userinfo_endpoint, Some(&tokens.access_token)),
+                    &errors::ErrOidcLoginFailed,
+                )
+                .await?;
+            (claims, self.conf.subject_claim.as_str())
+        };
+        identity_from_claims(&claims, subject_claim).ok_or_else(|| {
+            warn!("oidc provider {} returned no {} claim", self.conf.name, subject_claim);
+            errors::ErrOidcLoginFailed.clone()
+        })
+    }
+
+    /// Checks the signature, issuer, audience, lifetime and nonce of an ID token
+    async fn validate_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<Value, AppError> {
+        let invalid = |reason: &str| {
+            warn!("oidc provider {} id token rejected: {}", self.conf.name, reason);
+            errors::ErrOidcLoginFailed.clone()
+        };
+        let header =
+            jsonwebtoken::decode_header(id_token).map_err(|err| invalid(&err.to_string()))?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(invalid(&format!("algorithm {:?} not accepted", header.alg)));
+        }
+        let jwk = self
+            .signing_key(&metadata.jwks_uri, header.kid.as_deref())
+            .await?
+            .ok_or_else(|| invalid(&format!("unknown key {:?}", header.kid)))?;
+        if jwk
+            .common
+            .key_algorithm
+            .is_some_and(|alg| format!("{:?}", alg) != format!("{:?}", header.alg))
+        {
+            return Err(invalid("algorithm does not match the key"));
+        }
+        let key = DecodingKey::from_jwk(&jwk).map_err(|err| invalid(&err.to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.conf.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = self.conf.leeway_secs;
+        let claims = jsonwebtoken::decode::<Value>(id_token, &key, &validation)
+            .map_err(|err| invalid(&err.to_string()))?
+            .claims;
+
+        let token_nonce = claims
+            .get("nonce")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !bool::from(token_nonce.as_bytes().ct_eq(nonce.as_bytes())) {
+            return Err(invalid("nonce mismatch"));
+        }
+        Ok(claims)
+    }
+
+    /// Finds the key signing an ID token, refetching the JWKS when it expired or the key is
+    /// unknown, at most once per `JWKS_MIN_REFRESH_SECS` in the latter case
+    async fn signing_key(
+        &self,
+        jwks_uri: &str,
+        kid: Option<&str>,
+    ) -> Result<Option<Jwk>, AppError> {
+        let cached = self
+            .cache
+            .jwks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(cached) = cached.filter(|cached| cached.younger_than(self.conf.cache_ttl_secs))
+        {
+            if let Some(jwk) = find_key(&cached.value, kid) {
+                return Ok(Some(jwk.clone()));
+            }
+            if cached.younger_than(JWKS_MIN_REFRESH_SECS) {
+                return Ok(None);
+            }
+        }
+
+        let jwks: JwkSet = self
+            .fetch_json(get_request(jwks_uri, None), &errors::ErrOidcProvider)
+            .await?;
+        info!("oidc provider {} fetched {} signing keys", self.conf.name, jwks.keys.len());
+        let jwk = find_key(&jwks, kid).cloned();
+        *self
+            .cache
+            .jwks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Cached::new(jwks));
+        Ok(jwk)
+    }
+
+    /// Sends a request on the blocking pool and decodes its JSON body
+    ///
+    /// # Arguments
+    /// * `request` - Sends the request with the agent of this provider
+    /// * `rejected` - Error returned when the provider answers with a 4xx status
+    async fn fetch_json<T, F>(&self, request: F, rejected: &AppError) -> Result<T, AppError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: FnOnce(&ureq::Agent) -> Result<ureq::http::Response<ureq::Body>, ureq::Error>
+            + Send
+            + 'static,
+    {
+        let agent = self.agent.clone();
+        let name = self.conf.name.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut response = request(&agent).map_err(|err| {
+                error!("call oidc provider {} error {}", name, err);
+                None
+            })?;
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.body_mut().read_to_string().unwrap_or_default();
+                warn!("oidc provider {} answered {} {}", name, status, body);
+                return Err(Some(status));
+            }
+            response.body_mut().read_json::<T>().map_err(|err| {
+                error!("unmarshal oidc provider {} response {}", name, err);
+                None
+            })
+        })
+        .await
+        .map_err(|err| {
+            error!("oidc provider task error {}", err);
+            errors::ErrOidcProvider.clone()
+        })?;
+        result.map_err(|status| match status {
+            Some(status) if status.is_client_error() => rejected.clone(),
+            _ => errors::ErrOidcProvider.clone(),
+        })
+    }
+}
+
+/// Builds a GET request expecting JSON, with an optional bearer token
+fn get_request(
+    url: &str,
+    bearer: Option<&str>,
+) -> impl FnOnce(&ureq::Agent) -> Result<ureq::http::Response<ureq::Body>, ureq::Error> + Send + 'static
+{
+    let url = url.to_string();
+    let authorization = bearer.map(|token| format!("Bearer {}", token));
+    move |agent: &ureq::Agent| {
+        let mut request = agent.get(&url).header("Accept", "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+        request.call()
+    }
+}
+
+/// Finds a key by id, a key set with a single key matches tokens without `kid`
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+/// Reads the identity out of ID token or userinfo claims
+///
+/// Numeric subjects, such as GitHub user ids, are turned into strings and `email_verified` may be
+/// a boolean or a string.
+pub fn identity_from_claims(claims: &Value, subject_claim: &str) -> Option<OidcIdentity> {
+    let subject = match claims.get(subject_claim)? {
+        Value::String(subject) if !subject.is_empty() => subject.clone(),
+        Value::Number(subject) => subject.to_string(),
+        _ => return None,
+    };
+    let text = |field: &str| {
+        claims
+            .get(field)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let email_verified = match claims.get("email_verified") {
+        Some(Value::Bool(verified)) => *verified,
+        Some(Value::String(verified)) => verified == "true",
+        _ => false,
+    };
+    Some(OidcIdentity {
+        subject,
+        email: text("email").map(|email| email.to_lowercase()),
+        email_verified,
+        name: text("name")
+            .or_else(|| text("preferred_username"))
+            .or_else(|| text("login")),
+    })
+}
+
+/// Configured login providers and their pending logins
+///
+/// Pending logins live in Redis as `oidc_state_{sha256(state)}` and are deleted by the callback,
+/// so a `state` can be used only once.
+#[derive(Clone)]
+pub struct Oidc {
+    state_ttl_secs: i64,
+    providers: Arc<HashMap<String, OidcProvider>>,
+}
+
+impl Oidc {
+    pub fn new(conf: OidcConf) -> Oidc {
+        let providers = conf
+            .providers
+            .into_iter()
+            .map(|provider| (provider.name.clone(), OidcProvider::new(provider)))
+            .collect();
+        Oidc {
+            state_ttl_secs: conf.state_ttl_secs,
+            providers: Arc::new(providers),
+        }
+    }
+
+    /// Lifetime of a pending login in seconds
+    pub fn state_ttl_secs(&self) -> i64 {
+        self.state_ttl_secs
+    }
+
+    /// Names of the configured providers, sorted
+    pub fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Returns a provider, `ErrOidcProviderUnknown` when none is configured under this name
+    pub fn provider(&self, name: &str) -> Result<&OidcProvider, AppError> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| errors::ErrOidcProviderUnknown.clone())
+    }
+
+    /// Stores a pending login until the callback or `state_ttl_secs`
+    pub fn save_pending(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        state: &str,
+        pending: &PendingLogin,
+    ) -> Result<(), AppError> {
+        let value = serde_json::to_string(pending).map_err(|err| {
+            error!("marshal pending oidc login error {}", err);
+            errors::ErrOidcProvider.clone()
+        })?;
+        let _: () = conn
+            .set_ex(state_key(state), value, self.state_ttl_secs.max(1) as u64)
+            .map_err(redis_error)?;
+        Ok(())
+    }
+
+    /// Deletes and returns a pending login
+    ///
+    /// # Returns
+    /// * `Result<PendingLogin, AppError>` - The pending login, `ErrOidcStateInvalid` when the
+    ///   state is unknown, expired or already used
+    pub fn take_pending(
+        &self,
+        conn: &mut PooledConnection<Client>,
+        state: &str,
+    ) -> Result<PendingLogin, AppError> {
+        let value: Option<String> = conn.get_del(state_key(state)).map_err(redis_error)?;
+        value
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .ok_or_else(|| errors::ErrOidcStateInvalid.clone())
+    }
+}
+
+fn state_key(state: &str) -> String {
+    format!("oidc_state_{}", utils::sha256_hex(state))
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("oidc redis error {}", err);
+    errors::ErrRedisClient.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use serde_json::json;
+
+    use super::*;
+
+    fn provider_conf(mock: &mock::MockOidc) -> OidcProviderConf {
+        OidcProviderConf {
+            name: "mock".to_string(),
+            issuer: mock.issuer(),
+            client_id: mock::CLIENT_ID.to_string(),
+            client_secret: mock::CLIENT_SECRET.to_string(),
+            redirect_uri: "http://localhost:8080/oidc/callback".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Follows the authorization URL like a browser and returns the code sent to the redirect URI
+    async fn authorize(url: String, user: &str) -> String {
+        let url = format!("{}&login_hint={}", url, user);
+        tokio::task::spawn_blocking(move || {
+            let agent: ureq::Agent = ureq::Agent::config_builder()
+                .max_redirects(0)
+                .max_redirects_will_error(false)
+                .build()
+                .into();
+            let response = agent.get(&url).call().unwrap();
+            let location = response.headers()["location"].to_str().unwrap().to_string();
+            location
+                .split(['?', '&'])
+                .find_map(|param| param.strip_prefix("code="))
+                .unwrap()
+                .to_string()
+        })
+        .await
+        .unwrap()
+    }
+
+    fn status(err: AppError) -> StatusCode {
+        err.into_response().status()
+    }
+
+    #[tokio::test]
+    async fn test_login_flow() {
+        let mock = mock::spawn().await.unwrap();
+        let provider = OidcProvider::new(provider_conf(&mock));
+        let pending = PendingLogin::new("mock", 0);
+
+        let url = provider.authorize_url("the-state", &pending).await.unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?response_type=code", mock.issuer())));
+        assert!(url.contains("&state=the-state&"));
+        assert!(url.contains(&format!("&nonce={}", pending.nonce)));
+        assert!(url.contains("&code_challenge_method=S256"));
+
+        let code = authorize(url, "alice").await;
+        let identity = provider.exchange(&code, &pending).await.unwrap();
+        assert_eq!(identity.subject, "mock_alice");
+        assert_eq!(identity.email.as_deref(), Some("alice@example.com"));
+        assert!(identity.email_verified);
+
+        // codes are single use
+        let err = provider.exchange(&code, &pending).await.unwrap_err();
+        assert_eq!(status(err), StatusCode::UNAUTHORIZED);
+
+        // the discovery document and the keys are cached
+        let url = provider.authorize_url("other", &pending).await.unwrap();
+        let code = authorize(url, "bob").await;
+        assert_eq!(provider.exchange(&code, &pending).await.unwrap().subject, "mock_bob");
+        assert_eq!(mock.discovery_requests(), 1);
+        assert_eq!(mock.jwks_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_wrong_verifier_and_nonce() {
+        let mock = mock::spawn().await.unwrap();
+        let provider = OidcProvider::new(provider_conf(&mock));
+        let pending = PendingLogin::new("mock", 0);
+
+        let url = provider.authorize_url("s", &pending).await.unwrap();
+        let code = authorize(url, "alice").await;
+        let wrong_verifier = PendingLogin {
+            verifier: utils::gen_token(32),
+            ..pending.clone()
+        };
+        let err = provider.exchange(&code, &wrong_verifier).await.unwrap_err();
+        assert_eq!(status(err), StatusCode::UNAUTHORIZED);
+
+        let url = provider.authorize_url("s", &pending).await.unwrap();
+        let code = authorize(url, "alice").await;
+        let wrong_nonce = PendingLogin {
+            nonce: "replayed".to_string(),
+            ..pending.clone()
+        };
+        let err = provider.exchange(&code, &wrong_nonce).await.unwrap_err();
+        assert_eq!(status(err), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_id_token_audience_checked() {
+        let mock = mock::spawn().await.unwrap();
+        let provider = OidcProvider::new(OidcProviderConf {
+            client_id: "another-client".to_string(),
+            ..provider_conf(&mock)
+        });
+        let pending = PendingLogin::new("mock", 0);
+        let url = provider.authorize_url("s", &pending).await.unwrap();
+        let code = authorize(url, "alice").await;
+        // the stand-in always issues ID tokens for `mock::CLIENT_ID`
+        let err = provider.exchange(&code, &pending).await.unwrap_err();
+        assert_eq!(status(err), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_plain_oauth2_userinfo() {
+        let mock = mock::spawn().await.unwrap();
+        let provider = OidcProvider::new(OidcProviderConf {
+            issuer: String::new(),
+            scopes: vec!["user".to_string()],
+            authorization_endpoint: format!("{}/authorize", mock.issuer()),
+            token_endpoint: format!("{}/token", mock.issuer()),
+            userinfo_endpoint: format!("{}/userinfo", mock.issuer()),
+            subject_claim: "id".to_string(),
+            ..provider_conf(&mock)
+        });
+        let pending = PendingLogin::new("mock", 0);
+        let url = provider.authorize_url("s", &pending).await.unwrap();
+        assert!(!url.contains("nonce="));
+
+        let code = authorize(url, "carol").await;
+        let identity = provider.exchange(&code, &pending).await.unwrap();
+        assert_eq!(identity.subject, mock::numeric_id("carol").to_string());
+        assert_eq!(identity.name.as_deref(), Some("carol"));
+        assert_eq!(mock.discovery_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_discovery_issuer_mismatch() {
+        // a provider serving the discovery document of another issuer
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = mock::router("https://login.example.com");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let provider = OidcProvider::new(OidcProviderConf {
+            name: "mock".to_string(),
+            issuer: format!("http://{}", addr),
+            ..Default::default()
+        });
+        let err = provider.metadata().await.unwrap_err();
+        assert_eq!(status(err), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_code_challenge_rfc7636() {
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_identity_from_claims() {
+        let github = json!({"id": 583231, "login": "octocat", "email": "Octo@GitHub.com"});
+        let identity = identity_from_claims(&github, "id").unwrap();
+        assert_eq!(identity.subject, "583231");
+        assert_eq!(identity.email.as_deref(), Some("octo@github.com"));
+        assert!(!identity.email_verified);
+        assert_eq!(identity.name.as_deref(), Some("octocat"));
+
+        let cognito = json!({"sub": "abc", "email_verified": "true", "name": " "});
+        let identity = identity_from_claims(&cognito, "sub").unwrap();
+        assert!(identity.email_verified);
+        assert_eq!(identity.name, None);
+
+        assert!(identity_from_claims(&json!({"sub": ""}), "sub").is_none());
+        assert!(identity_from_claims(&json!({"id": 1}), "sub").is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use sqlx::MySqlPool;
+
+use crate::core::rest::AppError;
+use crate::data::mysql::covert_error;
+use crate::models::api_key::ApiKey;
+
+/// 创建 API Key
+pub async fn create(conn: &MySqlPool, key: &mut ApiKey) -> Result<(), AppError> {
+    key.id = sqlx::query!(
+        r#"INSERT INTO api_key (name, prefix, key_hash, scopes, user_id, expires_at, last_used_at, created_at, revoked_at)
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        key.name,
+        key.prefix,
+        key.key_hash,
+        key.scopes,
+        key.user_id,
+        key.expires_at,
+        key.last_used_at,
+        key.created_at,
+        key.revoked_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+
+    Ok(())
+}
+
+/// 根据 key 的哈希获取 API Key，不存在时返回 None
+pub async fn get_by_hash(conn: &MySqlPool, key_hash: &str) -> Result<Option<ApiKey>, AppError> {
+    let key = sqlx::query_as!(ApiKey, r#"SELECT * FROM api_key WHERE key_hash = ?"#, key_hash)
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+    Ok(key)
+}
+
+/// 获取全部 API Key
+pub async fn list(conn: &MySqlPool) -> Result<Vec<ApiKey>, AppError> {
+    let keys = sqlx::query_as!(ApiKey, r#"SELECT * FROM api_key ORDER BY id"#)
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+    Ok(keys)
+}
+
+/// 获取用户名下的 API Key
+pub async fn list_by_user(conn: &MySqlPool, user_id: i64) -> Result<Vec<ApiKey>, AppError> {
+    let keys =
+        sqlx::query_as!(ApiKey, r#"SELECT * FROM api_key WHERE user_id = ? ORDER BY id"#, user_id)
+            .fetch_all(conn)
+            .await
+            .map_err(covert_error)?;
+    Ok(keys)
+}
+
+/// 吊销 API Key，返回是否有记录被吊销
+pub async fn revoke(conn: &MySqlPool, id: i64, revoked_at: i64) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE api_key SET revoked_at = ? WHERE id = ? AND revoked_at = 0"#,
+        revoked_at,
+        id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 更新最后使用时间
+pub async fn touch(conn: &MySqlPool, id: i64, last_used_at: i64) -> Result<(), AppError> {
+    sqlx::query!(r#"UPDATE api_key SET last_used_at = ? WHERE id = ?"#, last_used_at, id)
+        .execute(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(())
+}
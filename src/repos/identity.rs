@@ -0,0 +1,111 @@
+use sqlx::MySqlPool;
+
+use crate::core::rest::AppError;
+use crate::data::mysql::covert_error;
+use crate::models::identity::UserIdentity;
+use crate::models::user::UserInfo;
+use crate::repos;
+
+/// 根据提供方和提供方用户标识获取绑定关系，不存在时返回 None
+pub async fn get_by_subject(
+    conn: &MySqlPool,
+    provider: &str,
+    subject: &str,
+) -> Result<Option<UserIdentity>, AppError> {
+    let identity = sqlx::query_as!(
+        UserIdentity,
+        r#"SELECT * FROM user_identity WHERE provider = ? AND subject = ?"#,
+        provider,
+        subject
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(covert_error)?;
+    Ok(identity)
+}
+
+/// 创建绑定关系，同一提供方账号已绑定时返回数据冲突错误
+pub async fn create(conn: &MySqlPool, identity: &mut UserIdentity) -> Result<(), AppError> {
+    identity.id = sqlx::query!(
+        r#"INSERT INTO user_identity (user_id, provider, subject, email, created_at, last_login_at)
+           VALUES (?, ?, ?, ?, ?, ?)"#,
+        identity.user_id,
+        identity.provider,
+        identity.subject,
+        identity.email,
+        identity.created_at,
+        identity.last_login_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+
+    Ok(())
+}
+
+/// 在同一事务中注册新用户并创建绑定关系
+pub async fn create_with_user(
+    conn: &MySqlPool,
+    user: &mut UserInfo,
+    identity: &mut UserIdentity,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await.map_err(covert_error)?;
+    repos::user::create(&mut *tx, user).await?;
+    identity.user_id = user.id;
+    identity.id = sqlx::query!(
+        r#"INSERT INTO user_identity (user_id, provider, subject, email, created_at, last_login_at)
+           VALUES (?, ?, ?, ?, ?, ?)"#,
+        identity.user_id,
+        identity.provider,
+        identity.subject,
+        identity.email,
+        identity.created_at,
+        identity.last_login_at
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+    tx.commit().await.map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 记录通过提供方登录的时间和最新邮箱
+pub async fn touch(conn: &MySqlPool, id: i64, email: &str, now: i64) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"UPDATE user_identity SET email = ?, last_login_at = ? WHERE id = ?"#,
+        email,
+        now,
+        id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 获取用户绑定的全部第三方账号
+pub async fn list_by_user(conn: &MySqlPool, user_id: i64) -> Result<Vec<UserIdentity>, AppError> {
+    let identities = sqlx::query_as!(
+        UserIdentity,
+        r#"SELECT * FROM user_identity WHERE user_id = ? ORDER BY id"#,
+        user_id
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(covert_error)?;
+    Ok(identities)
+}
+
+/// 解除用户的一个绑定，返回是否有记录被删除
+pub async fn delete(conn: &MySqlPool, id: i64, user_id: i64) -> Result<bool, AppError> {
+    let result =
+        sqlx::query!(r#"DELETE FROM user_identity WHERE id = ? AND user_id = ?"#, id, user_id)
+            .execute(conn)
+            .await
+            .map_err(covert_error)?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,104 @@
+use sqlx::MySqlPool;
+
+use crate::core::rest::AppError;
+use crate::data::mysql::covert_error;
+use crate::models::login_event::LoginEvent;
+
+/// 记录一次登录尝试
+pub async fn create(conn: &MySqlPool, event: &mut LoginEvent) -> Result<(), AppError> {
+    event.id = sqlx::query!(
+        r#"INSERT INTO login_event
+           (user_id, method, ip, user_agent, device, success, reason, new_ip, new_device, created_at)
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        event.user_id,
+        event.method,
+        event.ip,
+        event.user_agent,
+        event.device,
+        event.success,
+        event.reason,
+        event.new_ip,
+        event.new_device,
+        event.created_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+
+    Ok(())
+}
+
+/// 统计用户此前的成功登录次数，以及其中来自给定 IP 和给定设备的次数
+pub async fn count_success(
+    conn: &MySqlPool,
+    user_id: i64,
+    ip: &str,
+    device: &str,
+) -> Result<(i64, i64, i64), AppError> {
+    let counts = sqlx::query!(
+        r#"SELECT COUNT(*) AS total,
+                  COUNT(CASE WHEN ip = ? THEN 1 END) AS from_ip,
+                  COUNT(CASE WHEN device = ? THEN 1 END) AS on_device
+           FROM login_event WHERE user_id = ? AND success = TRUE"#,
+        ip,
+        device,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok((counts.total, counts.from_ip, counts.on_device))
+}
+
+/// 获取用户的登录记录（分页查询，最近的在前）
+pub async fn list_by_user(
+    conn: &MySqlPool,
+    user_id: i64,
+    page: u32,
+    page_size: u32,
+) -> Result<Vec<LoginEvent>, AppError> {
+    let offset = (page - 1) * page_size;
+    let events = sqlx::query_as!(
+        LoginEvent,
+        r#"SELECT id, user_id, method, ip, user_agent, device, success AS `success: bool`, reason,
+                  new_ip AS `new_ip: bool`, new_device AS `new_device: bool`, created_at
+           FROM login_event WHERE user_id = ? ORDER BY id DESC LIMIT ? OFFSET ?"#,
+        user_id,
+        page_size as i64,
+        offset as i64
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(events)
+}
+
+/// 获取用户的全部登录记录，用于导出个人数据
+pub async fn list_all_by_user(conn: &MySqlPool, user_id: i64) -> Result<Vec<LoginEvent>, AppError> {
+    let events = sqlx::query_as!(
+        LoginEvent,
+        r#"SELECT id, user_id, method, ip, user_agent, device, success AS `success: bool`, reason,
+                  new_ip AS `new_ip: bool`, new_device AS `new_device: bool`, created_at
+           FROM login_event WHERE user_id = ? ORDER BY id DESC"#,
+        user_id
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(events)
+}
+
+/// 获取用户的登录记录总数
+pub async fn count_by_user(conn: &MySqlPool, user_id: i64) -> Result<i64, AppError> {
+    let count =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) FROM login_event WHERE user_id = ?"#, user_id)
+            .fetch_one(conn)
+            .await
+            .map_err(covert_error)?;
+
+    Ok(count)
+}
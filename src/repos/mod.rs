@@ -1 +1,50 @@
+pub mod api_key;
+pub mod identity;
+pub mod login_event;
+pub mod rbac;
+pub mod totp;
 pub mod user;
+
+/// 软删除查询范围，默认排除已软删除的行
+///
+/// 查询以 `deleted_at BETWEEN ? AND ?` 绑定 `deleted_at_range` 的上下界，同一条 SQL 支持三种范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// 只包含未删除的行
+    #[default]
+    Active,
+    /// 包含已软删除的行
+    WithDeleted,
+    /// 只包含已软删除的行
+    OnlyDeleted,
+}
+
+impl Scope {
+    /// `deleted_at` 的取值范围（闭区间）
+    pub fn deleted_at_range(self) -> (i64, i64) {
+        match self {
+            Scope::Active => (0, 0),
+            Scope::WithDeleted => (0, i64::MAX),
+            Scope::OnlyDeleted => (1, i64::MAX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_range() {
+        let contains = |scope: Scope, deleted_at: i64| {
+            let (min, max) = scope.deleted_at_range();
+            (min..=max).contains(&deleted_at)
+        };
+        assert!(contains(Scope::default(), 0));
+        assert!(!contains(Scope::default(), 1_700_000_000));
+        assert!(contains(Scope::WithDeleted, 0));
+        assert!(contains(Scope::WithDeleted, 1_700_000_000));
+        assert!(!contains(Scope::OnlyDeleted, 0));
+        assert!(contains(Scope::OnlyDeleted, 1_700_000_000));
+    }
+}
@@ -0,0 +1,173 @@
+use sqlx::MySqlPool;
+
+use crate::core::rest::AppError;
+use crate::data::mysql::covert_error;
+use crate::models::rbac::Permission;
+use crate::models::rbac::Role;
+
+/// 创建角色
+pub async fn create_role(conn: &MySqlPool, role: &mut Role) -> Result<(), AppError> {
+    role.id = sqlx::query!(
+        r#"INSERT INTO role (name, description, created_at, updated_at) VALUES (?, ?, ?, ?)"#,
+        role.name,
+        role.description,
+        role.created_at,
+        role.updated_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+
+    Ok(())
+}
+
+/// 根据名称获取角色，不存在时返回 None
+pub async fn get_role_by_name(conn: &MySqlPool, name: &str) -> Result<Option<Role>, AppError> {
+    let role = sqlx::query_as!(Role, r#"SELECT * FROM role WHERE name = ?"#, name)
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+    Ok(role)
+}
+
+/// 获取全部角色
+pub async fn list_roles(conn: &MySqlPool) -> Result<Vec<Role>, AppError> {
+    let roles = sqlx::query_as!(Role, r#"SELECT * FROM role ORDER BY id"#)
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+    Ok(roles)
+}
+
+/// 创建权限
+pub async fn create_permission(
+    conn: &MySqlPool,
+    permission: &mut Permission,
+) -> Result<(), AppError> {
+    permission.id = sqlx::query!(
+        r#"INSERT INTO permission (code, description, created_at) VALUES (?, ?, ?)"#,
+        permission.code,
+        permission.description,
+        permission.created_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
+
+    Ok(())
+}
+
+/// 根据编码获取权限，不存在时返回 None
+pub async fn get_permission_by_code(
+    conn: &MySqlPool,
+    code: &str,
+) -> Result<Option<Permission>, AppError> {
+    let permission =
+        sqlx::query_as!(Permission, r#"SELECT * FROM permission WHERE code = ?"#, code)
+            .fetch_optional(conn)
+            .await
+            .map_err(covert_error)?;
+    Ok(permission)
+}
+
+/// 为角色授予权限，已授予时忽略
+pub async fn grant_permission(
+    conn: &MySqlPool,
+    role_id: i64,
+    permission_id: i64,
+    created_at: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT IGNORE INTO role_permission (role_id, permission_id, created_at) VALUES (?, ?, ?)"#,
+        role_id,
+        permission_id,
+        created_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 收回角色的权限
+pub async fn revoke_permission(
+    conn: &MySqlPool,
+    role_id: i64,
+    permission_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"DELETE FROM role_permission WHERE role_id = ? AND permission_id = ?"#,
+        role_id,
+        permission_id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 为用户分配角色，已分配时忽略
+pub async fn assign_role(
+    conn: &MySqlPool,
+    user_id: i64,
+    role_id: i64,
+    created_at: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT IGNORE INTO user_role (user_id, role_id, created_at) VALUES (?, ?, ?)"#,
+        user_id,
+        role_id,
+        created_at
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 移除用户的角色
+pub async fn remove_role(conn: &MySqlPool, user_id: i64, role_id: i64) -> Result<(), AppError> {
+    sqlx::query!(r#"DELETE FROM user_role WHERE user_id = ? AND role_id = ?"#, user_id, role_id)
+        .execute(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 获取用户的角色
+pub async fn list_roles_by_user(conn: &MySqlPool, user_id: i64) -> Result<Vec<Role>, AppError> {
+    let roles = sqlx::query_as!(
+        Role,
+        r#"SELECT r.* FROM role r INNER JOIN user_role ur ON ur.role_id = r.id
+           WHERE ur.user_id = ? ORDER BY r.id"#,
+        user_id
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(covert_error)?;
+    Ok(roles)
+}
+
+/// 获取用户通过角色拥有的全部权限编码
+pub async fn list_permission_codes_by_user(
+    conn: &MySqlPool,
+    user_id: i64,
+) -> Result<Vec<String>, AppError> {
+    let codes = sqlx::query_scalar!(
+        r#"SELECT p.code FROM permission p
+           INNER JOIN role_permission rp ON rp.permission_id = p.id
+           INNER JOIN user_role ur ON ur.role_id = rp.role_id
+           WHERE ur.user_id = ? GROUP BY p.code ORDER BY p.code"#,
+        user_id
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(covert_error)?;
+    Ok(codes)
+}
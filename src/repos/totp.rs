@@ -0,0 +1,177 @@
+use sqlx::MySql;
+use sqlx::MySqlPool;
+use sqlx::Transaction;
+
+use crate::core::rest::AppError;
+use crate::data::mysql::covert_error;
+use crate::models::totp::UserTotp;
+
+/// 获取用户的 TOTP 密钥，未登记时返回 None
+pub async fn get(conn: &MySqlPool, user_id: i64) -> Result<Option<UserTotp>, AppError> {
+    let totp = sqlx::query_as!(UserTotp, r#"SELECT * FROM user_totp WHERE user_id = ?"#, user_id)
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+    Ok(totp)
+}
+
+/// 保存待验证的新密钥，覆盖尚未启用的旧密钥
+pub async fn save_pending(
+    conn: &MySqlPool,
+    user_id: i64,
+    secret: &str,
+    now: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT INTO user_totp (user_id, secret, enabled_at, last_step, created_at, updated_at)
+           VALUES (?, ?, 0, 0, ?, ?)
+           ON DUPLICATE KEY UPDATE secret = IF(enabled_at = 0, VALUES(secret), secret),
+           created_at = IF(enabled_at = 0, VALUES(created_at), created_at),
+           updated_at = IF(enabled_at = 0, VALUES(updated_at), updated_at)"#,
+        user_id,
+        secret,
+        now,
+        now
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 启用 TOTP 并保存恢复码哈希，返回是否启用成功（已启用时返回 false）
+pub async fn enable(
+    conn: &MySqlPool,
+    user_id: i64,
+    step: i64,
+    code_hashes: &[String],
+    now: i64,
+) -> Result<bool, AppError> {
+    let mut tx = conn.begin().await.map_err(covert_error)?;
+    let enabled = sqlx::query!(
+        r#"UPDATE user_totp SET enabled_at = ?, last_step = ?, updated_at = ?
+           WHERE user_id = ? AND enabled_at = 0"#,
+        now,
+        step,
+        now,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(covert_error)?
+    .rows_affected();
+    if enabled == 0 {
+        tx.rollback().await.map_err(covert_error)?;
+        return Ok(false);
+    }
+
+    reset_recovery_codes(&mut tx, user_id, code_hashes, now).await?;
+    tx.commit().await.map_err(covert_error)?;
+
+    Ok(true)
+}
+
+/// 记录验证通过的时间步，返回是否晚于上次的时间步（防止重放）
+pub async fn use_step(conn: &MySqlPool, user_id: i64, step: i64) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_totp SET last_step = ? WHERE user_id = ? AND last_step < ?"#,
+        step,
+        user_id,
+        step
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 使用一个恢复码，返回恢复码是否有效且未被使用
+pub async fn use_recovery_code(
+    conn: &MySqlPool,
+    user_id: i64,
+    code_hash: &str,
+    used_at: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_recovery_code SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at = 0"#,
+        used_at,
+        user_id,
+        code_hash
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 替换用户的全部恢复码
+pub async fn replace_recovery_codes(
+    conn: &MySqlPool,
+    user_id: i64,
+    code_hashes: &[String],
+    now: i64,
+) -> Result<(), AppError> {
+    let mut tx = conn.begin().await.map_err(covert_error)?;
+    reset_recovery_codes(&mut tx, user_id, code_hashes, now).await?;
+    tx.commit().await.map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 统计未使用的恢复码数量
+pub async fn count_recovery_codes(conn: &MySqlPool, user_id: i64) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM user_recovery_code WHERE user_id = ? AND used_at = 0"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(count)
+}
+
+/// 关闭 TOTP，删除密钥和恢复码
+pub async fn delete(conn: &MySqlPool, user_id: i64) -> Result<(), AppError> {
+    let mut tx = conn.begin().await.map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM user_totp WHERE user_id = ?"#, user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM user_recovery_code WHERE user_id = ?"#, user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    tx.commit().await.map_err(covert_error)?;
+
+    Ok(())
+}
+
+/// 在事务中删除旧恢复码并写入新恢复码
+async fn reset_recovery_codes(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: i64,
+    code_hashes: &[String],
+    now: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(r#"DELETE FROM user_recovery_code WHERE user_id = ?"#, user_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(covert_error)?;
+    for code_hash in code_hashes {
+        sqlx::query!(
+            r#"INSERT INTO user_recovery_code (user_id, code_hash, used_at, created_at) VALUES (?, ?, 0, ?)"#,
+            user_id,
+            code_hash,
+            now
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(covert_error)?;
+    }
+
+    Ok(())
+}
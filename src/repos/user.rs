@@ -1,21 +1,30 @@
+use sqlx::Executor;
+use sqlx::MySql;
 use sqlx::MySqlPool;
 use sqlx::QueryBuilder;
 
 use crate::core::rest::AppError;
 use crate::data::mysql::covert_error;
+use crate::models::user::ProfileField;
 use crate::models::user::UserInfo;
+use crate::repos::Scope;
 
-/// 创建用户
-pub async fn create(conn: &MySqlPool, user: &mut UserInfo) -> Result<(), AppError> {
+/// 创建用户，可以在事务中执行
+pub async fn create<'c, E>(conn: E, user: &mut UserInfo) -> Result<(), AppError>
+where
+    E: Executor<'c, Database = MySql>,
+{
     user.id = sqlx::query_as!(UserInfo,
-        r#"INSERT INTO user_info (nick_name, avatar, signature, age, phone, wx_open_id, salt, password, created_at, updated_at, deleted_at) 
-           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        r#"INSERT INTO user_info (nick_name, avatar, signature, age, phone, wx_open_id, email, email_verified_at, salt, password, created_at, updated_at, deleted_at) 
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
         user.nick_name,
         user.avatar,
         user.signature,
         user.age,
         user.phone,
         user.wx_open_id,
+        user.email,
+        user.email_verified_at,
         user.salt,
         user.password,
         user.created_at,
@@ -23,72 +32,349 @@ pub async fn create(conn: &MySqlPool, user: &mut UserInfo) -> Result<(), AppErro
         user.deleted_at
     )
     .execute(conn)
-    .await.map_err(|err|{
-        covert_error(err)
-    })?.last_insert_id() as i64;
+    .await
+    .map_err(covert_error)?
+    .last_insert_id() as i64;
 
     Ok(())
 }
 
-/// 更新用户信息
-pub async fn update(conn: &MySqlPool, user: &UserInfo) -> Result<(), AppError> {
-    sqlx::query!(
+/// 以单条多行 INSERT 批量创建用户，返回插入的行数
+///
+/// 任一行违反唯一约束时整批都不会插入
+pub async fn create_many(conn: &MySqlPool, users: &[UserInfo]) -> Result<u64, AppError> {
+    if users.is_empty() {
+        return Ok(0);
+    }
+    let mut query_builder = QueryBuilder::<MySql>::new(
+        "INSERT INTO user_info (nick_name, avatar, signature, age, phone, wx_open_id, email, salt, password, created_at, updated_at, deleted_at) ",
+    );
+    query_builder.push_values(users, |mut row, user| {
+        row.push_bind(&user.nick_name)
+            .push_bind(&user.avatar)
+            .push_bind(&user.signature)
+            .push_bind(user.age)
+            .push_bind(&user.phone)
+            .push_bind(&user.wx_open_id)
+            .push_bind(&user.email)
+            .push_bind(&user.salt)
+            .push_bind(&user.password)
+            .push_bind(user.created_at)
+            .push_bind(user.updated_at)
+            .push_bind(user.deleted_at);
+    });
+
+    let result = query_builder
+        .build()
+        .execute(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(result.rows_affected())
+}
+
+/// 更新用户信息，仅当用户仍是读取时的版本 `user.version` 时更新，并将版本号加 1
+///
+/// 返回是否更新了用户，false 表示用户已被并发修改
+pub async fn update(conn: &MySqlPool, user: &UserInfo) -> Result<bool, AppError> {
+    let result = sqlx::query!(
         r#"UPDATE user_info SET 
            nick_name = ?, avatar = ?, signature = ?, age = ?, phone = ?, 
-           wx_open_id = ?, salt = ?, password = ?, updated_at = ?
-           WHERE id = ?"#,
+           wx_open_id = ?, email = ?, email_verified_at = ?, salt = ?, password = ?, updated_at = ?,
+           version = version + 1
+           WHERE id = ? AND version = ?"#,
         user.nick_name,
         user.avatar,
         user.signature,
         user.age,
         user.phone,
         user.wx_open_id,
+        user.email,
+        user.email_verified_at,
         user.salt,
         user.password,
         user.updated_at,
-        user.id
+        user.id,
+        user.version
     )
     .execute(conn)
     .await
-    .map_err(|err| covert_error(err))?;
+    .map_err(covert_error)?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-/// 根据ID获取用户
+/// 根据ID获取用户，不包含已删除的用户
 pub async fn get_by_id(conn: &MySqlPool, id: i64) -> Result<UserInfo, AppError> {
-    let user = sqlx::query_as!(UserInfo, r#"SELECT * FROM user_info WHERE id = ?"#, id)
-        .fetch_one(conn)
+    UserQuery::default().get_by_id(conn, id).await
+}
+
+/// 根据手机号（E.164 格式）获取用户，不存在或已删除时返回 None
+pub async fn get_by_phone(conn: &MySqlPool, phone: &str) -> Result<Option<UserInfo>, AppError> {
+    UserQuery::default().get_by_phone(conn, phone).await
+}
+
+/// 根据微信Open ID获取用户，不存在或已删除时返回 None
+pub async fn get_by_wx_open_id(
+    conn: &MySqlPool,
+    wx_open_id: &str,
+) -> Result<Option<UserInfo>, AppError> {
+    UserQuery::default()
+        .get_by_wx_open_id(conn, wx_open_id)
+        .await
+}
+
+/// 根据邮箱获取用户，不存在或已删除时返回 None
+pub async fn get_by_email(conn: &MySqlPool, email: &str) -> Result<Option<UserInfo>, AppError> {
+    UserQuery::default().get_by_email(conn, email).await
+}
+
+/// 获取用户列表（分页查询），不包含已删除的用户
+pub async fn list(conn: &MySqlPool, page: u32, page_size: u32) -> Result<Vec<UserInfo>, AppError> {
+    UserQuery::default().list(conn, page, page_size).await
+}
+
+/// 获取 ID 小于 `before_id` 的用户（键集分页），不包含已删除的用户
+pub async fn list_before(
+    conn: &MySqlPool,
+    before_id: i64,
+    limit: u32,
+) -> Result<Vec<UserInfo>, AppError> {
+    UserQuery::default()
+        .list_before(conn, before_id, limit)
         .await
-        .map_err(|err| covert_error(err))?;
-    Ok(user)
 }
 
-/// 根据手机号获取用户
-pub async fn get_by_phone(conn: &MySqlPool, phone: &str) -> Result<UserInfo, AppError> {
-    let user = sqlx::query_as!(UserInfo, r#"SELECT * FROM user_info WHERE phone = ?"#, phone)
+/// 获取用户总数，不包含已删除的用户
+pub async fn count(conn: &MySqlPool) -> Result<i64, AppError> {
+    UserQuery::default().count(conn).await
+}
+
+/// 包含已软删除用户的查询，例如登录时找回注销宽限期内的账号
+pub fn with_deleted() -> UserQuery {
+    UserQuery {
+        scope: Scope::WithDeleted,
+    }
+}
+
+/// 只查询已软删除的用户（回收站）
+pub fn only_deleted() -> UserQuery {
+    UserQuery {
+        scope: Scope::OnlyDeleted,
+    }
+}
+
+/// 按软删除范围过滤的用户查询，默认排除已删除的用户
+///
+/// 模块中的同名函数使用默认范围，需要已删除用户时显式使用 `with_deleted()` 或 `only_deleted()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserQuery {
+    scope: Scope,
+}
+
+impl UserQuery {
+    /// 根据ID获取用户
+    pub async fn get_by_id(&self, conn: &MySqlPool, id: i64) -> Result<UserInfo, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let user = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE id = ? AND deleted_at BETWEEN ? AND ?"#,
+            id,
+            min,
+            max
+        )
         .fetch_one(conn)
         .await
-        .map_err(|err| covert_error(err))?;
-    Ok(user)
+        .map_err(covert_error)?;
+        Ok(user)
+    }
+
+    /// 根据手机号（E.164 格式）获取用户，不存在时返回 None
+    pub async fn get_by_phone(
+        &self,
+        conn: &MySqlPool,
+        phone: &str,
+    ) -> Result<Option<UserInfo>, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let user = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE phone = ? AND deleted_at BETWEEN ? AND ?"#,
+            phone,
+            min,
+            max
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+        Ok(user)
+    }
+
+    /// 根据微信Open ID获取用户，不存在时返回 None
+    pub async fn get_by_wx_open_id(
+        &self,
+        conn: &MySqlPool,
+        wx_open_id: &str,
+    ) -> Result<Option<UserInfo>, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let user = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE wx_open_id = ? AND deleted_at BETWEEN ? AND ?"#,
+            wx_open_id,
+            min,
+            max
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+        Ok(user)
+    }
+
+    /// 根据邮箱获取用户，不存在时返回 None
+    pub async fn get_by_email(
+        &self,
+        conn: &MySqlPool,
+        email: &str,
+    ) -> Result<Option<UserInfo>, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let user = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE email = ? AND deleted_at BETWEEN ? AND ?"#,
+            email,
+            min,
+            max
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(covert_error)?;
+        Ok(user)
+    }
+
+    /// 获取用户列表（分页查询），按 ID 倒序
+    pub async fn list(
+        &self,
+        conn: &MySqlPool,
+        page: u32,
+        page_size: u32,
+    ) -> Result<Vec<UserInfo>, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let offset = (page - 1) * page_size;
+        let users = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE deleted_at BETWEEN ? AND ? 
+               ORDER BY id DESC LIMIT ? OFFSET ?"#,
+            min,
+            max,
+            page_size as i64,
+            offset as i64
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+        Ok(users)
+    }
+
+    /// 获取 ID 小于 `before_id` 的用户（键集分页），按 ID 倒序，最多 `limit` 个
+    ///
+    /// 翻页深度不影响性能，翻页期间新注册的用户也不会造成重复或遗漏
+    pub async fn list_before(
+        &self,
+        conn: &MySqlPool,
+        before_id: i64,
+        limit: u32,
+    ) -> Result<Vec<UserInfo>, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let users = sqlx::query_as!(
+            UserInfo,
+            r#"SELECT * FROM user_info WHERE id < ? AND deleted_at BETWEEN ? AND ? 
+               ORDER BY id DESC LIMIT ?"#,
+            before_id,
+            min,
+            max,
+            limit as i64
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+        Ok(users)
+    }
+
+    /// 获取用户总数
+    pub async fn count(&self, conn: &MySqlPool) -> Result<i64, AppError> {
+        let (min, max) = self.scope.deleted_at_range();
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM user_info WHERE deleted_at BETWEEN ? AND ?"#,
+            min,
+            max
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(covert_error)?;
+
+        Ok(count)
+    }
 }
 
-/// 根据微信Open ID获取用户
-pub async fn get_by_wx_open_id(conn: &MySqlPool, wx_open_id: &str) -> Result<UserInfo, AppError> {
-    let user =
-        sqlx::query_as!(UserInfo, r#"SELECT * FROM user_info WHERE wx_open_id = ?"#, wx_open_id)
-            .fetch_one(conn)
-            .await
-            .map_err(|err| covert_error(err))?;
-    Ok(user)
+/// 更新用户密码哈希，仅当用户仍是 `version` 版本时更新，返回是否更新了用户
+pub async fn update_password(
+    conn: &MySqlPool,
+    id: i64,
+    version: i64,
+    salt: &str,
+    password: &str,
+    updated_at: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_info SET salt = ?, password = ?, updated_at = ?, version = version + 1 WHERE id = ? AND version = ?"#,
+        salt,
+        password,
+        updated_at,
+        id,
+        version
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 更新用户绑定的邮箱，邮箱已通过验证码验证，同时记录验证时间
+///
+/// 仅当用户仍是 `version` 版本时更新，返回是否更新了用户
+pub async fn update_email(
+    conn: &MySqlPool,
+    id: i64,
+    version: i64,
+    email: &str,
+    updated_at: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_info SET email = ?, email_verified_at = ?, updated_at = ?, version = version + 1 WHERE id = ? AND version = ?"#,
+        email,
+        updated_at,
+        updated_at,
+        id,
+        version
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
 }
 
 /// 软删除用户（设置deleted_at时间戳）
 pub async fn delete(conn: &MySqlPool, id: i64, deleted_at: i64) -> Result<(), AppError> {
-    sqlx::query!(r#"UPDATE user_info SET deleted_at = ? WHERE id = ?"#, deleted_at, id)
-        .execute(conn)
-        .await
-        .map_err(|err| covert_error(err))?;
+    sqlx::query!(
+        r#"UPDATE user_info SET deleted_at = ?, version = version + 1 WHERE id = ?"#,
+        deleted_at,
+        id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
 
     Ok(())
 }
@@ -98,88 +384,376 @@ pub async fn hard_delete(conn: &MySqlPool, id: i64) -> Result<(), AppError> {
     sqlx::query!(r#"DELETE FROM user_info WHERE id = ?"#, id)
         .execute(conn)
         .await
-        .map_err(|err| covert_error(err))?;
+        .map_err(covert_error)?;
 
     Ok(())
 }
 
-/// 获取用户列表（分页查询）
-pub async fn list(conn: &MySqlPool, page: u32, page_size: u32) -> Result<Vec<UserInfo>, AppError> {
-    let offset = (page - 1) * page_size;
-    let users = sqlx::query_as!(
-        UserInfo,
-        r#"SELECT * FROM user_info WHERE deleted_at = 0 ORDER BY id DESC LIMIT ? OFFSET ?"#,
-        page_size as i64,
-        offset as i64
+/// 申请注销：软删除用户并记录计划彻底删除的时间
+pub async fn schedule_deletion(
+    conn: &MySqlPool,
+    id: i64,
+    deleted_at: i64,
+    purge_at: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"UPDATE user_info SET deleted_at = ?, purge_at = ?, updated_at = ?, version = version + 1 WHERE id = ?"#,
+        deleted_at,
+        purge_at,
+        deleted_at,
+        id
     )
-    .fetch_all(conn)
+    .execute(conn)
     .await
-    .map_err(|err| covert_error(err))?;
+    .map_err(covert_error)?;
 
-    Ok(users)
+    Ok(())
 }
 
-/// 获取用户总数
-pub async fn count(conn: &MySqlPool) -> Result<i64, AppError> {
-    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) FROM user_info WHERE deleted_at = 0"#)
-        .fetch_one(conn)
-        .await
-        .map_err(|err| covert_error(err))?;
+/// 撤销注销申请，返回是否存在待处理的注销申请
+pub async fn cancel_deletion(conn: &MySqlPool, id: i64, updated_at: i64) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_info SET deleted_at = 0, purge_at = 0, updated_at = ?, version = version + 1 WHERE id = ? AND purge_at > 0"#,
+        updated_at,
+        id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
 
-    Ok(count)
+    Ok(result.rows_affected() > 0)
 }
 
-/// 根据昵称搜索用户
-pub async fn search_by_nickname(
-    conn: &MySqlPool,
-    nickname: &str,
-    page: u32,
-    page_size: u32,
-) -> Result<Vec<UserInfo>, AppError> {
-    let offset = (page - 1) * page_size;
-    let search_pattern = format!("%{}%", nickname);
-
-    let users = sqlx::query_as!(
-        UserInfo,
-        r#"SELECT * FROM user_info 
-           WHERE nick_name LIKE ? AND deleted_at = 0 
-           ORDER BY id DESC LIMIT ? OFFSET ?"#,
-        search_pattern,
-        page_size as i64,
-        offset as i64
+/// 恢复已软删除的用户，同时撤销待处理的注销申请，返回是否恢复了用户
+pub async fn restore(conn: &MySqlPool, id: i64, updated_at: i64) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE user_info SET deleted_at = 0, purge_at = 0, updated_at = ?, version = version + 1 WHERE id = ? AND deleted_at > 0"#,
+        updated_at,
+        id
+    )
+    .execute(conn)
+    .await
+    .map_err(covert_error)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// 获取注销宽限期已过、需要彻底删除的用户ID
+pub async fn list_due_purge(conn: &MySqlPool, now: i64, limit: u32) -> Result<Vec<i64>, AppError> {
+    let ids = sqlx::query_scalar!(
+        r#"SELECT id FROM user_info WHERE purge_at > 0 AND purge_at <= ? ORDER BY purge_at LIMIT ?"#,
+        now,
+        limit as i64
     )
     .fetch_all(conn)
     .await
-    .map_err(|err| covert_error(err))?;
+    .map_err(covert_error)?;
 
-    Ok(users)
+    Ok(ids)
 }
 
-/// 更新用户部分信息（使用QueryBuilder动态构建更新语句）
-pub async fn update_partial(
-    conn: &MySqlPool,
-    id: i64,
-    updates: &[(&str, &str)],
-) -> Result<(), sqlx::Error> {
-    if updates.is_empty() {
-        return Ok(());
+/// 彻底删除注销宽限期已过的用户及其关联数据（角色、API Key、两步验证、第三方账号、登录记录），
+/// 返回是否删除了用户
+///
+/// 用户在宽限期内重新登录撤销了注销时不做任何删除
+pub async fn purge(conn: &MySqlPool, id: i64, now: i64) -> Result<bool, AppError> {
+    let mut tx = conn.begin().await.map_err(covert_error)?;
+    let deleted = sqlx::query!(
+        r#"DELETE FROM user_info WHERE id = ? AND purge_at > 0 AND purge_at <= ?"#,
+        id,
+        now
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(covert_error)?
+    .rows_affected();
+    if deleted == 0 {
+        tx.rollback().await.map_err(covert_error)?;
+        return Ok(false);
     }
 
-    let mut query_builder = QueryBuilder::new("UPDATE user_info SET ");
+    sqlx::query!(r#"DELETE FROM user_role WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM api_key WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM user_totp WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM user_recovery_code WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM user_identity WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    sqlx::query!(r#"DELETE FROM login_event WHERE user_id = ?"#, id)
+        .execute(&mut *tx)
+        .await
+        .map_err(covert_error)?;
+    tx.commit().await.map_err(covert_error)?;
 
-    for (i, (field, value)) in updates.iter().enumerate() {
-        if i > 0 {
-            query_builder.push(", ");
-        }
-        query_builder.push(field);
+    Ok(true)
+}
+
+/// 构造包含匹配的 LIKE 模式，转义通配符
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// 更新用户资料的部分字段，同时刷新 updated_at 并将版本号加 1，已删除的用户不会被更新
+///
+/// 列名只来自 `ProfileField` 的白名单，值全部通过参数绑定；仅当用户仍是 `version` 版本时更新，
+/// 返回是否更新了用户
+pub async fn update_profile(
+    conn: &MySqlPool,
+    id: i64,
+    version: i64,
+    fields: &[ProfileField],
+    updated_at: i64,
+) -> Result<bool, AppError> {
+    let mut query_builder =
+        QueryBuilder::<MySql>::new("UPDATE user_info SET version = version + 1, updated_at = ");
+    query_builder.push_bind(updated_at);
+
+    for field in fields {
+        query_builder.push(", ");
+        query_builder.push(field.column());
         query_builder.push(" = ");
-        query_builder.push_bind(value);
+        match field {
+            ProfileField::NickName(value)
+            | ProfileField::Avatar(value)
+            | ProfileField::Signature(value) => query_builder.push_bind(value.clone()),
+            ProfileField::Age(value) => query_builder.push_bind(*value),
+        };
     }
 
     query_builder.push(" WHERE id = ");
     query_builder.push_bind(id);
+    query_builder.push(" AND version = ");
+    query_builder.push_bind(version);
+    push_scope(&mut query_builder, Scope::Active);
 
-    query_builder.build().execute(conn).await?;
+    let result = query_builder
+        .build()
+        .execute(conn)
+        .await
+        .map_err(covert_error)?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
+}
+
+/// 获取邮箱、手机号或微信 Open ID 在给定列表中的用户，包含已软删除的用户
+///
+/// 批量导入前用于找出已被占用的登录标识
+pub async fn list_by_identifiers(
+    conn: &MySqlPool,
+    emails: &[String],
+    phones: &[String],
+    wx_open_ids: &[String],
+) -> Result<Vec<UserInfo>, AppError> {
+    let mut query_builder = QueryBuilder::<MySql>::new("SELECT * FROM user_info WHERE 1 = 0");
+    for (column, values) in [
+        ("email", emails),
+        ("phone", phones),
+        ("wx_open_id", wx_open_ids),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        query_builder.push(" OR ");
+        query_builder.push(column);
+        query_builder.push(" IN (");
+        let mut separated = query_builder.separated(", ");
+        for value in values {
+            separated.push_bind(value);
+        }
+        separated.push_unseparated(")");
+    }
+
+    let users = query_builder
+        .build_query_as::<UserInfo>()
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(users)
+}
+
+/// 用户导出的过滤条件，默认导出全部未删除的用户
+#[derive(Debug, Clone, Default)]
+pub struct UserFilter {
+    /// 昵称包含的文本，`%` 和 `_` 按字面匹配
+    pub nickname: Option<String>,
+    /// 注册时间下界（含）
+    pub created_from: Option<i64>,
+    /// 注册时间上界（不含）
+    pub created_to: Option<i64>,
+    /// 软删除范围
+    pub scope: Scope,
+}
+
+/// 按 ID 正序获取 ID 大于 `after_id` 且满足过滤条件的用户，最多 `limit` 个
+///
+/// 导出时逐批调用，每批从上一批最后一个用户之后开始，内存中只保留一批用户
+pub async fn list_filtered_after(
+    conn: &MySqlPool,
+    filter: &UserFilter,
+    after_id: i64,
+    limit: u32,
+) -> Result<Vec<UserInfo>, AppError> {
+    let mut query_builder = QueryBuilder::<MySql>::new("SELECT * FROM user_info WHERE id > ");
+    query_builder.push_bind(after_id);
+    push_scope(&mut query_builder, filter.scope);
+    if let Some(nickname) = &filter.nickname {
+        query_builder.push(" AND nick_name LIKE ");
+        query_builder.push_bind(like_pattern(nickname));
+    }
+    if let Some(created_from) = filter.created_from {
+        query_builder.push(" AND created_at >= ");
+        query_builder.push_bind(created_from);
+    }
+    if let Some(created_to) = filter.created_to {
+        query_builder.push(" AND created_at < ");
+        query_builder.push_bind(created_to);
+    }
+    query_builder.push(" ORDER BY id LIMIT ");
+    query_builder.push_bind(limit as i64);
+
+    let users = query_builder
+        .build_query_as::<UserInfo>()
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(users)
+}
+
+/// 按 ID 获取用户，不包含已删除的用户，结果顺序不定
+pub async fn list_by_ids(conn: &MySqlPool, ids: &[i64]) -> Result<Vec<UserInfo>, AppError> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut query_builder = QueryBuilder::<MySql>::new("SELECT * FROM user_info WHERE id IN (");
+    let mut separated = query_builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    query_builder.push(")");
+    push_scope(&mut query_builder, Scope::Active);
+
+    let users = query_builder
+        .build_query_as::<UserInfo>()
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(users)
+}
+
+/// 全文检索昵称和签名（ngram 分词的 FULLTEXT 索引），不包含已删除的用户
+///
+/// 按相关度倒序、相关度相同时按 ID 倒序，跳过 `offset` 个后最多返回 `limit` 个用户 ID 及其相关度
+pub async fn search_fulltext(
+    conn: &MySqlPool,
+    query: &str,
+    offset: u32,
+    limit: u32,
+) -> Result<Vec<(i64, f64)>, AppError> {
+    let mut query_builder =
+        QueryBuilder::<MySql>::new("SELECT id, MATCH(nick_name, signature) AGAINST(");
+    query_builder.push_bind(query);
+    query_builder.push(" IN NATURAL LANGUAGE MODE) AS score FROM user_info WHERE ");
+    push_fulltext_match(&mut query_builder, query);
+    push_scope(&mut query_builder, Scope::Active);
+    query_builder.push(" ORDER BY score DESC, id DESC LIMIT ");
+    query_builder.push_bind(limit as i64);
+    query_builder.push(" OFFSET ");
+    query_builder.push_bind(offset as i64);
+
+    let hits = query_builder
+        .build_query_as::<(i64, f64)>()
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(hits)
+}
+
+/// 全文检索昵称和签名，返回排在 `after`（相关度, ID）之后的最多 `limit` 个用户 ID 及其相关度，
+/// 不包含已删除的用户
+///
+/// 排序与 `search_fulltext` 相同；按上一批最后一个结果继续检索，不随翻页深度变慢，
+/// 排在该位置之前新增的用户也不会让后续结果错位
+pub async fn search_fulltext_after(
+    conn: &MySqlPool,
+    query: &str,
+    after: Option<(f64, i64)>,
+    limit: u32,
+) -> Result<Vec<(i64, f64)>, AppError> {
+    let mut query_builder =
+        QueryBuilder::<MySql>::new("SELECT id, MATCH(nick_name, signature) AGAINST(");
+    query_builder.push_bind(query);
+    query_builder.push(" IN NATURAL LANGUAGE MODE) AS score FROM user_info WHERE ");
+    push_fulltext_match(&mut query_builder, query);
+    push_scope(&mut query_builder, Scope::Active);
+    if let Some((score, id)) = after {
+        query_builder.push(" HAVING score < ");
+        query_builder.push_bind(score);
+        query_builder.push(" OR (score = ");
+        query_builder.push_bind(score);
+        query_builder.push(" AND id < ");
+        query_builder.push_bind(id);
+        query_builder.push(")");
+    }
+    query_builder.push(" ORDER BY score DESC, id DESC LIMIT ");
+    query_builder.push_bind(limit as i64);
+
+    let hits = query_builder
+        .build_query_as::<(i64, f64)>()
+        .fetch_all(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(hits)
+}
+
+/// 统计全文检索命中的用户数，不包含已删除的用户
+pub async fn count_fulltext(conn: &MySqlPool, query: &str) -> Result<i64, AppError> {
+    let mut query_builder = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM user_info WHERE ");
+    push_fulltext_match(&mut query_builder, query);
+    push_scope(&mut query_builder, Scope::Active);
+
+    let count = query_builder
+        .build_query_scalar::<i64>()
+        .fetch_one(conn)
+        .await
+        .map_err(covert_error)?;
+
+    Ok(count)
+}
+
+/// 追加软删除范围条件，与 `UserQuery` 的查询一致
+fn push_scope(query_builder: &mut QueryBuilder<'_, MySql>, scope: Scope) {
+    let (min, max) = scope.deleted_at_range();
+    query_builder.push(" AND deleted_at BETWEEN ");
+    query_builder.push_bind(min);
+    query_builder.push(" AND ");
+    query_builder.push_bind(max);
+}
+
+/// 追加全文检索条件，自然语言模式不解析检索文本中的运算符
+fn push_fulltext_match(query_builder: &mut QueryBuilder<'_, MySql>, query: &str) {
+    query_builder.push("MATCH(nick_name, signature) AGAINST(");
+    query_builder.push_bind(query.to_string());
+    query_builder.push(" IN NATURAL LANGUAGE MODE)");
 }
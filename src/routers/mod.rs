@@ -1,11 +1,15 @@
 use std::time::Duration;
 
+use axum::Extension;
 use axum::Router;
+use axum::extract::DefaultBodyLimit;
 use axum::extract::Request;
 use axum::http::HeaderValue;
+use axum::http::StatusCode;
 use axum::middleware;
 use axum::middleware::Next;
 use axum::response::Response;
+use axum::routing::delete;
 use axum::routing::get;
 use axum::routing::post;
 use tower::ServiceBuilder;
@@ -20,9 +24,20 @@ use tower_request_id::RequestIdLayer;
 use tracing::Instrument;
 use tracing::Level;
 
+use crate::core::rbac::RequirePermission;
 use crate::core::state::AppState;
+use crate::handlers::account;
+use crate::handlers::blob;
+use crate::handlers::bulk;
 use crate::handlers::foo;
 use crate::handlers::health;
+use crate::handlers::lockout;
+use crate::handlers::login_event;
+use crate::handlers::oidc;
+use crate::handlers::rbac;
+use crate::handlers::session;
+use crate::handlers::token;
+use crate::handlers::two_factor;
 use crate::handlers::user as userHandler;
 
 async fn not_implemented() -> crate::core::Result<u8> {
@@ -44,24 +59,110 @@ pub fn app_routers(state: AppState) -> Router {
         .layer(RequestDecompressionLayer::new())
         .layer(CompressionLayer::new())
         .layer(trace_layer);
+    // room for the multipart framing around the largest accepted avatar
+    let avatar_body_limit = state.avatar.max_bytes as usize + 64 * 1024;
     //
-    let app = Router::new()
+    Router::new()
+        .route(
+            "/users",
+            get(userHandler::list_users).route_layer(RequirePermission("user:read")),
+        )
+        .route(
+            "/users/search",
+            get(userHandler::search_users).route_layer(RequirePermission("user:read")),
+        )
         .route("/user/{id}", get(userHandler::user_by_id))
         .route("/user/wx/login", post(userHandler::wechat_login))
         .route("/user/email", post(userHandler::bind_email))
         .route("/user/email/pre", post(userHandler::pre_bind_email))
-        .route("/user/random", get(userHandler::random_user))
+        .route("/user/email/login", post(userHandler::email_login))
+        .route("/user/email/register", post(userHandler::email_register))
+        .route(
+            "/user/email/register/pre",
+            post(userHandler::pre_email_register),
+        )
+        .route("/user/sms/pre", post(userHandler::sms_pre))
+        .route("/user/sms/login", post(userHandler::sms_login))
+        .route("/user/password/forgot", post(userHandler::forgot_password))
+        .route("/user/password/reset", post(userHandler::reset_password))
+        .route(
+            "/user/random",
+            get(userHandler::random_user).route_layer(RequirePermission("user:write")),
+        )
+        .route("/user/token/refresh", post(token::refresh))
+        .route("/user/logout", post(token::logout))
+        .route(
+            "/user/me",
+            delete(account::delete_me).patch(userHandler::update_me),
+        )
+        .route("/user/me/export", get(account::export_me))
+        .route(
+            "/user/me/avatar",
+            post(userHandler::upload_avatar).layer(DefaultBodyLimit::max(avatar_body_limit)),
+        )
+        .route("/blobs/{*key}", get(blob::get_blob))
+        .route("/user/me/logins", get(login_event::list_logins))
+        .route("/user/sessions", get(session::list_sessions))
+        .route("/user/sessions/{id}", delete(session::revoke_session))
+        .route("/user/2fa/totp/enroll", post(two_factor::enroll_totp))
+        .route("/user/2fa/totp/activate", post(two_factor::activate_totp))
+        .route("/user/2fa/totp/disable", post(two_factor::disable_totp))
+        .route(
+            "/user/2fa/recovery-codes",
+            post(two_factor::regenerate_recovery_codes),
+        )
+        .route("/user/2fa/login", post(two_factor::two_factor_login))
+        .route("/user/oidc/providers", get(oidc::providers))
+        .route("/user/oidc/{provider}/authorize", get(oidc::authorize))
+        .route("/user/oidc/{provider}/link", post(oidc::link))
+        .route("/user/oidc/{provider}/callback", post(oidc::callback))
+        .route("/user/identities", get(oidc::list_identities))
+        .route("/user/identities/{id}", delete(oidc::unlink_identity))
+        .route(
+            "/admin/roles",
+            get(rbac::list_roles).route_layer(RequirePermission("role:admin")),
+        )
+        .route(
+            "/admin/users/{id}/roles",
+            get(rbac::user_roles)
+                .post(rbac::assign_role)
+                .route_layer(RequirePermission("role:admin")),
+        )
+        .route(
+            "/admin/users/{id}/roles/{role}",
+            delete(rbac::remove_role).route_layer(RequirePermission("role:admin")),
+        )
+        .route(
+            "/admin/users/trash",
+            get(userHandler::list_deleted_users).route_layer(RequirePermission("user:read")),
+        )
+        .route(
+            "/admin/users/import",
+            post(bulk::import_users).route_layer(RequirePermission("user:write")),
+        )
+        .route(
+            "/admin/users/export",
+            get(bulk::export_users).route_layer(RequirePermission("user:read")),
+        )
+        .route(
+            "/admin/users/{id}/restore",
+            post(userHandler::restore_user).route_layer(RequirePermission("user:write")),
+        )
+        .route(
+            "/admin/users/{id}/lockout",
+            delete(lockout::unlock_user).route_layer(RequirePermission("user:write")),
+        )
         .route("/foo", get(foo::foo))
         .route("/health", get(health::health))
         .fallback(not_implemented)
         .layer(layer)
-        .layer(TimeoutLayer::new(Duration::from_secs(30)))
+        .layer(TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, Duration::from_secs(30)))
         .layer(cors_layer)
         .layer(middleware::from_fn(inject_request_id))
         .layer(RequestIdLayer)
-        .with_state(state);
-
-    app
+        // used by route guards such as `RequirePermission`
+        .layer(Extension(state.clone()))
+        .with_state(state)
 }
 
 /// Middleware function that injects request ID into the response headers and tracing spans
@@ -0,0 +1,335 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use tantivy::DocId;
+use tantivy::Index;
+use tantivy::IndexReader;
+use tantivy::IndexWriter;
+use tantivy::ReloadPolicy;
+use tantivy::Score;
+use tantivy::SegmentReader;
+use tantivy::TantivyDocument;
+use tantivy::Term;
+use tantivy::collector::Count;
+use tantivy::collector::TopDocs;
+use tantivy::query::BooleanQuery;
+use tantivy::query::BoostQuery;
+use tantivy::query::Occur;
+use tantivy::query::Query;
+use tantivy::query::TermQuery;
+use tantivy::schema::FAST;
+use tantivy::schema::Field;
+use tantivy::schema::INDEXED;
+use tantivy::schema::IndexRecordOption;
+use tantivy::schema::STORED;
+use tantivy::schema::Schema;
+use tantivy::schema::TextFieldIndexing;
+use tantivy::schema::TextOptions;
+use tantivy::schema::Value;
+use tantivy::tokenizer::PreTokenizedString;
+use tantivy::tokenizer::Token;
+
+use crate::search::SearchHit;
+use crate::search::SearchHits;
+use crate::search::SearchIndex;
+use crate::search::UserDocument;
+use crate::search::grams;
+
+/// Weight of a term found in the nickname relative to one found in the signature
+const NICK_NAME_BOOST: f32 = 2.0;
+
+/// Backend keeping a tantivy index in the memory of the process
+///
+/// Text is indexed pre-split by `grams`, so queries and highlights use the very same terms.
+/// Results are ranked by BM25, a match in the nickname weighing twice a match in the signature.
+/// Writes and searches are CPU bound and run on the blocking pool.
+pub struct EmbeddedSearchIndex {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: Field,
+    nick_name: Field,
+    signature: Field,
+    writer: Mutex<IndexWriter>,
+    reader: IndexReader,
+}
+
+impl EmbeddedSearchIndex {
+    /// Creates an empty index, `writer_memory` bytes being shared by the indexing buffers
+    pub fn new(writer_memory: usize) -> anyhow::Result<EmbeddedSearchIndex> {
+        let mut builder = Schema::builder();
+        let text = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default().set_index_option(IndexRecordOption::WithFreqs),
+        );
+        let id = builder.add_i64_field("id", INDEXED | STORED | FAST);
+        let nick_name = builder.add_text_field("nick_name", text.clone());
+        let signature = builder.add_text_field("signature", text);
+        let index = Index::create_in_ram(builder.build());
+        let writer = index
+            .writer_with_num_threads(1, writer_memory)
+            .map_err(|err| anyhow::anyhow!("create search index writer error {}", err))?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(EmbeddedSearchIndex {
+            inner: Arc::new(Inner {
+                id,
+                nick_name,
+                signature,
+                writer: Mutex::new(writer),
+                reader,
+            }),
+        })
+    }
+
+    /// Applies `change` with the writer, then commits and makes it visible to searches
+    async fn write<F>(&self, change: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&Inner, &IndexWriter) -> anyhow::Result<()> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = inner
+                .writer
+                .lock()
+                .map_err(|_| anyhow::anyhow!("search index writer poisoned"))?;
+            change(&inner, &writer)?;
+            writer.commit()?;
+            inner.reader.reload()?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl SearchIndex for EmbeddedSearchIndex {
+    fn follows_table(&self) -> bool {
+        false
+    }
+
+    async fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.inner.reader.searcher().num_docs() == 0)
+    }
+
+    async fn upsert(&self, users: Vec<UserDocument>) -> anyhow::Result<()> {
+        self.write(move |inner, writer| {
+            for user in users {
+                writer.delete_term(Term::from_field_i64(inner.id, user.id));
+                let mut document = TantivyDocument::new();
+                document.add_i64(inner.id, user.id);
+                document.add_pre_tokenized_text(inner.nick_name, tokenize(&user.nick_name));
+                document.add_pre_tokenized_text(inner.signature, tokenize(&user.signature));
+                writer.add_document(document)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove(&self, ids: Vec<i64>) -> anyhow::Result<()> {
+        self.write(move |inner, writer| {
+            for id in ids {
+                writer.delete_term(Term::from_field_i64(inner.id, id));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn search(&self, query: &str, offset: u32, limit: u32) -> anyhow::Result<SearchHits> {
+        let Some(query) = self.inner.query(query) else {
+            return Ok(SearchHits::default());
+        };
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let searcher = inner.reader.searcher();
+            let top = TopDocs::with_limit(limit as usize).and_offset(offset as usize);
+            let (total, top) = searcher.search(&query, &(Count, top))?;
+
+            let mut hits = Vec::with_capacity(top.len());
+            for (score, address) in top {
+                let document: TantivyDocument = searcher.doc(address)?;
+                let id = document
+                    .get_first(inner.id)
+                    .and_then(|value| value.as_i64())
+                    .ok_or_else(|| anyhow::anyhow!("search index document without id"))?;
+                hits.push(SearchHit {
+                    id,
+                    score: score as f64,
+                });
+            }
+            Ok(SearchHits {
+                total: total as u64,
+                hits,
+            })
+        })
+        .await?
+    }
+
+    async fn search_after(
+        &self,
+        query: &str,
+        after: Option<SearchHit>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<SearchHit>> {
+        let Some(query) = self.inner.query(query) else {
+            return Ok(Vec::new());
+        };
+        let after = after.map(|hit| (hit.score, hit.id));
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            // ranked by (score, id), the hits up to `after` rank below all others as `None`
+            let top =
+                TopDocs::with_limit(limit as usize).tweak_score(move |segment: &SegmentReader| {
+                    let ids = segment
+                        .fast_fields()
+                        .i64("id")
+                        .ok()
+                        .map(|column| column.first_or_default_col(0));
+                    move |doc: DocId, score: Score| {
+                        let key = (score as f64, ids.as_ref()?.get_val(doc));
+                        match after {
+                            Some(after) if key >= after => None,
+                            _ => Some(key),
+                        }
+                    }
+                });
+            let top = inner.reader.searcher().search(&query, &top)?;
+            Ok(top
+                .into_iter()
+                .filter_map(|(key, _)| key)
+                .map(|(score, id)| SearchHit { id, score })
+                .collect())
+        })
+        .await?
+    }
+}
+
+impl Inner {
+    /// Query matching any term of `text` in the nickname or the signature, `None` without terms
+    fn query(&self, text: &str) -> Option<BooleanQuery> {
+        let terms: BTreeSet<String> = grams(text).into_iter().map(|gram| gram.text).collect();
+        if terms.is_empty() {
+            return None;
+        }
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for term in &terms {
+            for (field, boost) in [(self.nick_name, NICK_NAME_BOOST), (self.signature, 1.0)] {
+                let query = TermQuery::new(
+                    Term::from_field_text(field, term),
+                    IndexRecordOption::WithFreqs,
+                );
+                clauses.push((Occur::Should, Box::new(BoostQuery::new(Box::new(query), boost))));
+            }
+        }
+        Some(BooleanQuery::new(clauses))
+    }
+}
+
+/// Text with its terms, indexed as they are
+fn tokenize(text: &str) -> PreTokenizedString {
+    let tokens = grams(text)
+        .into_iter()
+        .enumerate()
+        .map(|(position, gram)| Token {
+            offset_from: gram.from,
+            offset_to: gram.to,
+            position,
+            text: gram.text,
+            position_length: 1,
+        })
+        .collect();
+    PreTokenizedString {
+        text: text.to_string(),
+        tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: i64, nick_name: &str, signature: &str) -> UserDocument {
+        UserDocument {
+            id,
+            nick_name: nick_name.to_string(),
+            signature: signature.to_string(),
+        }
+    }
+
+    fn ids(hits: &SearchHits) -> Vec<i64> {
+        hits.hits.iter().map(|hit| hit.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let index = EmbeddedSearchIndex::new(20_000_000).unwrap();
+        assert!(index.is_empty().await.unwrap());
+        index
+            .upsert(vec![
+                user(1, "小明", "喜欢写代码"),
+                user(2, "代码小王子", "rust lover"),
+                user(3, "Alice", "我是小明的同学"),
+            ])
+            .await
+            .unwrap();
+        assert!(!index.is_empty().await.unwrap());
+
+        // a match in the nickname ranks first
+        let hits = index.search("小明", 0, 10).await.unwrap();
+        assert_eq!(hits.total, 2);
+        assert_eq!(ids(&hits), [1, 3]);
+        assert!(hits.hits[0].score > hits.hits[1].score);
+
+        let hits = index.search("代码", 0, 10).await.unwrap();
+        assert_eq!(ids(&hits), [2, 1]);
+        let hits = index.search("代码", 1, 10).await.unwrap();
+        assert_eq!((hits.total, ids(&hits)), (2, vec![1]));
+
+        let hits = index.search("RUST", 0, 10).await.unwrap();
+        assert_eq!(ids(&hits), [2]);
+        assert_eq!(index.search("?!", 0, 10).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_after() {
+        let index = EmbeddedSearchIndex::new(20_000_000).unwrap();
+        let users = (1..=5).map(|id| user(id, "小明", "")).collect();
+        index.upsert(users).await.unwrap();
+        index.upsert(vec![user(6, "小明", "小明")]).await.unwrap();
+
+        // equal scores are ranked by descending id
+        let first = index.search_after("小明", None, 3).await.unwrap();
+        assert_eq!(first.iter().map(|hit| hit.id).collect::<Vec<_>>(), [6, 5, 4]);
+        assert!(first[0].score > first[1].score);
+
+        let rest = index
+            .search_after("小明", first.last().cloned(), 3)
+            .await
+            .unwrap();
+        assert_eq!(rest.iter().map(|hit| hit.id).collect::<Vec<_>>(), [3, 2, 1]);
+        let end = index
+            .search_after("小明", rest.last().cloned(), 3)
+            .await
+            .unwrap();
+        assert!(end.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_and_remove() {
+        let index = EmbeddedSearchIndex::new(20_000_000).unwrap();
+        index.upsert(vec![user(1, "小明", "")]).await.unwrap();
+        index.upsert(vec![user(1, "大明", "")]).await.unwrap();
+        assert_eq!(index.search("小明", 0, 10).await.unwrap().total, 0);
+        assert_eq!(ids(&index.search("大明", 0, 10).await.unwrap()), [1]);
+
+        index.remove(vec![1]).await.unwrap();
+        assert_eq!(index.search("大明", 0, 10).await.unwrap().total, 0);
+        assert!(index.is_empty().await.unwrap());
+    }
+}
@@ -0,0 +1,446 @@
+pub mod embedded;
+pub mod mysql;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use sqlx::MySqlPool;
+use tracing::error;
+use tracing::info;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::models::user::UserInfo;
+use crate::repos;
+use crate::repos::user::UserFilter;
+use crate::search::embedded::EmbeddedSearchIndex;
+use crate::search::mysql::MySqlSearchIndex;
+
+/// Full-text search configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct SearchConf {
+    /// Search backend, either "mysql" or "embedded"
+    /// - mysql: FULLTEXT index of `user_info` with the ngram parser, kept up to date by MySQL
+    /// - embedded: in-memory index of the process, built from `user_info` at startup and
+    ///   updated by the application on every change
+    #[default("mysql")]
+    pub backend: String,
+
+    /// Maximum time spent on one search or index update, in seconds
+    #[default(5)]
+    pub timeout_secs: u64,
+
+    /// Deepest result reachable by paging, bounds the work of a single search
+    #[default(1000)]
+    pub max_results: u32,
+
+    /// Memory of the embedded indexer in bytes, at least 15 MB
+    #[default(50_000_000)]
+    pub writer_memory: usize,
+
+    /// Users read from the table per batch while building the embedded index
+    #[default(1000)]
+    pub rebuild_batch_size: u32,
+}
+
+impl SearchConf {
+    /// Builds the search index of the configured backend
+    ///
+    /// # Returns
+    /// - `Ok(SearchClient)` when the backend is known and its settings are valid
+    /// - `Err(anyhow::Error)` if the backend is unknown or misconfigured
+    pub fn build(&self, conn: &MySqlPool) -> anyhow::Result<SearchClient> {
+        let index: Arc<dyn SearchIndex> = match self.backend.as_str() {
+            "mysql" => Arc::new(MySqlSearchIndex::new(conn.clone())),
+            "embedded" => Arc::new(EmbeddedSearchIndex::new(self.writer_memory)?),
+            other => return Err(anyhow::anyhow!("unsupported search backend {}", other)),
+        };
+        Ok(SearchClient::new(index, self))
+    }
+}
+
+/// Searchable fields of a user
+#[derive(Debug, Clone)]
+pub struct UserDocument {
+    pub id: i64,
+    pub nick_name: String,
+    pub signature: String,
+}
+
+impl From<&UserInfo> for UserDocument {
+    fn from(user: &UserInfo) -> Self {
+        UserDocument {
+            id: user.id,
+            nick_name: user.nick_name.clone(),
+            signature: user.signature.clone(),
+        }
+    }
+}
+
+/// A user matching a search and its relevance
+///
+/// Hits are ranked by descending `score`, then by descending `id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub id: i64,
+    pub score: f64,
+}
+
+/// One page of the results of a search, most relevant first
+#[derive(Debug, Default)]
+pub struct SearchHits {
+    /// Number of matching users
+    pub total: u64,
+    pub hits: Vec<SearchHit>,
+}
+
+/// Full-text index of the nicknames and signatures of the live users
+///
+/// Both fields are split into the same terms (see `grams`), so Chinese text, which has no
+/// spaces between words, is found by any part of two characters or more.
+#[async_trait]
+pub trait SearchIndex: Send + Sync {
+    /// Whether the index follows `user_info` by itself, changes of users then need no update
+    fn follows_table(&self) -> bool;
+
+    /// Whether the index holds no user, for instance right after startup
+    async fn is_empty(&self) -> anyhow::Result<bool>;
+
+    /// Adds users to the index, replacing their previous fields
+    async fn upsert(&self, users: Vec<UserDocument>) -> anyhow::Result<()>;
+
+    /// Removes users from the index
+    async fn remove(&self, ids: Vec<i64>) -> anyhow::Result<()>;
+
+    /// Users matching `query`, most relevant first, skipping the first `offset`
+    async fn search(&self, query: &str, offset: u32, limit: u32) -> anyhow::Result<SearchHits>;
+
+    /// Users matching `query` ranked after the hit `after`, or from the first one without it
+    ///
+    /// The cost of a slice does not grow with its depth and users ranked before the position
+    /// do not shift the next slices. Relevance depends on the whole index though, so a change
+    /// of the index between two slices may still move some users across the position.
+    async fn search_after(
+        &self,
+        query: &str,
+        after: Option<SearchHit>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<SearchHit>>;
+}
+
+/// Search index shared by the whole application
+///
+/// Bounds every call by the configured timeout. Cloning is cheap.
+#[derive(Clone)]
+pub struct SearchClient {
+    index: Arc<dyn SearchIndex>,
+    timeout: Duration,
+    max_results: u32,
+    rebuild_batch_size: u32,
+}
+
+impl SearchClient {
+    pub fn new(index: Arc<dyn SearchIndex>, conf: &SearchConf) -> SearchClient {
+        SearchClient {
+            index,
+            timeout: Duration::from_secs(conf.timeout_secs),
+            max_results: conf.max_results,
+            rebuild_batch_size: conf.rebuild_batch_size.max(1),
+        }
+    }
+
+    /// Live users matching `query`, most relevant first
+    ///
+    /// Results past `max_results` are never returned and `total` counts at most `max_results`
+    /// users, so the last page of a search can always be reached.
+    ///
+    /// # Returns
+    /// * `Result<SearchHits, AppError>` - `ErrSearchTimeout` when the backend is too slow and
+    ///   `ErrSearchIndex` for any other failure
+    pub async fn search(
+        &self,
+        query: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<SearchHits, AppError> {
+        let window = limit.min(self.max_results.saturating_sub(offset));
+        // past the deepest result a single hit is asked for the total, then dropped
+        let offset = offset.min(self.max_results);
+        let mut hits = self
+            .bounded("search", self.index.search(query, offset, window.max(1)))
+            .await?;
+        hits.hits.truncate(window as usize);
+        hits.total = hits.total.min(self.max_results as u64);
+        Ok(hits)
+    }
+
+    /// Live users matching `query` ranked after the hit `after`, for keyset pagination
+    ///
+    /// # Returns
+    /// * `Result<Vec<SearchHit>, AppError>` - `ErrSearchTimeout` when the backend is too slow
+    ///   and `ErrSearchIndex` for any other failure
+    pub async fn search_after(
+        &self,
+        query: &str,
+        after: Option<SearchHit>,
+        limit: u32,
+    ) -> Result<Vec<SearchHit>, AppError> {
+        self.bounded("search", self.index.search_after(query, after, limit))
+            .await
+    }
+
+    /// Whether changes of users have to be reported with `sync`
+    pub fn follows_table(&self) -> bool {
+        self.index.follows_table()
+    }
+
+    /// Brings the index up to date with the users `ids` after they changed
+    ///
+    /// Live users are indexed again, deleted or erased ones removed. The change is already
+    /// stored, failures are logged and the index catches up at the next change or restart.
+    pub async fn sync(&self, conn: &MySqlPool, ids: &[i64]) {
+        if self.follows_table() || ids.is_empty() {
+            return;
+        }
+        if let Err(err) = self.try_sync(conn, ids).await {
+            error!("sync search index of users {:?} error {:?}", ids, err);
+        }
+    }
+
+    async fn try_sync(&self, conn: &MySqlPool, ids: &[i64]) -> Result<(), AppError> {
+        let users = repos::user::list_by_ids(conn, ids).await?;
+        let live: HashSet<i64> = users.iter().map(|user| user.id).collect();
+        let gone: Vec<i64> = ids
+            .iter()
+            .copied()
+            .filter(|id| !live.contains(id))
+            .collect();
+        if !users.is_empty() {
+            let documents = users.iter().map(UserDocument::from).collect();
+            self.bounded("upsert", self.index.upsert(documents)).await?;
+        }
+        if !gone.is_empty() {
+            self.bounded("remove", self.index.remove(gone)).await?;
+        }
+        Ok(())
+    }
+
+    /// Indexes every live user when the index is empty and does not follow the table
+    ///
+    /// # Returns
+    /// * `Result<u64, AppError>` - Number of indexed users
+    pub async fn rebuild(&self, conn: &MySqlPool) -> Result<u64, AppError> {
+        if self.follows_table() || !self.bounded("check", self.index.is_empty()).await? {
+            return Ok(0);
+        }
+        let filter = UserFilter::default();
+        let mut indexed = 0;
+        let mut after_id = 0;
+        loop {
+            let users =
+                repos::user::list_filtered_after(conn, &filter, after_id, self.rebuild_batch_size)
+                    .await?;
+            let Some(last) = users.last() else {
+                break;
+            };
+            after_id = last.id;
+            indexed += users.len() as u64;
+            let documents = users.iter().map(UserDocument::from).collect();
+            self.bounded("upsert", self.index.upsert(documents)).await?;
+        }
+        Ok(indexed)
+    }
+
+    async fn bounded<T>(
+        &self,
+        action: &str,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> Result<T, AppError> {
+        match tokio::time::timeout(self.timeout, call).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => {
+                error!("search index {} error {}", action, err);
+                Err(errors::ErrSearchIndex.clone())
+            }
+            Err(_) => {
+                error!("search index {} timeout after {:?}", action, self.timeout);
+                Err(errors::ErrSearchTimeout.clone())
+            }
+        }
+    }
+}
+
+/// Starts building the index from the table when it does not follow the table by itself
+///
+/// Searches answer from the users indexed so far while the task runs.
+pub fn spawn_rebuild(search: SearchClient, conn: MySqlPool) {
+    if search.follows_table() {
+        return;
+    }
+    tokio::spawn(async move {
+        match search.rebuild(&conn).await {
+            Ok(indexed) => info!("search index built with {} users", indexed),
+            Err(err) => error!("build search index error {:?}", err),
+        }
+    });
+}
+
+/// Term of the index found in a text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gram {
+    /// Lowercase text of the term
+    pub text: String,
+    /// Byte range of the term in the text
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Splits a text into the terms of the index, like the MySQL ngram parser
+///
+/// Words are runs of letters and digits. A word of one character is a term, a longer word
+/// gives every pair of consecutive characters: "小明同学" gives "小明", "明同" and "同学".
+pub fn grams(text: &str) -> Vec<Gram> {
+    let mut grams = Vec::new();
+    let mut word: Vec<(usize, usize)> = Vec::new();
+    let chars = text
+        .char_indices()
+        .map(|(offset, ch)| (offset, ch.len_utf8(), ch.is_alphanumeric()))
+        .chain(std::iter::once((text.len(), 0, false)));
+    for (offset, len, alphanumeric) in chars {
+        if alphanumeric {
+            word.push((offset, offset + len));
+            continue;
+        }
+        let spans: Vec<(usize, usize)> = match word.as_slice() {
+            [] => Vec::new(),
+            [single] => vec![*single],
+            pairs => pairs
+                .windows(2)
+                .map(|pair| (pair[0].0, pair[1].1))
+                .collect(),
+        };
+        grams.extend(spans.into_iter().map(|(from, to)| Gram {
+            text: text[from..to].to_lowercase(),
+            from,
+            to,
+        }));
+        word.clear();
+    }
+    grams
+}
+
+/// Text with the terms of `query` it contains wrapped in `<em>` tags, the rest HTML-escaped
+///
+/// # Returns
+/// * `Option<String>` - `None` when the text contains no term of the query
+pub fn highlight(text: &str, query: &str) -> Option<String> {
+    let terms: HashSet<String> = grams(query).into_iter().map(|gram| gram.text).collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for gram in grams(text)
+        .into_iter()
+        .filter(|gram| terms.contains(&gram.text))
+    {
+        match ranges.last_mut() {
+            Some(last) if gram.from <= last.1 => last.1 = last.1.max(gram.to),
+            _ => ranges.push((gram.from, gram.to)),
+        }
+    }
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let mut out = String::with_capacity(text.len() + ranges.len() * 9);
+    let mut position = 0;
+    for (from, to) in ranges {
+        escape_into(&mut out, &text[position..from]);
+        out.push_str("<em>");
+        escape_into(&mut out, &text[from..to]);
+        out.push_str("</em>");
+        position = to;
+    }
+    escape_into(&mut out, &text[position..]);
+    Some(out)
+}
+
+fn escape_into(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            ch => out.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(text: &str) -> Vec<String> {
+        grams(text).into_iter().map(|gram| gram.text).collect()
+    }
+
+    #[test]
+    fn test_grams() {
+        assert_eq!(texts("小明同学"), ["小明", "明同", "同学"]);
+        assert_eq!(texts("Bob, a CAT"), ["bo", "ob", "a", "ca", "at"]);
+        assert_eq!(texts(" -- "), Vec::<String>::new());
+        let gram = &grams("hi 世界")[1];
+        assert_eq!((gram.from, gram.to), (3, 9));
+    }
+
+    #[tokio::test]
+    async fn test_search_window() {
+        let conf = SearchConf {
+            backend: "embedded".to_string(),
+            max_results: 3,
+            writer_memory: 20_000_000,
+            ..Default::default()
+        };
+        let conn = MySqlPool::connect_lazy("mysql://root@127.0.0.1:1/test").unwrap();
+        let search = conf.build(&conn).unwrap();
+        assert!(!search.follows_table());
+        let users = (1..=5)
+            .map(|id| UserDocument {
+                id,
+                nick_name: format!("小明{}", id),
+                signature: String::new(),
+            })
+            .collect();
+        search.index.upsert(users).await.unwrap();
+
+        let hits = search.search("小明", 0, 2).await.unwrap();
+        assert_eq!((hits.total, hits.hits.len()), (3, 2));
+        let hits = search.search("小明", 2, 2).await.unwrap();
+        assert_eq!((hits.total, hits.hits.len()), (3, 1));
+        let hits = search.search("小明", 4, 2).await.unwrap();
+        assert_eq!((hits.total, hits.hits.len()), (3, 0));
+
+        // keyset slices are not bounded by the window
+        let first = search.search_after("小明", None, 3).await.unwrap();
+        let rest = search
+            .search_after("小明", first.last().cloned(), 3)
+            .await
+            .unwrap();
+        assert_eq!((first.len(), rest.len()), (3, 2));
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(highlight("我是小明同学", "明同学").as_deref(), Some("我是小<em>明同学</em>"));
+        assert_eq!(
+            highlight("Alice <3 rust & Go", "RUST go").as_deref(),
+            Some("Alice &lt;3 <em>rust</em> &amp; <em>Go</em>")
+        );
+        assert_eq!(highlight("alice", "bob"), None);
+        assert_eq!(highlight("", "bob"), None);
+    }
+}
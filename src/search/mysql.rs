@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use sqlx::MySqlPool;
+
+use crate::repos;
+use crate::search::SearchHit;
+use crate::search::SearchHits;
+use crate::search::SearchIndex;
+use crate::search::UserDocument;
+
+/// Backend searching the FULLTEXT index of `user_info`
+///
+/// The index is built with the ngram parser (`ngram_token_size = 2`), which splits text the
+/// same way as `grams` except that one-character words are not indexed. MySQL updates it with
+/// the rows, so there is nothing to synchronize.
+pub struct MySqlSearchIndex {
+    conn: MySqlPool,
+}
+
+impl MySqlSearchIndex {
+    pub fn new(conn: MySqlPool) -> MySqlSearchIndex {
+        MySqlSearchIndex { conn }
+    }
+}
+
+#[async_trait]
+impl SearchIndex for MySqlSearchIndex {
+    fn follows_table(&self) -> bool {
+        true
+    }
+
+    async fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn upsert(&self, _users: Vec<UserDocument>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn remove(&self, _ids: Vec<i64>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn search(&self, query: &str, offset: u32, limit: u32) -> anyhow::Result<SearchHits> {
+        let db_error = |err| anyhow::anyhow!("fulltext query error {:?}", err);
+        let total = repos::user::count_fulltext(&self.conn, query)
+            .await
+            .map_err(db_error)?;
+        if total == 0 {
+            return Ok(SearchHits::default());
+        }
+        let hits = repos::user::search_fulltext(&self.conn, query, offset, limit)
+            .await
+            .map_err(db_error)?;
+        Ok(SearchHits {
+            total: total as u64,
+            hits: hits
+                .into_iter()
+                .map(|(id, score)| SearchHit { id, score })
+                .collect(),
+        })
+    }
+
+    async fn search_after(
+        &self,
+        query: &str,
+        after: Option<SearchHit>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<SearchHit>> {
+        let after = after.map(|hit| (hit.score, hit.id));
+        let hits = repos::user::search_fulltext_after(&self.conn, query, after, limit)
+            .await
+            .map_err(|err| anyhow::anyhow!("fulltext query error {:?}", err))?;
+        Ok(hits
+            .into_iter()
+            .map(|(id, score)| SearchHit { id, score })
+            .collect())
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::Cursor;
+use std::io::Write;
+use std::time::Duration;
+
+use redis::Commands;
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use tracing::error;
+use tracing::info;
+use zip::CompressionMethod;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::rbac::invalidate_permissions;
+use crate::core::rest::AppError;
+use crate::core::session;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::ok;
+use crate::repos;
+use crate::services::token::revoke_user_sessions;
+use crate::services::user::wechat_session_key;
+use crate::types::account::DeleteAccountResponse;
+use crate::types::account::UserExport;
+
+/// Self-service account deletion configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct AccountConf {
+    /// Days between a deletion request and the erasure of the account
+    #[default(15)]
+    pub deletion_grace_days: i64,
+
+    /// Delay between two runs of the purge task, in seconds, 0 disables the task
+    #[default(3600)]
+    pub purge_interval_secs: u64,
+
+    /// Maximum number of accounts erased per run
+    #[default(100)]
+    pub purge_batch_size: u32,
+}
+
+/// Account deletion and personal data export service
+///
+/// A deletion request soft deletes the account and signs it out everywhere. Logging in again
+/// within the grace period cancels it, otherwise the purge task erases the row and its related
+/// records.
+pub struct AccountService;
+
+impl AccountService {
+    /// Schedules the deletion of the caller's account
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<DeleteAccountResponse>` - When the account will be erased
+    pub async fn delete_me(state: AppState, auth: AuthUser) -> Result<DeleteAccountResponse> {
+        let now = chrono::Utc::now().timestamp();
+        let purge_at = now + state.account.deletion_grace_days.max(0) * 86400;
+        repos::user::schedule_deletion(&state.get_conn(), auth.user_id, now, purge_at).await?;
+        state.search.sync(&state.get_conn(), &[auth.user_id]).await;
+        let revoked = revoke_user_sessions(
+            &mut state.get_redis_client()?,
+            auth.user_id,
+            state.jwt.access_ttl_secs(),
+        )?;
+        info!(
+            "user {} scheduled deletion at {}, {} sessions revoked",
+            auth.user_id, purge_at, revoked
+        );
+        ok!(DeleteAccountResponse { purge_at })
+    }
+
+    /// Collects everything stored about the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<UserExport, AppError>` - Profile, roles, API keys, linked identities, sessions and
+    ///   login history of the caller
+    pub async fn export(
+        state: AppState,
+        auth: AuthUser,
+    ) -> core::result::Result<UserExport, AppError> {
+        let conn = state.get_conn();
+        let user = repos::user::get_by_id(&conn, auth.user_id).await?;
+        let roles = repos::rbac::list_roles_by_user(&conn, auth.user_id).await?;
+        let api_keys = repos::api_key::list_by_user(&conn, auth.user_id).await?;
+        let identities = repos::identity::list_by_user(&conn, auth.user_id).await?;
+        let sessions = session::list(&mut state.get_redis_client()?, auth.user_id)?;
+        let logins = repos::login_event::list_all_by_user(&conn, auth.user_id).await?;
+        info!("user {} export personal data", auth.user_id);
+        Ok(UserExport {
+            exported_at: chrono::Utc::now().timestamp(),
+            user,
+            roles,
+            api_keys,
+            identities,
+            sessions,
+            logins,
+        })
+    }
+}
+
+/// Packs an export into a ZIP archive with one JSON file per kind of data
+pub fn export_zip(export: &UserExport) -> anyhow::Result<Vec<u8>> {
+    let files = [
+        ("user.json", serde_json::to_vec_pretty(&export.user)?),
+        ("roles.json", serde_json::to_vec_pretty(&export.roles)?),
+        ("api_keys.json", serde_json::to_vec_pretty(&export.api_keys)?),
+        ("identities.json", serde_json::to_vec_pretty(&export.identities)?),
+        ("sessions.json", serde_json::to_vec_pretty(&export.sessions)?),
+        ("logins.json", serde_json::to_vec_pretty(&export.logins)?),
+        ("export.json", serde_json::to_vec_pretty(&ExportInfo::from(export))?),
+    ];
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(&content)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Metadata written next to the data of a ZIP export
+#[derive(Serialize)]
+struct ExportInfo {
+    user_id: i64,
+    exported_at: i64,
+}
+
+impl From<&UserExport> for ExportInfo {
+    fn from(export: &UserExport) -> Self {
+        ExportInfo {
+            user_id: export.user.id,
+            exported_at: export.exported_at,
+        }
+    }
+}
+
+/// Starts the task erasing accounts whose deletion grace period is over
+pub fn spawn_purge(state: AppState) {
+    let interval = state.account.purge_interval_secs;
+    if interval == 0 {
+        info!("account purge task disabled");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            match purge_due(&state, chrono::Utc::now().timestamp()).await {
+                Ok(0) => {}
+                Ok(purged) => info!("purged {} deleted accounts", purged),
+                Err(err) => error!("purge deleted accounts error {:?}", err),
+            }
+        }
+    });
+}
+
+/// Erases one batch of accounts whose grace period ended before `now`
+///
+/// # Returns
+/// * `Result<usize, AppError>` - Number of erased accounts
+pub async fn purge_due(state: &AppState, now: i64) -> core::result::Result<usize, AppError> {
+    let conn = state.get_conn();
+    let ids = repos::user::list_due_purge(&conn, now, state.account.purge_batch_size).await?;
+    let mut purged = 0;
+    for id in ids {
+        if !repos::user::purge(&conn, id, now).await? {
+            continue;
+        }
+        state.search.sync(&conn, &[id]).await;
+        invalidate_permissions(state, id)?;
+        let _: () = state
+            .get_redis_client()?
+            .del(wechat_session_key(id))
+            .map_err(|err| {
+                error!("delete wechat session key of user {} error {}", id, err);
+                errors::ErrRedisClient.clone()
+            })?;
+        info!("purged user {}", id);
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::models::user::UserInfo;
+
+    #[test]
+    fn test_export_zip() {
+        let export = UserExport {
+            exported_at: 42,
+            user: UserInfo {
+                id: 7,
+                nick_name: "张三".to_string(),
+                password: "secret-hash".to_string(),
+                ..Default::default()
+            },
+            roles: vec![],
+            api_keys: vec![],
+            identities: vec![],
+            sessions: vec![],
+            logins: vec![],
+        };
+        let bytes = export_zip(&export).unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 7);
+        let mut user = String::new();
+        archive
+            .by_name("user.json")
+            .unwrap()
+            .read_to_string(&mut user)
+            .unwrap();
+        assert!(user.contains("张三"));
+        assert!(!user.contains("secret-hash"));
+    }
+}
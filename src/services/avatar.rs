@@ -0,0 +1,426 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use ::image::ImageFormat;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tracing::error;
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::image;
+use crate::core::image::DecodeLimits;
+use crate::core::image::ThumbnailFormat;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::models::user::ProfileField;
+use crate::ok;
+use crate::repos;
+use crate::types::avatar::AvatarVariant;
+use crate::types::avatar::UploadAvatarResponse;
+use crate::utils;
+use crate::utils::HashAlgorithm;
+
+/// Bytes kept from the start of an upload to recognize its type
+const SNIFF_LEN: usize = 16;
+
+/// Avatar upload configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct AvatarConf {
+    /// Largest accepted file in bytes
+    #[default(5 * 1024 * 1024)]
+    pub max_bytes: u64,
+
+    /// Accepted image types, recognized from the content of the file, never from the name or
+    /// the type announced by the client
+    #[default(vec![
+        "image/jpeg".to_string(),
+        "image/png".to_string(),
+        "image/webp".to_string(),
+        "image/gif".to_string(),
+    ])]
+    pub allowed_types: Vec<String>,
+
+    /// Directory receiving uploads while they are checked, the system temporary directory
+    /// when empty
+    pub upload_dir: String,
+
+    /// Largest accepted width or height in pixels
+    #[default(8192)]
+    pub max_dimension: u32,
+
+    /// Largest accepted number of pixels, refused from the image header before decoding
+    #[default(40_000_000)]
+    pub max_pixels: u64,
+
+    /// Sizes in pixels of the square thumbnails generated for every avatar
+    #[default(vec![64, 160, 320])]
+    pub thumbnail_sizes: Vec<u32>,
+
+    /// Format of the thumbnails, "webp" (lossless) or "jpeg"
+    #[default(ThumbnailFormat::Webp)]
+    pub thumbnail_format: ThumbnailFormat,
+
+    /// Quality of JPEG pictures, from 1 to 100
+    #[default(85)]
+    pub jpeg_quality: u8,
+}
+
+impl AvatarConf {
+    fn decode_limits(&self) -> DecodeLimits {
+        DecodeLimits {
+            max_dimension: self.max_dimension,
+            max_pixels: self.max_pixels,
+        }
+    }
+}
+
+/// Image formats accepted as avatars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageType {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+impl ImageType {
+    /// Recognizes an image from the magic bytes at the start of the file
+    pub fn sniff(head: &[u8]) -> Option<ImageType> {
+        match head {
+            [0xFF, 0xD8, 0xFF, ..] => Some(ImageType::Jpeg),
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(ImageType::Png),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageType::Gif),
+            [
+                b'R',
+                b'I',
+                b'F',
+                b'F',
+                _,
+                _,
+                _,
+                _,
+                b'W',
+                b'E',
+                b'B',
+                b'P',
+                ..,
+            ] => Some(ImageType::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Png => "image/png",
+            ImageType::Gif => "image/gif",
+            ImageType::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageType::Jpeg => "jpg",
+            ImageType::Png => "png",
+            ImageType::Gif => "gif",
+            ImageType::Webp => "webp",
+        }
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        match self {
+            ImageType::Jpeg => ImageFormat::Jpeg,
+            ImageType::Png => ImageFormat::Png,
+            ImageType::Gif => ImageFormat::Gif,
+            ImageType::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+/// Avatar upload service
+///
+/// Uploads are decoded and encoded again without their metadata, and the result is stored
+/// content-addressed under `avatars/{sha256}.{ext}` with its thumbnails next to it under
+/// `avatars/{sha256}_{size}.{ext}`. The digest of the upload itself is only recorded under
+/// `avatars/sources/{digest}`: the same file uploaded twice, by one user or many, is processed
+/// and stored once and shares its URL.
+pub struct AvatarService;
+
+impl AvatarService {
+    /// Stores an uploaded avatar and makes it the avatar of the caller
+    ///
+    /// The avatar is only ever written here, to URLs of the blob store. The upload replaces it
+    /// without `If-Match` and bumps the version of the profile.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing the blob store
+    /// * `auth` - Authenticated caller
+    /// * `file` - Content of the uploaded file
+    ///
+    /// # Returns
+    /// * `Result<UploadAvatarResponse>` - URLs and digest of the stored files, `ErrAvatarTooLarge`,
+    ///   `ErrAvatarType`, `ErrImageCorrupt` or `ErrImageDimensions` when the file is refused
+    pub async fn upload<R>(state: AppState, auth: AuthUser, file: R) -> Result<UploadAvatarResponse>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let conf = &state.avatar;
+        let upload = spool(file, conf).await?;
+        let image = ImageType::sniff(&upload.head)
+            .filter(|image| conf.allowed_types.iter().any(|mime| mime == image.mime()))
+            .ok_or_else(|| errors::ErrAvatarType.clone())?;
+
+        let path = upload.path.clone();
+        let digest =
+            tokio::task::spawn_blocking(move || utils::file_digest(&path, HashAlgorithm::SHA256))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|digest| digest.map_err(|err| err.to_string()))
+                .map_err(|err| {
+                    error!("digest avatar upload of user {} error {}", auth.user_id, err);
+                    errors::ErrBlobStore.clone()
+                })?;
+
+        // the upload is encoded again before it is stored, its digest only finds the picture
+        // stored from the same upload earlier
+        let source = format!("avatars/sources/{}", digest);
+        let (sha256, size) = match state.blobs.get(&source).await {
+            Ok(stored) => {
+                info!("user {} avatar {} already stored", auth.user_id, source);
+                parse_source(&stored).ok_or_else(|| {
+                    error!("invalid avatar source {}", source);
+                    errors::ErrBlobStore.clone()
+                })?
+            }
+            Err(err) if err.err_no() == errors::ErrBlobNotFound.err_no() => {
+                let path = upload.path.clone();
+                let pipeline = conf.clone();
+                let processed =
+                    tokio::task::spawn_blocking(move || process(&path, image, &pipeline))
+                        .await
+                        .map_err(|err| {
+                            error!("process avatar upload of user {} error {}", auth.user_id, err);
+                            errors::ErrImageEncode.clone()
+                        })??;
+                let sha256 = {
+                    let mut hasher = HashAlgorithm::SHA256.hasher();
+                    hasher.update(&processed.picture);
+                    hasher.finalize()
+                };
+                let size = processed.picture.len() as u64;
+                for (side, data) in conf.thumbnail_sizes.iter().zip(processed.thumbnails) {
+                    let key = variant_key(&sha256, *side, conf.thumbnail_format);
+                    let mime = conf.thumbnail_format.mime();
+                    state.blobs.put(&key, data, mime).await?;
+                }
+                let key = format!("avatars/{}.{}", sha256, image.extension());
+                state
+                    .blobs
+                    .put(&key, processed.picture, image.mime())
+                    .await?;
+                // the source goes last, its presence tells the picture and thumbnails are stored
+                let stored = format!("{} {}", sha256, size).into_bytes();
+                state.blobs.put(&source, stored, "text/plain").await?;
+                (sha256, size)
+            }
+            Err(err) => return Err(err),
+        };
+
+        let key = format!("avatars/{}.{}", sha256, image.extension());
+        let avatar = state.blobs.url(&key);
+        let conn = state.get_conn();
+        let user = repos::user::get_by_id(&conn, auth.user_id).await?;
+        let fields = [ProfileField::Avatar(avatar.clone())];
+        let now = chrono::Utc::now().timestamp();
+        if !repos::user::update_profile(&conn, user.id, user.version, &fields, now).await? {
+            return Err(errors::ErrVersionConflict.clone());
+        }
+        info!("user {} uploaded avatar {}", user.id, key);
+        ok!(UploadAvatarResponse {
+            avatar,
+            sha256: sha256.clone(),
+            size,
+            content_type: image.mime().to_string(),
+            variants: conf
+                .thumbnail_sizes
+                .iter()
+                .map(|size| AvatarVariant {
+                    size: *size,
+                    url: state
+                        .blobs
+                        .url(&variant_key(&sha256, *size, conf.thumbnail_format)),
+                })
+                .collect(),
+            version: user.version + 1,
+        })
+    }
+}
+
+/// Key of a thumbnail of the picture stored under `avatars/{sha256}.{ext}`
+fn variant_key(sha256: &str, size: u32, format: ThumbnailFormat) -> String {
+    format!("avatars/{}_{}.{}", sha256, size, format.extension())
+}
+
+/// Digest and size of the stored picture recorded for an upload, as `{sha256} {size}`
+fn parse_source(stored: &[u8]) -> Option<(String, u64)> {
+    let (sha256, size) = std::str::from_utf8(stored).ok()?.split_once(' ')?;
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((sha256.to_string(), size.parse().ok()?))
+}
+
+/// Files derived from an uploaded picture
+struct Processed {
+    /// The picture without metadata, in its original format
+    picture: Vec<u8>,
+    /// Thumbnails in the order of `thumbnail_sizes`
+    thumbnails: Vec<Vec<u8>>,
+}
+
+/// Decodes an uploaded picture and encodes it again along with its thumbnails
+///
+/// CPU bound, runs on the blocking pool.
+fn process(
+    path: &Path,
+    image: ImageType,
+    conf: &AvatarConf,
+) -> core::result::Result<Processed, AppError> {
+    let data = std::fs::read(path).map_err(|err| {
+        error!("read avatar upload {:?} error {}", path, err);
+        errors::ErrBlobStore.clone()
+    })?;
+    let decoded = image::decode(&data, image.format(), conf.decode_limits())?;
+    let picture = image::encode(&decoded, image.format(), conf.jpeg_quality)?;
+    let thumbnails = conf
+        .thumbnail_sizes
+        .iter()
+        .map(|size| {
+            let thumbnail = image::thumbnail(&decoded, *size);
+            image::encode(&thumbnail, conf.thumbnail_format.format(), conf.jpeg_quality)
+        })
+        .collect::<core::result::Result<Vec<_>, _>>()?;
+    Ok(Processed {
+        picture,
+        thumbnails,
+    })
+}
+
+/// An upload written to a temporary file, removed when dropped
+struct Spooled {
+    path: PathBuf,
+    size: u64,
+    head: Vec<u8>,
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            error!("remove avatar upload {:?} error {}", self.path, err);
+        }
+    }
+}
+
+/// Copies an upload into a temporary file, refusing it once it exceeds `max_bytes`
+async fn spool<R>(mut file: R, conf: &AvatarConf) -> core::result::Result<Spooled, AppError>
+where
+    R: AsyncRead + Unpin,
+{
+    let dir = match conf.upload_dir.as_str() {
+        "" => std::env::temp_dir(),
+        dir => Path::new(dir).to_path_buf(),
+    };
+    let path = dir.join(format!("avatar-{}.upload", utils::gen_token(8)));
+    let io_error = |err: std::io::Error| {
+        error!("spool avatar upload error {}", err);
+        errors::ErrBlobStore.clone()
+    };
+    let mut out = tokio::fs::File::create(&path).await.map_err(io_error)?;
+    // removes the file on every early return from now on
+    let mut spooled = Spooled {
+        path,
+        size: 0,
+        head: Vec::with_capacity(SNIFF_LEN),
+    };
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(|err| {
+            error!("read avatar upload error {}", err);
+            errors::ErrBadRequest.clone()
+        })?;
+        if read == 0 {
+            break;
+        }
+        spooled.size += read as u64;
+        if spooled.size > conf.max_bytes {
+            return Err(errors::ErrAvatarTooLarge.clone());
+        }
+        let missing = SNIFF_LEN.saturating_sub(spooled.head.len()).min(read);
+        spooled.head.extend_from_slice(&buffer[..missing]);
+        out.write_all(&buffer[..read]).await.map_err(io_error)?;
+    }
+    out.flush().await.map_err(io_error)?;
+    Ok(spooled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(ImageType::sniff(PNG), Some(ImageType::Png));
+        assert_eq!(ImageType::sniff(b"\xFF\xD8\xFF\xE0\0\x10JFIF"), Some(ImageType::Jpeg));
+        assert_eq!(ImageType::sniff(b"GIF89a\x01\0"), Some(ImageType::Gif));
+        assert_eq!(ImageType::sniff(b"RIFF\x24\0\0\0WEBPVP8 "), Some(ImageType::Webp));
+        assert_eq!(ImageType::sniff(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(ImageType::sniff(b"<svg xmlns="), None);
+        assert_eq!(ImageType::sniff(b""), None);
+    }
+
+    #[test]
+    fn test_parse_source() {
+        let sha256 = "ab".repeat(32);
+        let stored = format!("{} 1024", sha256);
+        assert_eq!(parse_source(stored.as_bytes()), Some((sha256.clone(), 1024)));
+        assert_eq!(parse_source(sha256.as_bytes()), None);
+        assert_eq!(parse_source(b"../etc 1024"), None);
+        assert_eq!(parse_source(format!("{} -1", sha256).as_bytes()), None);
+    }
+
+    #[tokio::test]
+    async fn test_spool() {
+        let conf = AvatarConf::default();
+        let upload = spool(PNG, &conf).await.unwrap();
+        let path = upload.path.clone();
+        assert_eq!(upload.size, PNG.len() as u64);
+        assert_eq!(upload.head, &PNG[..SNIFF_LEN]);
+        assert_eq!(utils::file_digest(&path, HashAlgorithm::SHA256).unwrap(), {
+            let mut hasher = HashAlgorithm::SHA256.hasher();
+            hasher.update(PNG);
+            hasher.finalize()
+        });
+        drop(upload);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_spool_too_large() {
+        let conf = AvatarConf {
+            max_bytes: 8,
+            ..Default::default()
+        };
+        let err = spool(PNG, &conf).await.err().unwrap();
+        assert_eq!(err.err_no(), errors::ErrAvatarTooLarge.err_no());
+    }
+}
@@ -0,0 +1,661 @@
+use std::collections::HashSet;
+use std::io;
+
+use csv_async::AsyncReaderBuilder;
+use csv_async::AsyncWriterBuilder;
+use csv_async::ErrorKind;
+use csv_async::Trim;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use futures_util::stream;
+use futures_util::stream::BoxStream;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use sqlx::MySqlPool;
+use tokio::io::AsyncRead;
+use tokio_util::codec::FramedRead;
+use tokio_util::codec::LinesCodec;
+use tokio_util::codec::LinesCodecError;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+use validator::Validate;
+
+use crate::core::password;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::models::user::UserInfo;
+use crate::ok;
+use crate::repos;
+use crate::repos::Scope;
+use crate::repos::user::UserFilter;
+use crate::search::SearchClient;
+use crate::sms::phone::normalize_phone;
+use crate::types::bulk::BulkFormat;
+use crate::types::bulk::ExportUsersRequest;
+use crate::types::bulk::ImportReport;
+use crate::types::bulk::ImportUsersRequest;
+use crate::types::bulk::RowError;
+use crate::types::bulk::UserImportRow;
+use crate::types::bulk::UserRecord;
+
+/// Longest accepted NDJSON line in bytes
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Bulk user import and export configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct BulkConf {
+    /// Users inserted by one multi-row INSERT
+    #[default(500)]
+    pub import_batch_size: usize,
+
+    /// Rows read by one import through the admin API, the CLI reads whole files
+    #[default(100_000)]
+    pub max_import_rows: u64,
+
+    /// Row errors kept in an import report, the others are only counted
+    #[default(1000)]
+    pub max_reported_errors: usize,
+
+    /// Users read by one query of an export
+    #[default(1000)]
+    pub export_batch_size: u32,
+}
+
+/// Bulk user transfer service of the admin API, the `users` CLI commands use `UserImporter` and
+/// `export_users` directly
+pub struct BulkService;
+
+impl BulkService {
+    /// Imports the users of a request body
+    ///
+    /// At most `max_import_rows` rows are read, bigger migrations go through the CLI.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - Format of the body and whether it is a dry run
+    /// * `body` - CSV or NDJSON request body
+    ///
+    /// # Returns
+    /// * `Result<ImportReport>` - Rows imported and rejected
+    pub async fn import_users<R>(
+        state: AppState,
+        req: ImportUsersRequest,
+        body: R,
+    ) -> crate::core::Result<ImportReport>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let conn = state.get_conn();
+        let report = UserImporter::new(&conn, &state.bulk, state.sms.default_country_code())
+            .dry_run(req.dry_run)
+            .max_rows(state.bulk.max_import_rows)
+            .search(&state.search)
+            .run(body, req.format)
+            .await?;
+        ok!(report)
+    }
+
+    /// Streams the users matching an export request
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - Format and filter of the export
+    ///
+    /// # Returns
+    /// * `impl Stream<Item = Result<Vec<u8>, AppError>>` - Encoded chunks of the export
+    pub fn export_users(
+        state: AppState,
+        req: ExportUsersRequest,
+    ) -> impl Stream<Item = Result<Vec<u8>, AppError>> + Send + 'static {
+        export_users(state.get_conn(), &state.bulk, req.format, export_filter(req))
+    }
+}
+
+/// Filter of the users an export request selects
+pub fn export_filter(req: ExportUsersRequest) -> UserFilter {
+    UserFilter {
+        nickname: req.q.filter(|q| !q.trim().is_empty()),
+        created_from: req.created_from,
+        created_to: req.created_to,
+        scope: if req.include_deleted {
+            Scope::WithDeleted
+        } else {
+            Scope::Active
+        },
+    }
+}
+
+/// Imports users from a CSV or NDJSON stream
+///
+/// Rows are validated one by one, a rejected row is reported with its line and never stops the
+/// import. Valid rows are buffered into batches of `import_batch_size` users, checked against the
+/// email, phone and WeChat Open ID already in use, soft-deleted accounts included, and inserted
+/// by a single multi-row INSERT. Only one batch is held in memory whatever the size of the input.
+pub struct UserImporter<'a> {
+    conn: &'a MySqlPool,
+    conf: &'a BulkConf,
+    country_code: &'a str,
+    dry_run: bool,
+    max_rows: Option<u64>,
+    search: Option<&'a SearchClient>,
+}
+
+impl<'a> UserImporter<'a> {
+    /// Creates an importer
+    ///
+    /// # Arguments
+    /// * `conn` - Database connection pool
+    /// * `conf` - Bulk configuration
+    /// * `country_code` - Country code of the phone numbers written without one
+    pub fn new(conn: &'a MySqlPool, conf: &'a BulkConf, country_code: &'a str) -> Self {
+        UserImporter {
+            conn,
+            conf,
+            country_code,
+            dry_run: false,
+            max_rows: None,
+            search: None,
+        }
+    }
+
+    /// Only validates the rows, nothing is inserted
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Stops after `max_rows` rows and flags the report as truncated
+    pub fn max_rows(mut self, max_rows: u64) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    /// Adds the imported users to `search` when the index does not follow the table
+    pub fn search(mut self, search: &'a SearchClient) -> Self {
+        self.search = Some(search);
+        self
+    }
+
+    /// Reads the whole input and imports its users
+    ///
+    /// # Arguments
+    /// * `reader` - CSV with a header line or NDJSON input
+    /// * `format` - Format of the input
+    ///
+    /// # Returns
+    /// * `Result<ImportReport, AppError>` - Rows imported and rejected, `ErrImportMalformed` when
+    ///   the input cannot be read at all. Batches inserted before such an error are kept.
+    pub async fn run<R>(self, reader: R, format: BulkFormat) -> Result<ImportReport, AppError>
+    where
+        R: AsyncRead + Unpin + Send + 'a,
+    {
+        let mut rows = read_rows(reader, format);
+        let mut report = ImportReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        let mut batch = Vec::with_capacity(self.conf.import_batch_size);
+        let mut seen = Identifiers::default();
+        let now = chrono::Utc::now().timestamp();
+
+        while let Some(row) = rows.next().await {
+            let (line, row) = row?;
+            if self.max_rows.is_some_and(|max| report.total >= max) {
+                report.truncated = true;
+                break;
+            }
+            report.total += 1;
+            match row.and_then(|row| self.to_user(row, now)) {
+                Ok(user) => batch.push((line, user)),
+                Err(message) => self.reject(&mut report, line, message),
+            }
+            if batch.len() >= self.conf.import_batch_size {
+                self.flush(&mut batch, &mut seen, &mut report).await?;
+            }
+        }
+        self.flush(&mut batch, &mut seen, &mut report).await?;
+
+        info!(
+            "import users total {} imported {} failed {} dry run {}",
+            report.total, report.imported, report.failed, report.dry_run
+        );
+        Ok(report)
+    }
+
+    /// Validates a row and builds the user to insert
+    fn to_user(&self, row: UserImportRow, now: i64) -> Result<UserInfo, String> {
+        row.validate().map_err(|err| err.to_string())?;
+        let phone = match non_empty(row.phone) {
+            Some(phone) => normalize_phone(&phone, self.country_code)
+                .map_err(|_| format!("invalid phone number {}", phone))?,
+            None => String::new(),
+        };
+        let email = non_empty(row.email).map(|email| email.to_lowercase());
+        let wx_open_id = non_empty(row.wx_open_id).unwrap_or_default();
+        if phone.is_empty() && email.is_none() && wx_open_id.is_empty() {
+            return Err("one of email, phone or wx_open_id is required".to_string());
+        }
+        let password = non_empty(row.password_hash).unwrap_or_default();
+        if !password.is_empty() && !password::is_known_hash(&password) {
+            return Err(
+                "password_hash is neither an Argon2 PHC string nor a hex digest".to_string()
+            );
+        }
+        let created_at = row.created_at.unwrap_or(now);
+        Ok(UserInfo {
+            nick_name: row.nick_name,
+            avatar: row.avatar.unwrap_or_default(),
+            signature: row.signature.unwrap_or_default(),
+            age: row.age.unwrap_or_default(),
+            phone,
+            email,
+            wx_open_id,
+            salt: row.salt.unwrap_or_default(),
+            password,
+            created_at,
+            updated_at: now,
+            ..Default::default()
+        })
+    }
+
+    /// Inserts the valid rows of a batch whose identifiers are still free
+    async fn flush(
+        &self,
+        batch: &mut Vec<(u64, UserInfo)>,
+        seen: &mut Identifiers,
+        report: &mut ImportReport,
+    ) -> Result<(), AppError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        // inserted batches are found in the database again, a dry run has to remember them
+        if !self.dry_run {
+            *seen = Identifiers::default();
+        }
+        let (emails, phones, wx_open_ids) = Identifiers::of(batch.iter().map(|(_, user)| user));
+        for user in
+            repos::user::list_by_identifiers(self.conn, &emails, &phones, &wx_open_ids).await?
+        {
+            seen.insert(&user);
+        }
+
+        let mut users = Vec::with_capacity(batch.len());
+        let mut lines = Vec::with_capacity(batch.len());
+        for (line, user) in batch.drain(..) {
+            if let Some(message) = seen.conflict(&user) {
+                self.reject(report, line, message);
+                continue;
+            }
+            seen.insert(&user);
+            users.push(user);
+            lines.push(line);
+        }
+        if self.dry_run || users.is_empty() {
+            return Ok(());
+        }
+
+        match repos::user::create_many(self.conn, &users).await {
+            Ok(inserted) => {
+                report.imported += inserted;
+                self.index(&users).await?;
+            }
+            Err(err) if err.err_no() == errors::ErrDbDataConflict.err_no() => {
+                // an account registered meanwhile, retry row by row to single it out
+                warn!("import batch conflict, insert {} users one by one", users.len());
+                let mut created = Vec::with_capacity(users.len());
+                for (line, mut user) in lines.into_iter().zip(users) {
+                    match repos::user::create(self.conn, &mut user).await {
+                        Ok(()) => {
+                            report.imported += 1;
+                            created.push(user.id);
+                        }
+                        Err(err) if err.err_no() == errors::ErrDbDataConflict.err_no() => {
+                            self.reject(report, line, "email already in use".to_string())
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                if let Some(search) = self.search {
+                    search.sync(self.conn, &created).await;
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// Reports users inserted by `create_many`, which gives no IDs, to the search index
+    ///
+    /// The users are found again by their login identifiers, one of which every row has.
+    async fn index(&self, users: &[UserInfo]) -> Result<(), AppError> {
+        let Some(search) = self.search.filter(|search| !search.follows_table()) else {
+            return Ok(());
+        };
+        let (emails, phones, wx_open_ids) = Identifiers::of(users.iter());
+        let ids: Vec<i64> =
+            repos::user::list_by_identifiers(self.conn, &emails, &phones, &wx_open_ids)
+                .await?
+                .iter()
+                .map(|user| user.id)
+                .collect();
+        search.sync(self.conn, &ids).await;
+        Ok(())
+    }
+
+    /// Counts a rejected row and keeps its error while the report has room for it
+    fn reject(&self, report: &mut ImportReport, line: u64, message: String) {
+        report.failed += 1;
+        if report.errors.len() < self.conf.max_reported_errors {
+            report.errors.push(RowError { line, message });
+        }
+    }
+}
+
+/// Login identifiers already taken
+#[derive(Debug, Default)]
+struct Identifiers {
+    emails: HashSet<String>,
+    phones: HashSet<String>,
+    wx_open_ids: HashSet<String>,
+}
+
+impl Identifiers {
+    /// Non-empty identifiers of users, for the database lookup
+    fn of<'u>(
+        users: impl Iterator<Item = &'u UserInfo>,
+    ) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let (mut emails, mut phones, mut wx_open_ids) = (Vec::new(), Vec::new(), Vec::new());
+        for user in users {
+            if let Some(email) = &user.email {
+                emails.push(email.clone());
+            }
+            if !user.phone.is_empty() {
+                phones.push(user.phone.clone());
+            }
+            if !user.wx_open_id.is_empty() {
+                wx_open_ids.push(user.wx_open_id.clone());
+            }
+        }
+        (emails, phones, wx_open_ids)
+    }
+
+    fn insert(&mut self, user: &UserInfo) {
+        if let Some(email) = &user.email {
+            self.emails.insert(email.to_lowercase());
+        }
+        if !user.phone.is_empty() {
+            self.phones.insert(user.phone.clone());
+        }
+        if !user.wx_open_id.is_empty() {
+            self.wx_open_ids.insert(user.wx_open_id.clone());
+        }
+    }
+
+    /// Why the user cannot be imported, `None` when its identifiers are free
+    fn conflict(&self, user: &UserInfo) -> Option<String> {
+        if let Some(email) = user
+            .email
+            .as_ref()
+            .filter(|email| self.emails.contains(*email))
+        {
+            return Some(format!("email {} already in use", email));
+        }
+        if self.phones.contains(&user.phone) {
+            return Some(format!("phone {} already in use", user.phone));
+        }
+        if self.wx_open_ids.contains(&user.wx_open_id) {
+            return Some(format!("wx_open_id {} already in use", user.wx_open_id));
+        }
+        None
+    }
+}
+
+/// A row of the input: its line and the parsed row or why it cannot be parsed
+type ImportRow = (u64, Result<UserImportRow, String>);
+
+/// Parses the input row by row, an `Err` item means the rest of the input cannot be read
+fn read_rows<'a, R>(reader: R, format: BulkFormat) -> BoxStream<'a, Result<ImportRow, AppError>>
+where
+    R: AsyncRead + Unpin + Send + 'a,
+{
+    match format {
+        BulkFormat::Csv => AsyncReaderBuilder::new()
+            .trim(Trim::All)
+            .create_deserializer(reader)
+            .into_deserialize_with_pos::<UserImportRow>()
+            .map(|(row, pos)| match row {
+                Ok(row) => Ok((pos.line(), Ok(row))),
+                Err(err) => match err.kind() {
+                    ErrorKind::Io(_) => {
+                        warn!("read csv import error {}", err);
+                        Err(errors::ErrImportMalformed.clone())
+                    }
+                    _ => {
+                        let line = err.position().map_or(pos.line(), |pos| pos.line());
+                        Ok((line, Err(err.to_string())))
+                    }
+                },
+            })
+            .boxed(),
+        BulkFormat::Ndjson => {
+            FramedRead::new(reader, LinesCodec::new_with_max_length(MAX_LINE_LEN))
+                .enumerate()
+                .filter_map(|(index, line)| async move {
+                    let line_no = index as u64 + 1;
+                    match line {
+                        Ok(line) if line.trim().is_empty() => None,
+                        Ok(line) => Some(Ok((
+                            line_no,
+                            serde_json::from_str(&line).map_err(|err| err.to_string()),
+                        ))),
+                        Err(LinesCodecError::MaxLineLengthExceeded) => Some(Ok((
+                            line_no,
+                            Err(format!("line longer than {} bytes", MAX_LINE_LEN)),
+                        ))),
+                        Err(LinesCodecError::Io(err)) => {
+                            warn!("read ndjson import error {}", err);
+                            Some(Err(errors::ErrImportMalformed.clone()))
+                        }
+                    }
+                })
+                .boxed()
+        }
+    }
+}
+
+/// Streams the users matching `filter` as CSV or NDJSON
+///
+/// Users are read `export_batch_size` at a time in id order, each batch starting after the last
+/// user of the previous one, and encoded into one chunk: memory stays bounded by a batch however
+/// many users are exported. A CSV export starts with its header line even when empty.
+///
+/// # Returns
+/// * `impl Stream<Item = Result<Vec<u8>, AppError>>` - Encoded chunks, an `Err` ends the export
+pub fn export_users(
+    conn: MySqlPool,
+    conf: &BulkConf,
+    format: BulkFormat,
+    filter: UserFilter,
+) -> impl Stream<Item = Result<Vec<u8>, AppError>> + Send + use<> {
+    let batch_size = conf.export_batch_size.max(1);
+    // position after which the next batch starts, `None` once the last batch is sent
+    stream::try_unfold(Some(0i64), move |after_id| {
+        let conn = conn.clone();
+        let filter = filter.clone();
+        async move {
+            let Some(after_id) = after_id else {
+                return Ok(None);
+            };
+            let users =
+                repos::user::list_filtered_after(&conn, &filter, after_id, batch_size).await?;
+            let next = match users.last() {
+                Some(last) if users.len() == batch_size as usize => Some(last.id),
+                _ => None,
+            };
+            let records = users.into_iter().map(UserRecord::from);
+            let chunk = encode(format, records, after_id == 0)
+                .await
+                .map_err(|err| {
+                    error!("encode user export error {}", err);
+                    errors::ErrExportEncode.clone()
+                })?;
+            Ok(Some((chunk, next)))
+        }
+    })
+}
+
+/// Encodes a batch of exported users, with the CSV header line for the first one
+async fn encode(
+    format: BulkFormat,
+    records: impl Iterator<Item = UserRecord>,
+    first: bool,
+) -> io::Result<Vec<u8>> {
+    match format {
+        BulkFormat::Csv => {
+            let mut out = Vec::new();
+            if first {
+                out.extend_from_slice(UserRecord::COLUMNS.join(",").as_bytes());
+                out.push(b'\n');
+            }
+            let mut writer = AsyncWriterBuilder::new()
+                .has_headers(false)
+                .create_serializer(out);
+            for record in records {
+                writer.serialize(record).await.map_err(io::Error::other)?;
+            }
+            writer.into_inner().await.map_err(io::Error::other)
+        }
+        BulkFormat::Ndjson => {
+            let mut out = Vec::new();
+            for record in records {
+                serde_json::to_writer(&mut out, &record)?;
+                out.push(b'\n');
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// Keeps a field which is not blank
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+
+    async fn rows(input: &str, format: BulkFormat) -> Vec<ImportRow> {
+        read_rows(input.as_bytes(), format)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_read_csv_rows() {
+        let input = "nick_name,email,age\nli, li@example.com ,30\nwang,,abc\n";
+        let rows = rows(input, BulkFormat::Csv).await;
+        assert_eq!(rows.len(), 2);
+        let (line, row) = &rows[0];
+        assert_eq!(*line, 2);
+        let row = row.as_ref().unwrap();
+        assert_eq!(row.email.as_deref(), Some("li@example.com"));
+        assert_eq!(row.age, Some(30));
+        // a bad value rejects its row only
+        assert_eq!(rows[1].0, 3);
+        assert!(rows[1].1.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_ndjson_rows() {
+        let input = "{\"nick_name\":\"li\",\"phone\":\"13800138000\"}\n\n{\"nick_name\":1}\n";
+        let rows = rows(input, BulkFormat::Ndjson).await;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 1);
+        assert!(rows[0].1.is_ok());
+        assert_eq!(rows[1].0, 3);
+        assert!(rows[1].1.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_to_user() {
+        let conn = MySqlPool::connect_lazy("mysql://root@127.0.0.1:1/test").unwrap();
+        let conf = BulkConf::default();
+        let importer = UserImporter::new(&conn, &conf, "86");
+        let row = UserImportRow {
+            nick_name: "li".to_string(),
+            phone: Some("138 0013 8000".to_string()),
+            email: Some("Li@Example.com".to_string()),
+            ..Default::default()
+        };
+        let user = importer.to_user(row, 1_700_000_000).unwrap();
+        assert_eq!(user.phone, "+8613800138000");
+        assert_eq!(user.email.as_deref(), Some("li@example.com"));
+        assert_eq!(user.created_at, 1_700_000_000);
+
+        let no_login = UserImportRow {
+            nick_name: "li".to_string(),
+            ..Default::default()
+        };
+        assert!(importer.to_user(no_login, 0).is_err());
+        let plain_password = UserImportRow {
+            nick_name: "li".to_string(),
+            wx_open_id: Some("o_li".to_string()),
+            password_hash: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert!(importer.to_user(plain_password, 0).is_err());
+    }
+
+    #[test]
+    fn test_identifier_conflict() {
+        let mut seen = Identifiers::default();
+        let user = UserInfo {
+            email: Some("li@example.com".to_string()),
+            phone: "+8613800138000".to_string(),
+            ..Default::default()
+        };
+        assert!(seen.conflict(&user).is_none());
+        seen.insert(&user);
+        assert!(seen.conflict(&user).unwrap().contains("email"));
+        // empty identifiers never collide
+        let other = UserInfo {
+            wx_open_id: "o_wang".to_string(),
+            ..Default::default()
+        };
+        assert!(seen.conflict(&other).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_encode_csv() {
+        let record = UserRecord::from(UserInfo {
+            id: 7,
+            nick_name: "li, jr".to_string(),
+            ..Default::default()
+        });
+        let first = encode(BulkFormat::Csv, std::iter::once(record.clone()), true)
+            .await
+            .unwrap();
+        let text = String::from_utf8(first).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), UserRecord::COLUMNS.join(","));
+        assert!(lines.next().unwrap().starts_with("7,\"li, jr\","));
+
+        let empty = encode(BulkFormat::Csv, std::iter::empty(), true)
+            .await
+            .unwrap();
+        assert_eq!(empty, format!("{}\n", UserRecord::COLUMNS.join(",")).into_bytes());
+        let next = encode(BulkFormat::Ndjson, std::iter::once(record), false)
+            .await
+            .unwrap();
+        assert!(next.starts_with(b"{\"id\":7,"));
+    }
+}
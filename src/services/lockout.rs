@@ -0,0 +1,47 @@
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::lockout::Subject;
+use crate::core::state::AppState;
+use crate::ok;
+use crate::repos;
+use crate::types::lockout::UnlockRequest;
+use crate::types::lockout::UnlockResponse;
+
+/// Administration of the brute-force lockouts
+pub struct LockoutService;
+
+impl LockoutService {
+    /// Lifts the delays and lockouts of a user, under its id, email and phone number
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `user_id` - Id of the user to unlock
+    /// * `req` - Client IP to unlock as well
+    ///
+    /// # Returns
+    /// * `Result<UnlockResponse>` - Whether something was locked, `ErrDbRowNotFound` for an unknown
+    ///   user
+    pub async fn unlock(
+        state: AppState,
+        user_id: i64,
+        req: UnlockRequest,
+    ) -> Result<UnlockResponse> {
+        let user = repos::user::get_by_id(&state.get_conn(), user_id).await?;
+        let mut subjects = vec![Subject::User(user.id)];
+        if let Some(email) = &user.email {
+            subjects.push(Subject::Email(email));
+        }
+        if !user.phone.is_empty() {
+            subjects.push(Subject::Phone(&user.phone));
+        }
+        if let Some(ip) = &req.ip {
+            subjects.push(Subject::Ip(ip));
+        }
+        let unlocked = state
+            .lockouts
+            .unlock(&mut state.get_redis_client()?, &subjects)?;
+        info!("user {} unlocked ({}), ip {:?}", user.id, unlocked, req.ip);
+        ok!(UnlockResponse { unlocked })
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::page::Page;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::models::login_event::LoginEvent;
+use crate::ok;
+use crate::repos;
+use crate::types::login_event::LoginEventListRequest;
+use crate::types::login_event::LoginEventListResponse;
+
+/// Login audit configuration
+#[derive(Debug, Deserialize, SmartDefault, Clone)]
+#[serde(default)]
+pub struct LoginEventConf {
+    /// Mail the user when a login comes from a device never used before
+    #[default(true)]
+    pub notify_new_device: bool,
+
+    /// Mail the user when a login comes from an IP never used before
+    #[default(false)]
+    pub notify_new_ip: bool,
+}
+
+/// Login history service
+///
+/// Every login path records its attempts through `record`, the caller's history is listed by
+/// `/user/me/logins`.
+pub struct LoginEventService;
+
+impl LoginEventService {
+    /// Lists the login attempts of the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `req` - Page to list
+    ///
+    /// # Returns
+    /// * `Result<LoginEventListResponse>` - One page of login attempts, most recent first
+    pub async fn list(
+        state: AppState,
+        auth: AuthUser,
+        req: LoginEventListRequest,
+    ) -> Result<LoginEventListResponse> {
+        let conn = state.get_conn();
+        let list = repos::login_event::list_by_user(
+            &conn,
+            auth.user_id,
+            req.page_no as u32,
+            req.page_size as u32,
+        )
+        .await?;
+        let total = repos::login_event::count_by_user(&conn, auth.user_id).await?;
+        ok!(Page::new(list, total, req.page_no, req.page_size))
+    }
+}
+
+/// Writes a login event in the background so the login does not wait for it
+pub(crate) fn record(state: &AppState, event: LoginEvent) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let user_id = event.user_id;
+        if let Err(err) = save(&state, event).await {
+            error!("record login event of user {} error {:?}", user_id, err);
+        }
+    });
+}
+
+/// Flags a successful login from a new IP or device, stores the event and warns the user
+async fn save(state: &AppState, mut event: LoginEvent) -> core::result::Result<(), AppError> {
+    let conn = state.get_conn();
+    if event.success && event.user_id > 0 {
+        let (total, from_ip, on_device) =
+            repos::login_event::count_success(&conn, event.user_id, &event.ip, &event.device)
+                .await?;
+        // the first login of an account has nothing to be compared with
+        event.new_ip = total > 0 && from_ip == 0;
+        event.new_device = total > 0 && on_device == 0;
+    }
+    repos::login_event::create(&conn, &mut event).await?;
+
+    if event.new_ip || event.new_device {
+        info!(
+            "user {} login from new ip {} ({}) or device {} ({})",
+            event.user_id, event.ip, event.new_ip, event.device, event.new_device
+        );
+        on_new_client(state, &event).await?;
+    }
+    Ok(())
+}
+
+/// Hook run after a login from a new IP or device, mails the user when configured to
+async fn on_new_client(state: &AppState, event: &LoginEvent) -> core::result::Result<(), AppError> {
+    let conf = &state.login_event;
+    if !(event.new_device && conf.notify_new_device || event.new_ip && conf.notify_new_ip) {
+        return Ok(());
+    }
+    let user = repos::user::get_by_id(&state.get_conn(), event.user_id).await?;
+    let Some(email) = user.email else {
+        warn!("user {} login from new device without email to notify", event.user_id);
+        return Ok(());
+    };
+    let at = chrono::DateTime::from_timestamp(event.created_at, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M:%S UTC");
+    let body = format!(
+        "Your account was signed in to from a new device or location.\n\nTime: {}\nDevice: {}\nIP address: {}\n\nIf this was not you, reset your password and sign out the devices you do not recognize.",
+        at, event.device, event.ip
+    );
+    // failures are logged by the mail client
+    let _ = state
+        .mail
+        .send_text(&email, "New sign-in to your account", &body)
+        .await;
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use tracing::info;
+use tracing::warn;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::rest::AppError;
+use crate::core::session::ClientMeta;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::models::identity::UserIdentity;
+use crate::models::login_event::LoginEvent;
+use crate::models::login_event::LoginMethod;
+use crate::models::user::UserInfo;
+use crate::oidc::OidcIdentity;
+use crate::oidc::PendingLogin;
+use crate::ok;
+use crate::repos;
+use crate::services::login_event;
+use crate::services::user::REGISTERED;
+use crate::services::user::finish_login;
+use crate::types::oidc::IdentityListResponse;
+use crate::types::oidc::OidcAuthorizeResponse;
+use crate::types::oidc::OidcCallbackRequest;
+use crate::types::oidc::OidcCallbackResponse;
+use crate::types::oidc::OidcProvidersResponse;
+use crate::types::oidc::UnlinkIdentityResponse;
+use crate::utils;
+
+/// Names reported by providers are truncated to this number of characters
+const MAX_NAME_LEN: usize = 64;
+
+/// OpenID Connect and OAuth2 provider login service
+///
+/// `authorize` (or `link` for a logged-in user) returns the provider URL, the frontend sends the
+/// `code` and `state` received by its redirect URI to `callback`. The first login through a
+/// provider registers a new account, or joins the account with the same verified email when the
+/// provider allows it.
+pub struct OidcService;
+
+impl OidcService {
+    /// Lists the configured providers
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing the providers
+    ///
+    /// # Returns
+    /// * `Result<OidcProvidersResponse>` - Names of the providers
+    pub async fn providers(state: AppState) -> Result<OidcProvidersResponse> {
+        ok!(OidcProvidersResponse {
+            providers: state.oidc.provider_names(),
+        })
+    }
+
+    /// Starts a login through a provider
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing the providers
+    /// * `provider` - Name of the provider
+    ///
+    /// # Returns
+    /// * `Result<OidcAuthorizeResponse>` - URL of the provider login page
+    pub async fn authorize(state: AppState, provider: &str) -> Result<OidcAuthorizeResponse> {
+        let resp = begin(&state, provider, 0).await?;
+        ok!(resp)
+    }
+
+    /// Starts linking a provider account to the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing the providers
+    /// * `auth` - Authenticated caller
+    /// * `provider` - Name of the provider
+    ///
+    /// # Returns
+    /// * `Result<OidcAuthorizeResponse>` - URL of the provider login page
+    pub async fn link(
+        state: AppState,
+        auth: AuthUser,
+        provider: &str,
+    ) -> Result<OidcAuthorizeResponse> {
+        let resp = begin(&state, provider, auth.user_id).await?;
+        ok!(resp)
+    }
+
+    /// Finishes a login or link with the code received by the redirect URI
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `client` - Device the user logs in from, recorded with the session
+    /// * `provider` - Name of the provider
+    /// * `req` - Code and state received by the redirect URI
+    ///
+    /// # Returns
+    /// * `Result<OidcCallbackResponse>` - Tokens or two-factor challenge of a login, and the
+    ///   identity; `ErrOidcIdentityLinked` when linking an identity of another user
+    pub async fn callback(
+        state: AppState,
+        client: ClientMeta,
+        provider: &str,
+        req: OidcCallbackRequest,
+    ) -> Result<OidcCallbackResponse> {
+        let failed = |user_id, reason| {
+            let event = LoginEvent::new(user_id, LoginMethod::Oidc, &client, false, reason);
+            login_event::record(&state, event);
+        };
+        let pending = state
+            .oidc
+            .take_pending(&mut state.get_redis_client()?, &req.state)
+            .inspect_err(|_| failed(0, "invalid_state"))?;
+        if pending.provider != provider {
+            warn!("oidc state of {} used with {}", pending.provider, provider);
+            failed(pending.link_user_id, "invalid_state");
+            return Err(errors::ErrOidcStateInvalid.clone());
+        }
+        let identity = state
+            .oidc
+            .provider(provider)?
+            .exchange(&req.code, &pending)
+            .await
+            .inspect_err(|_| failed(pending.link_user_id, "provider_rejected"))?;
+
+        let conn = state.get_conn();
+        let now = chrono::Utc::now().timestamp();
+        let linked = repos::identity::get_by_subject(&conn, provider, &identity.subject).await?;
+
+        if pending.link_user_id > 0 {
+            let linked = match linked {
+                Some(linked) if linked.user_id != pending.link_user_id => {
+                    info!(
+                        "user {} tried to link {} identity of user {}",
+                        pending.link_user_id, provider, linked.user_id
+                    );
+                    return Err(errors::ErrOidcIdentityLinked.clone());
+                }
+                Some(linked) => linked,
+                None => {
+                    let mut linked = new_identity(provider, &identity, pending.link_user_id, now);
+                    repos::identity::create(&conn, &mut linked).await?;
+                    info!("user {} linked {} identity {}", linked.user_id, provider, linked.id);
+                    linked
+                }
+            };
+            return ok!(OidcCallbackResponse {
+                login: None,
+                identity: linked,
+            });
+        }
+
+        let (user, linked, reason) = match linked {
+            Some(mut linked) => {
+                linked.email = identity.email.clone().unwrap_or_default();
+                linked.last_login_at = now;
+                repos::identity::touch(&conn, linked.id, &linked.email, now).await?;
+                (
+                    repos::user::with_deleted()
+                        .get_by_id(&conn, linked.user_id)
+                        .await?,
+                    linked,
+                    "",
+                )
+            }
+            None => {
+                let (user, linked, registered) = register(&state, provider, &identity, now).await?;
+                (user, linked, if registered { REGISTERED } else { "" })
+            }
+        };
+        info!("user {} login by {}", user.id, provider);
+        let login = finish_login(&state, &user, &client, LoginMethod::Oidc, reason).await?;
+        ok!(OidcCallbackResponse {
+            login: Some(login),
+            identity: linked,
+        })
+    }
+
+    /// Lists the provider accounts linked to the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<IdentityListResponse>` - Linked identities
+    pub async fn identities(state: AppState, auth: AuthUser) -> Result<IdentityListResponse> {
+        let identities = repos::identity::list_by_user(&state.get_conn(), auth.user_id).await?;
+        ok!(identities)
+    }
+
+    /// Unlinks a provider account from the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `id` - Id of the identity
+    ///
+    /// # Returns
+    /// * `Result<UnlinkIdentityResponse>` - Response indicating success,
+    ///   `ErrOidcLastLoginMethod` when the account would have no way left to log in
+    pub async fn unlink(
+        state: AppState,
+        auth: AuthUser,
+        id: i64,
+    ) -> Result<UnlinkIdentityResponse> {
+        let conn = state.get_conn();
+        let identities = repos::identity::list_by_user(&conn, auth.user_id).await?;
+        if !identities.iter().any(|identity| identity.id == id) {
+            return Err(errors::ErrIdentityNotFound.clone());
+        }
+        let user = repos::user::get_by_id(&conn, auth.user_id).await?;
+        let other_login = identities.len() > 1
+            || !user.password.is_empty()
+            || !user.phone.is_empty()
+            || !user.wx_open_id.is_empty();
+        if !other_login {
+            return Err(errors::ErrOidcLastLoginMethod.clone());
+        }
+        if !repos::identity::delete(&conn, id, auth.user_id).await? {
+            return Err(errors::ErrIdentityNotFound.clone());
+        }
+        info!("user {} unlinked identity {}", auth.user_id, id);
+        ok!(UnlinkIdentityResponse::default())
+    }
+}
+
+/// Stores a pending login and builds the provider URL
+async fn begin(
+    state: &AppState,
+    provider: &str,
+    link_user_id: i64,
+) -> core::result::Result<OidcAuthorizeResponse, AppError> {
+    let pending = PendingLogin::new(provider, link_user_id);
+    let login_state = utils::gen_token(32);
+    let authorize_url = state
+        .oidc
+        .provider(provider)?
+        .authorize_url(&login_state, &pending)
+        .await?;
+    state
+        .oidc
+        .save_pending(&mut state.get_redis_client()?, &login_state, &pending)?;
+    Ok(OidcAuthorizeResponse {
+        authorize_url,
+        state: login_state,
+        expires_in: state.oidc.state_ttl_secs(),
+    })
+}
+
+fn new_identity(provider: &str, identity: &OidcIdentity, user_id: i64, now: i64) -> UserIdentity {
+    UserIdentity {
+        id: 0,
+        user_id,
+        provider: provider.to_string(),
+        subject: identity.subject.clone(),
+        email: identity.email.clone().unwrap_or_default(),
+        created_at: now,
+        last_login_at: now,
+    }
+}
+
+/// Handles the first login through a provider
+///
+/// A verified email already registered joins its account when the provider has `link_by_email`
+/// and the account proved owning the email too, otherwise a new account is registered and takes
+/// the email only when it is verified and free.
+/// Returns whether an account was registered.
+async fn register(
+    state: &AppState,
+    provider: &str,
+    identity: &OidcIdentity,
+    now: i64,
+) -> core::result::Result<(UserInfo, UserIdentity, bool), AppError> {
+    let conn = state.get_conn();
+    let link_by_email = state.oidc.provider(provider)?.conf().link_by_email;
+    let mut linked = new_identity(provider, identity, 0, now);
+
+    let mut email = identity.email.clone().filter(|_| identity.email_verified);
+    if let Some(address) = &email
+        && let Some(owner) = repos::user::with_deleted()
+            .get_by_email(&conn, address)
+            .await?
+    {
+        // an email typed in without proof must not hand its account over to the provider login
+        if link_by_email && owner.email_verified_at > 0 {
+            linked.user_id = owner.id;
+            repos::identity::create(&conn, &mut linked).await?;
+            info!("user {} joined by {} identity with the same email", owner.id, provider);
+            return Ok((owner, linked, false));
+        }
+        email = None;
+    }
+
+    let mut user = UserInfo::default();
+    let name = identity
+        .name
+        .as_ref()
+        .map(|name| name.chars().take(MAX_NAME_LEN).collect())
+        .unwrap_or_else(|| format!("{}用户{}", provider, utils::gen_valid_code(6)));
+    let verified_at = if email.is_some() { now } else { 0 };
+    user.set_name(name)
+        .set_email(email)
+        .set_email_verified_at(verified_at)
+        .set_created_at(now)
+        .set_updated_at(now);
+    repos::identity::create_with_user(&conn, &mut user, &mut linked).await?;
+    state.search.sync(&conn, &[user.id]).await;
+    info!("register user {} by {}", user.id, provider);
+    Ok((user, linked, true))
+}
@@ -0,0 +1,95 @@
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::rbac::invalidate_permissions;
+use crate::core::rest::AppError;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::models::rbac::Role;
+use crate::ok;
+use crate::repos;
+use crate::types::rbac::AssignRoleRequest;
+use crate::types::rbac::RoleListResponse;
+use crate::types::rbac::UpdateRoleResponse;
+use crate::types::rbac::UserRolesResponse;
+
+/// Role management service
+pub struct RbacService;
+
+impl RbacService {
+    /// Lists every role
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    ///
+    /// # Returns
+    /// * `Result<RoleListResponse>` - All roles ordered by id
+    pub async fn list_roles(state: AppState) -> Result<RoleListResponse> {
+        let roles = repos::rbac::list_roles(&state.get_conn()).await?;
+        ok!(roles)
+    }
+
+    /// Lists the roles of a user
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `user_id` - Id of the user
+    ///
+    /// # Returns
+    /// * `Result<UserRolesResponse>` - Roles assigned to the user
+    pub async fn user_roles(state: AppState, user_id: i64) -> Result<UserRolesResponse> {
+        let roles = repos::rbac::list_roles_by_user(&state.get_conn(), user_id).await?;
+        ok!(roles)
+    }
+
+    /// Assigns a role to a user and drops its cached permissions
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `user_id` - Id of the user
+    /// * `req` - AssignRoleRequest containing the role name
+    ///
+    /// # Returns
+    /// * `Result<UpdateRoleResponse>` - `ErrRoleNotFound` if the role does not exist
+    pub async fn assign_role(
+        state: AppState,
+        user_id: i64,
+        req: AssignRoleRequest,
+    ) -> Result<UpdateRoleResponse> {
+        let conn = state.get_conn();
+        repos::user::get_by_id(&conn, user_id).await?;
+        let role = role_by_name(&state, &req.role).await?;
+        let now = chrono::Utc::now().timestamp();
+        repos::rbac::assign_role(&conn, user_id, role.id, now).await?;
+        invalidate_permissions(&state, user_id)?;
+        info!("assign role {} to user {}", role.name, user_id);
+        ok!(UpdateRoleResponse::default())
+    }
+
+    /// Removes a role from a user and drops its cached permissions
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `user_id` - Id of the user
+    /// * `role` - Name of the role
+    ///
+    /// # Returns
+    /// * `Result<UpdateRoleResponse>` - `ErrRoleNotFound` if the role does not exist
+    pub async fn remove_role(
+        state: AppState,
+        user_id: i64,
+        role: &str,
+    ) -> Result<UpdateRoleResponse> {
+        let role = role_by_name(&state, role).await?;
+        repos::rbac::remove_role(&state.get_conn(), user_id, role.id).await?;
+        invalidate_permissions(&state, user_id)?;
+        info!("remove role {} from user {}", role.name, user_id);
+        ok!(UpdateRoleResponse::default())
+    }
+}
+
+async fn role_by_name(state: &AppState, name: &str) -> core::result::Result<Role, AppError> {
+    repos::rbac::get_role_by_name(&state.get_conn(), name)
+        .await?
+        .ok_or_else(|| errors::ErrRoleNotFound.clone())
+}
@@ -0,0 +1,66 @@
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::session;
+use crate::core::state::AppState;
+use crate::errors;
+use crate::ok;
+use crate::services::token::revoke_session;
+use crate::types::session::RevokeSessionResponse;
+use crate::types::session::SessionInfo;
+use crate::types::session::SessionListResponse;
+
+/// Service listing and revoking the logged-in devices of a user
+pub struct SessionService;
+
+impl SessionService {
+    /// Lists the live sessions of the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing Redis pool
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<SessionListResponse>` - Sessions, most recently active first
+    pub async fn list(state: AppState, auth: AuthUser) -> Result<SessionListResponse> {
+        let sessions = session::list(&mut state.get_redis_client()?, auth.user_id)?
+            .into_iter()
+            .map(|record| SessionInfo {
+                current: record.id == auth.session_id,
+                id: record.id,
+                device: record.device,
+                ip: record.ip,
+                user_agent: record.user_agent,
+                created_at: record.created_at,
+                last_seen_at: record.last_seen_at,
+            })
+            .collect();
+        ok!(sessions)
+    }
+
+    /// Revokes a session of the caller, its tokens are rejected from the next request on
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing Redis pool
+    /// * `auth` - Authenticated caller
+    /// * `session_id` - Id of the session to revoke
+    ///
+    /// # Returns
+    /// * `Result<RevokeSessionResponse>` - Response indicating success, `ErrSessionNotFound` when
+    ///   the session does not exist or belongs to another user
+    pub async fn revoke(
+        state: AppState,
+        auth: AuthUser,
+        session_id: &str,
+    ) -> Result<RevokeSessionResponse> {
+        let mut conn = state.get_redis_client()?;
+        match session::get(&mut conn, session_id)? {
+            Some(record) if record.user_id == auth.user_id => {}
+            _ => return Err(errors::ErrSessionNotFound.clone()),
+        }
+        revoke_session(&mut conn, session_id, state.jwt.access_ttl_secs())?;
+        info!("user {} revoke session {}", auth.user_id, session_id);
+        ok!(RevokeSessionResponse::default())
+    }
+}
@@ -0,0 +1,224 @@
+use r2d2::PooledConnection;
+use redis::Client;
+use redis::Commands;
+use redis::Script;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::rest::AppError;
+use crate::core::session;
+use crate::core::session::ClientMeta;
+use crate::core::state::AppState;
+use crate::core::state::revoked_session_key;
+use crate::errors;
+use crate::ok;
+use crate::types::token::LogoutResponse;
+use crate::types::token::RefreshTokenRequest;
+use crate::types::token::RefreshTokenResponse;
+use crate::types::token::TokenPair;
+use crate::utils;
+
+/// Compare-and-set of the current refresh token of a family
+///
+/// Returns `{1, user_id}` after a rotation, `{0, ""}` when the token was already rotated (reuse)
+/// and `{-1, ""}` when the family does not exist anymore.
+const ROTATE_SCRIPT: &str = r#"
+local current = redis.call('HGET', KEYS[1], 'current')
+if not current then
+    return {-1, ''}
+end
+if current ~= ARGV[1] then
+    return {0, ''}
+end
+redis.call('HSET', KEYS[1], 'current', ARGV[2])
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return {1, redis.call('HGET', KEYS[1], 'user_id')}
+"#;
+
+/// Token service handling refresh token rotation and session revocation
+///
+/// Every login starts a session, i.e. a refresh token family stored in Redis:
+/// - `refresh_family_{sid}` hash with the owner `user_id` and the hash of the `current` token
+/// - `refresh_token_{sha256(token)}` pointing every token ever issued to its family
+///
+/// A refresh token that is not the current one of its family has already been used, so the whole
+/// family is revoked. Each session also has a record describing the device it was started from,
+/// see `core::session`.
+pub struct TokenService;
+
+impl TokenService {
+    /// Starts a new session for a logged-in user
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing Redis pool and JWT keys
+    /// * `user_id` - Id of the user who just logged in
+    /// * `client` - Device the user logged in from
+    ///
+    /// # Returns
+    /// * `Result<TokenPair, AppError>` - A new access and refresh token pair
+    pub async fn issue(
+        state: &AppState,
+        user_id: i64,
+        client: &ClientMeta,
+    ) -> core::result::Result<TokenPair, AppError> {
+        let session_id = utils::gen_token(16);
+        let refresh_token = utils::gen_token(32);
+        let ttl = state.jwt.refresh_ttl_secs();
+
+        let mut conn = state.get_redis_client()?;
+        let family_key = refresh_family_key(&session_id);
+        let _: () = conn
+            .hset_multiple(
+                &family_key,
+                &[
+                    ("user_id", user_id.to_string()),
+                    ("current", utils::sha256_hex(&refresh_token)),
+                ],
+            )
+            .map_err(redis_error)?;
+        let _: () = conn.expire(&family_key, ttl).map_err(redis_error)?;
+        store_refresh_token(&mut conn, &refresh_token, &session_id, ttl)?;
+        let now = chrono::Utc::now().timestamp();
+        session::create(&mut conn, &session_id, user_id, client, now, ttl)?;
+
+        let access = state.jwt.issue(user_id, &session_id)?;
+        Ok(TokenPair {
+            auth: access.token,
+            refresh_token,
+            expires_in: access.expires_in,
+        })
+    }
+
+    /// Exchanges a refresh token for a new token pair
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing Redis pool and JWT keys
+    /// * `req` - RefreshTokenRequest containing the refresh token
+    ///
+    /// # Returns
+    /// * `Result<RefreshTokenResponse>` - The rotated token pair, `ErrRefreshTokenReused` if the
+    ///   token was already used
+    pub async fn refresh(
+        state: AppState,
+        req: RefreshTokenRequest,
+    ) -> Result<RefreshTokenResponse> {
+        let mut conn = state.get_redis_client()?;
+        let token_hash = utils::sha256_hex(&req.refresh_token);
+        let session_id: Option<String> = conn
+            .get(refresh_token_key(&token_hash))
+            .map_err(redis_error)?;
+        let Some(session_id) = session_id else {
+            return Err(errors::ErrRefreshTokenInvalid.clone());
+        };
+
+        let refresh_token = utils::gen_token(32);
+        let ttl = state.jwt.refresh_ttl_secs();
+        let (status, user_id): (i64, String) = Script::new(ROTATE_SCRIPT)
+            .key(refresh_family_key(&session_id))
+            .arg(&token_hash)
+            .arg(utils::sha256_hex(&refresh_token))
+            .arg(ttl)
+            .invoke(&mut *conn)
+            .map_err(redis_error)?;
+
+        match status {
+            1 => {}
+            0 => {
+                warn!("refresh token reused, revoke session {}", session_id);
+                revoke_session(&mut conn, &session_id, state.jwt.access_ttl_secs())?;
+                return Err(errors::ErrRefreshTokenReused.clone());
+            }
+            _ => return Err(errors::ErrRefreshTokenInvalid.clone()),
+        }
+        store_refresh_token(&mut conn, &refresh_token, &session_id, ttl)?;
+
+        let user_id = user_id.parse::<i64>().map_err(|err| {
+            error!("invalid user id {} in session {} {}", user_id, session_id, err);
+            errors::ErrRefreshTokenInvalid.clone()
+        })?;
+        let now = chrono::Utc::now().timestamp();
+        session::extend(&mut conn, &session_id, user_id, now, ttl)?;
+        let access = state.jwt.issue(user_id, &session_id)?;
+        ok!(RefreshTokenResponse {
+            auth: access.token,
+            refresh_token,
+            expires_in: access.expires_in,
+        })
+    }
+
+    /// Logs out the current session
+    ///
+    /// The refresh token family is deleted and access tokens of the session are rejected until
+    /// they expire.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing Redis pool
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<LogoutResponse>` - Response indicating success
+    pub async fn logout(state: AppState, auth: AuthUser) -> Result<LogoutResponse> {
+        let mut conn = state.get_redis_client()?;
+        revoke_session(&mut conn, &auth.session_id, state.jwt.access_ttl_secs())?;
+        info!("user {} logout session {}", auth.user_id, auth.session_id);
+        ok!(LogoutResponse::default())
+    }
+}
+
+/// Deletes a refresh token family and its record, and blocks its access tokens
+pub(crate) fn revoke_session(
+    conn: &mut PooledConnection<Client>,
+    session_id: &str,
+    access_ttl_secs: i64,
+) -> core::result::Result<(), AppError> {
+    let _: () = conn
+        .del(refresh_family_key(session_id))
+        .map_err(redis_error)?;
+    let _: () = conn
+        .set_ex(revoked_session_key(session_id), 1, access_ttl_secs.max(1) as u64)
+        .map_err(redis_error)?;
+    session::remove(conn, session_id)?;
+    Ok(())
+}
+
+/// Revokes every recorded session of a user, signing it out on all devices
+pub(crate) fn revoke_user_sessions(
+    conn: &mut PooledConnection<Client>,
+    user_id: i64,
+    access_ttl_secs: i64,
+) -> core::result::Result<usize, AppError> {
+    let sessions = session::list(conn, user_id)?;
+    for record in &sessions {
+        revoke_session(conn, &record.id, access_ttl_secs)?;
+    }
+    Ok(sessions.len())
+}
+
+fn store_refresh_token(
+    conn: &mut PooledConnection<Client>,
+    refresh_token: &str,
+    session_id: &str,
+    ttl: i64,
+) -> core::result::Result<(), AppError> {
+    let key = refresh_token_key(&utils::sha256_hex(refresh_token));
+    let _: () = conn
+        .set_ex(key, session_id, ttl.max(1) as u64)
+        .map_err(redis_error)?;
+    Ok(())
+}
+
+fn refresh_family_key(session_id: &str) -> String {
+    format!("refresh_family_{}", session_id)
+}
+
+fn refresh_token_key(token_hash: &str) -> String {
+    format!("refresh_token_{}", token_hash)
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+    error!("redis command error {}", err);
+    errors::ErrRedisClient.clone()
+}
@@ -0,0 +1,259 @@
+use tracing::info;
+
+use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::lockout::Subject;
+use crate::core::rest::AppError;
+use crate::core::session::ClientMeta;
+use crate::core::state::AppState;
+use crate::core::totp;
+use crate::errors;
+use crate::models::login_event::LoginEvent;
+use crate::models::login_event::LoginMethod;
+use crate::ok;
+use crate::repos;
+use crate::services::login_event;
+use crate::services::user::LOCKED;
+use crate::services::user::start_session;
+use crate::types::two_factor::DisableTotpResponse;
+use crate::types::two_factor::EnrollTotpResponse;
+use crate::types::two_factor::RecoveryCodesResponse;
+use crate::types::two_factor::TwoFactorCodeRequest;
+use crate::types::two_factor::TwoFactorLoginRequest;
+use crate::types::two_factor::TwoFactorLoginResponse;
+
+/// TOTP two-factor authentication service
+///
+/// Enrollment is two-step: `enroll` stores a pending secret and `activate` enables it once the
+/// user proves the authenticator works, returning the recovery codes. Password logins of users
+/// with an enabled authenticator end with a challenge completed by `login`.
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    /// Generates a new secret for the caller, replacing a pending one
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    ///
+    /// # Returns
+    /// * `Result<EnrollTotpResponse>` - The secret and its `otpauth://` URI,
+    ///   `ErrTotpAlreadyEnabled` when an authenticator is already enabled
+    pub async fn enroll(state: AppState, auth: AuthUser) -> Result<EnrollTotpResponse> {
+        let conn = state.get_conn();
+        let current = repos::totp::get(&conn, auth.user_id).await?;
+        if current.is_some_and(|totp| totp.is_enabled()) {
+            return Err(errors::ErrTotpAlreadyEnabled.clone());
+        }
+        let user = repos::user::get_by_id(&conn, auth.user_id).await?;
+
+        let secret = totp::generate_secret();
+        let now = chrono::Utc::now().timestamp();
+        repos::totp::save_pending(&conn, user.id, &state.cipher.encrypt(&secret)?, now).await?;
+
+        let account = match user.email {
+            Some(email) => email,
+            None if !user.phone.is_empty() => user.phone,
+            None => format!("user{}", user.id),
+        };
+        info!("user {} enroll totp", user.id);
+        ok!(EnrollTotpResponse {
+            secret: totp::base32_encode(&secret),
+            otpauth_uri: totp::otpauth_uri(&state.two_factor.conf().issuer, &account, &secret),
+        })
+    }
+
+    /// Enables the pending secret of the caller once a code generated from it is verified
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `req` - Request containing a TOTP code
+    ///
+    /// # Returns
+    /// * `Result<RecoveryCodesResponse>` - Recovery codes, shown only once
+    pub async fn activate(
+        state: AppState,
+        auth: AuthUser,
+        req: TwoFactorCodeRequest,
+    ) -> Result<RecoveryCodesResponse> {
+        let conn = state.get_conn();
+        let pending = repos::totp::get(&conn, auth.user_id)
+            .await?
+            .ok_or_else(|| errors::ErrTotpNotEnrolled.clone())?;
+        if pending.is_enabled() {
+            return Err(errors::ErrTotpAlreadyEnabled.clone());
+        }
+        let secret = state.cipher.decrypt(&pending.secret)?;
+        let now = chrono::Utc::now().timestamp();
+        let step = state
+            .two_factor
+            .verify(&secret, &req.code, now, pending.last_step)
+            .ok_or_else(|| errors::ErrTotpCodeInvalid.clone())?;
+
+        let codes = totp::generate_recovery_codes(state.two_factor.conf().recovery_codes);
+        let hashes: Vec<String> = codes
+            .iter()
+            .map(|code| totp::hash_recovery_code(code))
+            .collect();
+        if !repos::totp::enable(&conn, auth.user_id, step, &hashes, now).await? {
+            return Err(errors::ErrTotpAlreadyEnabled.clone());
+        }
+        info!("user {} enabled totp", auth.user_id);
+        ok!(RecoveryCodesResponse {
+            recovery_codes: codes
+        })
+    }
+
+    /// Replaces the recovery codes of the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `req` - Request containing a TOTP code, recovery codes are not accepted
+    ///
+    /// # Returns
+    /// * `Result<RecoveryCodesResponse>` - New recovery codes, the previous ones stop working
+    pub async fn regenerate_recovery_codes(
+        state: AppState,
+        auth: AuthUser,
+        req: TwoFactorCodeRequest,
+    ) -> Result<RecoveryCodesResponse> {
+        verify_second_factor(&state, auth.user_id, &req.code, false).await?;
+        let codes = totp::generate_recovery_codes(state.two_factor.conf().recovery_codes);
+        let hashes: Vec<String> = codes
+            .iter()
+            .map(|code| totp::hash_recovery_code(code))
+            .collect();
+        let now = chrono::Utc::now().timestamp();
+        repos::totp::replace_recovery_codes(&state.get_conn(), auth.user_id, &hashes, now).await?;
+        info!("user {} regenerated recovery codes", auth.user_id);
+        ok!(RecoveryCodesResponse {
+            recovery_codes: codes
+        })
+    }
+
+    /// Disables two-factor authentication of the caller
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `req` - Request containing a TOTP or recovery code
+    ///
+    /// # Returns
+    /// * `Result<DisableTotpResponse>` - Response indicating success
+    pub async fn disable(
+        state: AppState,
+        auth: AuthUser,
+        req: TwoFactorCodeRequest,
+    ) -> Result<DisableTotpResponse> {
+        verify_second_factor(&state, auth.user_id, &req.code, true).await?;
+        repos::totp::delete(&state.get_conn(), auth.user_id).await?;
+        info!("user {} disabled totp", auth.user_id);
+        ok!(DisableTotpResponse::default())
+    }
+
+    /// Completes a password login with a TOTP or recovery code
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `client` - Device the user logs in from, recorded with the session
+    /// * `req` - Request containing the challenge and the code
+    ///
+    /// # Returns
+    /// * `Result<TwoFactorLoginResponse>` - Access and refresh tokens,
+    ///   `ErrTwoFactorChallengeInvalid` once the challenge expired or too many codes were tried
+    pub async fn login(
+        state: AppState,
+        client: ClientMeta,
+        req: TwoFactorLoginRequest,
+    ) -> Result<TwoFactorLoginResponse> {
+        let mut conn = state.get_redis_client()?;
+        let failed = |user_id, reason| {
+            let event = LoginEvent::new(user_id, LoginMethod::TwoFactor, &client, false, reason);
+            login_event::record(&state, event);
+        };
+        let user_id = state
+            .two_factor
+            .attempt_challenge(&mut conn, &req.challenge)
+            .inspect_err(|_| failed(0, "invalid_challenge"))?;
+        verify_second_factor(&state, user_id, &req.code, true)
+            .await
+            .inspect_err(|err| {
+                let locked = err.err_no() == errors::ErrTooManyFailedAttempts.err_no();
+                failed(user_id, if locked { LOCKED } else { "invalid_code" });
+            })?;
+        state
+            .two_factor
+            .finish_challenge(&mut conn, &req.challenge)?;
+
+        let user = repos::user::with_deleted()
+            .get_by_id(&state.get_conn(), user_id)
+            .await?;
+        let resp: TwoFactorLoginResponse = start_session(&state, &user, &client).await?;
+        let event = LoginEvent::new(user.id, LoginMethod::TwoFactor, &client, true, "");
+        login_event::record(&state, event);
+        ok!(resp)
+    }
+}
+
+/// Checks a TOTP code of an enabled authenticator, or an unused recovery code when allowed
+///
+/// Accepted TOTP steps and recovery codes are recorded so a code cannot be used twice. Wrong codes
+/// delay then lock the user.
+async fn verify_second_factor(
+    state: &AppState,
+    user_id: i64,
+    code: &str,
+    allow_recovery: bool,
+) -> core::result::Result<(), AppError> {
+    let conn = state.get_conn();
+    let enabled = repos::totp::get(&conn, user_id)
+        .await?
+        .filter(|totp| totp.is_enabled())
+        .ok_or_else(|| errors::ErrTotpNotEnrolled.clone())?;
+    let subject = [Subject::User(user_id)];
+    state
+        .lockouts
+        .check(&mut state.get_redis_client()?, &subject)?;
+    let secret = state.cipher.decrypt(&enabled.secret)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let accepted = match state
+        .two_factor
+        .verify(&secret, code, now, enabled.last_step)
+    {
+        Some(step) => {
+            let fresh = repos::totp::use_step(&conn, user_id, step).await?;
+            if !fresh {
+                info!("user {} totp code replayed", user_id);
+            }
+            fresh
+        }
+        None if allow_recovery => {
+            let used = repos::totp::use_recovery_code(
+                &conn,
+                user_id,
+                &totp::hash_recovery_code(code),
+                now,
+            )
+            .await?;
+            if used {
+                info!("user {} used a recovery code", user_id);
+            }
+            used
+        }
+        None => false,
+    };
+    if !accepted {
+        info!("user {} invalid two-factor code", user_id);
+        state
+            .lockouts
+            .fail(&mut state.get_redis_client()?, &subject)?;
+        return Err(errors::ErrTotpCodeInvalid.clone());
+    }
+    state
+        .lockouts
+        .succeed(&mut state.get_redis_client()?, &subject[0])?;
+    Ok(())
+}
@@ -1,109 +1,564 @@
+use std::collections::HashMap;
+
 use redis::Commands;
-use serde::Deserialize;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
 
 use crate::core::Result;
+use crate::core::auth::AuthUser;
+use crate::core::etag::IfMatch;
+use crate::core::lockout::Subject;
+use crate::core::page::CursorPage;
+use crate::core::page::Page;
+use crate::core::password::PasswordVerdict;
+use crate::core::rest::AppError;
+use crate::core::session::ClientMeta;
 use crate::core::state::AppState;
+use crate::core::verify_code::IssuedCode;
 use crate::errors;
+use crate::models::login_event::LoginEvent;
+use crate::models::login_event::LoginMethod;
+use crate::models::user::ProfileField;
 use crate::models::user::UserInfo;
 use crate::ok;
 use crate::repos;
+use crate::search;
+use crate::search::SearchHit;
+use crate::services::login_event;
+use crate::services::token::TokenService;
+use crate::services::token::revoke_user_sessions;
+use crate::types::token::TokenPair;
+use crate::types::two_factor::TwoFactorChallenge;
 use crate::types::user::BindEmailRequest;
 use crate::types::user::BindEmailResponse;
 use crate::types::user::ByUserIdRequest;
 use crate::types::user::ByUserIdResponse;
+use crate::types::user::DeletedUsersListRequest;
+use crate::types::user::DeletedUsersListResponse;
+use crate::types::user::EmailLoginRequest;
+use crate::types::user::EmailLoginResponse;
+use crate::types::user::EmailRegisterRequest;
+use crate::types::user::EmailRegisterResponse;
+use crate::types::user::ForgotPasswordRequest;
+use crate::types::user::ForgotPasswordResponse;
+use crate::types::user::LoginResponse;
 use crate::types::user::PreBindEmailRequest;
 use crate::types::user::PreBindEmailResponse;
+use crate::types::user::PreEmailRegisterRequest;
+use crate::types::user::PreEmailRegisterResponse;
 use crate::types::user::RandomUserRequest;
 use crate::types::user::RandomUserResponse;
+use crate::types::user::ResetPasswordRequest;
+use crate::types::user::ResetPasswordResponse;
+use crate::types::user::RestoreUserResponse;
+use crate::types::user::SearchHighlight;
+use crate::types::user::SearchUsersRequest;
+use crate::types::user::SearchUsersResponse;
+use crate::types::user::SmsLoginRequest;
+use crate::types::user::SmsLoginResponse;
+use crate::types::user::SmsPreRequest;
+use crate::types::user::SmsPreResponse;
+use crate::types::user::UpdateProfileRequest;
+use crate::types::user::UpdateProfileResponse;
+use crate::types::user::UserSearchHit;
+use crate::types::user::UsersCursorResponse;
+use crate::types::user::UsersListRequest;
+use crate::types::user::UsersListResponse;
 use crate::types::user::WxMiniLoginRequest;
 use crate::types::user::WxMiniLoginResponse;
 use crate::utils;
 
-#[derive(Debug, Deserialize)]
-/// Response structure for WeChat login API
-struct InnerWechatLoginResponse {
-    /// WeChat user's unique identifier
-    pub openid: String,
-}
 /// User service for handling user-related operations
 pub struct UserService;
 
 impl UserService {
-    /// Pre-binds an email address by generating and storing a validation code
+    /// Pre-binds an email address by generating and sending a validation code
     ///
     /// # Arguments
     /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller who wants to bind the email
+    /// * `ip` - Client IP, rate limited alongside the email
     /// * `req` - PreBindEmailRequest containing the email address
     ///
     /// # Returns
-    /// * `Result<PreBindEmailResponse>` - Response indicating success
+    /// * `Result<PreBindEmailResponse>` - Lifetime of the code and delay before a resend
     pub async fn pre_bind_email(
         state: AppState,
+        auth: AuthUser,
+        ip: &str,
         req: PreBindEmailRequest,
     ) -> Result<PreBindEmailResponse> {
-        let key = format!("bind_email_{}", req.email);
-        let valid_code = utils::gen_valid_code(5);
-        info!("valid_code {}", valid_code);
-        let _: () = state
-            .get_redis_client()
-            .unwrap()
-            .set(&key, valid_code)
-            .unwrap();
-        ok!(PreBindEmailResponse::default())
+        let email = req.email.trim().to_lowercase();
+        ensure_email_available(&state, auth.user_id, &email).await?;
+
+        let issued = send_email_code(&state, BIND_EMAIL_SCENE, &email, ip).await?;
+        info!("user {} bind email {} code sent", auth.user_id, email);
+        ok!(PreBindEmailResponse {
+            expires_in: issued.expires_in,
+            resend_in: issued.resend_in,
+        })
+    }
+
+    /// Sends the validation code proving the email of a new account belongs to its owner
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `ip` - Client IP, rate limited alongside the email
+    /// * `req` - PreEmailRegisterRequest containing the email address
+    ///
+    /// # Returns
+    /// * `Result<PreEmailRegisterResponse>` - Lifetime of the code and delay before a resend,
+    ///   `ErrEmailRegistered` when the email already belongs to an account
+    pub async fn pre_email_register(
+        state: AppState,
+        ip: &str,
+        req: PreEmailRegisterRequest,
+    ) -> Result<PreEmailRegisterResponse> {
+        let email = req.email.trim().to_lowercase();
+        ensure_email_available(&state, 0, &email).await?;
+
+        let issued = send_email_code(&state, REGISTER_EMAIL_SCENE, &email, ip).await?;
+        info!("register email {} code sent", email);
+        ok!(PreEmailRegisterResponse {
+            expires_in: issued.expires_in,
+            resend_in: issued.resend_in,
+        })
     }
 
     /// Handles WeChat mini-program login
     ///
+    /// Exchanges the code through WeChat `jscode2session`, registers the user on first login and
+    /// keeps the encrypted `session_key` in Redis for later decryption of WeChat user data.
+    ///
     /// # Arguments
     /// * `state` - Application state containing database connections
+    /// * `client` - Device the user logs in from, recorded with the session
     /// * `req` - WxMiniLoginRequest containing WeChat login code
     ///
     /// # Returns
-    /// * `Result<WxMiniLoginResponse>` - Login response with user authentication info
-    pub async fn wx_login(state: AppState, req: WxMiniLoginRequest) -> Result<WxMiniLoginResponse> {
+    /// * `Result<WxMiniLoginResponse>` - Login response with user authentication info, or a
+    ///   two-factor challenge
+    pub async fn wx_login(
+        state: AppState,
+        client: ClientMeta,
+        req: WxMiniLoginRequest,
+    ) -> Result<WxMiniLoginResponse> {
         debug!("wx login {}", req.code);
-        // Not Implemented Yet
-        let resp = ureq::get("https://exmaple.com/foo/baz")
-            .call()
-            .map_err(|err| {
-                error!("call wechat api error {}", err);
-                errors::ErrWechatLogin.clone()
-            })?
-            .body_mut()
-            .read_json::<InnerWechatLoginResponse>()
-            .map_err(|err| {
-                error!("unmarshal wechat response {}", err);
-                errors::ErrUnmarshalJSON.clone()
+        let session = state
+            .wechat
+            .code2session(&req.code)
+            .await
+            .inspect_err(|_| {
+                let event = LoginEvent::new(0, LoginMethod::Wechat, &client, false, "wechat_error");
+                login_event::record(&state, event);
             })?;
 
-        let open_id = resp.openid.clone();
-        let user = repos::user::get_by_wx_open_id(&state.get_conn(), &open_id).await?;
+        let mut reason = "";
+        let user = match repos::user::with_deleted()
+            .get_by_wx_open_id(&state.get_conn(), &session.openid)
+            .await?
+        {
+            Some(user) => user,
+            None => {
+                let now = chrono::Utc::now().timestamp();
+                let mut user = UserInfo::default();
+                user.set_name(format!("微信用户{}", utils::gen_valid_code(6)))
+                    .set_wx_open_id(session.openid.clone())
+                    .set_created_at(now)
+                    .set_updated_at(now);
+                repos::user::create(&state.get_conn(), &mut user).await?;
+                state.search.sync(&state.get_conn(), &[user.id]).await;
+                info!("register user {} by wechat", user.id);
+                reason = REGISTERED;
+                user
+            }
+        };
         info!("user info {:?}", user);
-        let resp = WxMiniLoginResponse {
-            auth: user.nick_name,
+
+        let session_key = state.cipher.encrypt(session.session_key.as_bytes())?;
+        let _: () = state
+            .get_redis_client()?
+            .set_ex(
+                wechat_session_key(user.id),
+                session_key,
+                state.wechat.conf().session_key_ttl_secs,
+            )
+            .map_err(|err| {
+                error!("store wechat session key error {}", err);
+                errors::ErrRedisClient.clone()
+            })?;
+
+        let resp: WxMiniLoginResponse =
+            finish_login(&state, &user, &client, LoginMethod::Wechat, reason).await?;
+        ok!(resp)
+    }
+
+    /// Handles email + password login
+    ///
+    /// Legacy salted hashes and Argon2 hashes with outdated parameters are replaced by a fresh
+    /// Argon2id hash once the password is verified. Accounts with two-factor authentication get a
+    /// challenge to complete at `/user/2fa/login` instead of tokens. Failures delay then lock the
+    /// email and the client IP.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `client` - Device the user logs in from, recorded with the session
+    /// * `req` - EmailLoginRequest containing email and password
+    ///
+    /// # Returns
+    /// * `Result<EmailLoginResponse>` - Login response with the access token or the challenge
+    pub async fn email_login(
+        state: AppState,
+        client: ClientMeta,
+        req: EmailLoginRequest,
+    ) -> Result<EmailLoginResponse> {
+        let email = req.email.trim().to_lowercase();
+        let subjects = [Subject::Email(&email), Subject::Ip(&client.ip)];
+        let found = repos::user::with_deleted()
+            .get_by_email(&state.get_conn(), &email)
+            .await?;
+        state
+            .lockouts
+            .check(&mut state.get_redis_client()?, &subjects)
+            .inspect_err(|_| {
+                let user_id = found.as_ref().map_or(0, |user| user.id);
+                let event = LoginEvent::new(user_id, LoginMethod::Email, &client, false, LOCKED);
+                login_event::record(&state, event);
+            })?;
+        let Some(user) = found else {
+            state.passwords.verify_dummy(&req.password).await;
+            info!("email login unknown email {}", email);
+            state
+                .lockouts
+                .fail(&mut state.get_redis_client()?, &subjects)?;
+            let event = LoginEvent::new(0, LoginMethod::Email, &client, false, "unknown_account");
+            login_event::record(&state, event);
+            return Err(errors::ErrEmailOrPasswordInvalid.clone());
         };
+
+        let verdict = state
+            .passwords
+            .verify(&req.password, &user.password, &user.salt)
+            .await?;
+        match verdict {
+            PasswordVerdict::Mismatch => {
+                info!("email login wrong password user {}", user.id);
+                state
+                    .lockouts
+                    .fail(&mut state.get_redis_client()?, &subjects)?;
+                let event =
+                    LoginEvent::new(user.id, LoginMethod::Email, &client, false, "wrong_password");
+                login_event::record(&state, event);
+                return Err(errors::ErrEmailOrPasswordInvalid.clone());
+            }
+            PasswordVerdict::MatchNeedsRehash => {
+                let hash = state.passwords.hash(&req.password).await?;
+                let now = chrono::Utc::now().timestamp();
+                let conn = state.get_conn();
+                // a concurrent change wins, the password is rehashed at the next login
+                if repos::user::update_password(&conn, user.id, user.version, "", &hash, now)
+                    .await?
+                {
+                    info!("rehash password user {}", user.id);
+                }
+            }
+            PasswordVerdict::Match => {}
+        }
+        state
+            .lockouts
+            .succeed(&mut state.get_redis_client()?, &subjects[0])?;
+
+        let resp: EmailLoginResponse =
+            finish_login(&state, &user, &client, LoginMethod::Email, "").await?;
         ok!(resp)
     }
 
-    /// Binds an email address to a user account
+    /// Registers a new account with email + password once the validation code mailed by
+    /// `pre_email_register` is checked
     ///
     /// # Arguments
     /// * `state` - Application state containing database connections
-    /// * `req` - BindEmailRequest containing email address
+    /// * `client` - Device the user logs in from, recorded with the session
+    /// * `req` - EmailRegisterRequest containing email, validation code, password and optional
+    ///   nickname
+    ///
+    /// # Returns
+    /// * `Result<EmailRegisterResponse>` - Login response with the access token of the new user
+    pub async fn email_register(
+        state: AppState,
+        client: ClientMeta,
+        req: EmailRegisterRequest,
+    ) -> Result<EmailRegisterResponse> {
+        let email = req.email.trim().to_lowercase();
+        let subjects = [Subject::Email(&email), Subject::Ip(&client.ip)];
+        let mut conn = state.get_redis_client()?;
+        state.lockouts.check(&mut conn, &subjects)?;
+        if let Err(err) =
+            state
+                .verify_codes
+                .verify(&mut conn, REGISTER_EMAIL_SCENE, &email, &req.valid_code)
+        {
+            state.lockouts.fail(&mut conn, &subjects)?;
+            return Err(err);
+        }
+        state.lockouts.succeed(&mut conn, &subjects[0])?;
+        ensure_email_available(&state, 0, &email).await?;
+
+        let nick_name = req
+            .nick_name
+            .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+        let hash = state.passwords.hash(&req.password).await?;
+        let now = chrono::Utc::now().timestamp();
+        let mut user = UserInfo::default();
+        user.set_name(nick_name)
+            .set_email(Some(email))
+            .set_email_verified_at(now)
+            .set_password(hash)
+            .set_created_at(now)
+            .set_updated_at(now);
+        repos::user::create(&state.get_conn(), &mut user).await?;
+        state.search.sync(&state.get_conn(), &[user.id]).await;
+        info!("register user {} by email", user.id);
+
+        let resp: EmailRegisterResponse = start_session(&state, &user, &client).await?;
+        let event = LoginEvent::new(user.id, LoginMethod::Email, &client, true, REGISTERED);
+        login_event::record(&state, event);
+        ok!(resp)
+    }
+
+    /// Binds an email address to a user account once the validation code is checked
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller the email is bound to
+    /// * `ip` - Client IP, locked alongside the caller after too many wrong codes
+    /// * `req` - BindEmailRequest containing email address and validation code
     ///
     /// # Returns
     /// * `Result<BindEmailResponse>` - Response indicating successful binding
-    pub async fn bind_email(state: AppState, req: BindEmailRequest) -> Result<BindEmailResponse> {
-        info!("bind email {}", req.email);
-        let mut redis_conn = state.get_redis_client()?;
-        let key = format!("user_{}", req.email);
-        let _: () = redis_conn.set(&key, "").unwrap();
+    pub async fn bind_email(
+        state: AppState,
+        auth: AuthUser,
+        ip: &str,
+        req: BindEmailRequest,
+    ) -> Result<BindEmailResponse> {
+        let email = req.email.trim().to_lowercase();
+        let subjects = [Subject::User(auth.user_id), Subject::Ip(ip)];
+        let mut conn = state.get_redis_client()?;
+        state.lockouts.check(&mut conn, &subjects)?;
+        if let Err(err) =
+            state
+                .verify_codes
+                .verify(&mut conn, BIND_EMAIL_SCENE, &email, &req.valid_code)
+        {
+            state.lockouts.fail(&mut conn, &subjects)?;
+            return Err(err);
+        }
+        state.lockouts.succeed(&mut conn, &subjects[0])?;
+
+        ensure_email_available(&state, auth.user_id, &email).await?;
+        let conn = state.get_conn();
+        let now = chrono::Utc::now().timestamp();
+        // the code is spent, a change of the profile winning the race is read again once
+        let mut bound = false;
+        for _ in 0..2 {
+            let user = repos::user::get_by_id(&conn, auth.user_id).await?;
+            if repos::user::update_email(&conn, user.id, user.version, &email, now).await? {
+                bound = true;
+                break;
+            }
+        }
+        if !bound {
+            return Err(errors::ErrVersionConflict.clone());
+        }
+        info!("user {} bind email {}", auth.user_id, email);
         ok!(BindEmailResponse::default())
     }
 
+    /// Sends an SMS login code to a phone number
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `ip` - Client IP, rate limited alongside the phone number
+    /// * `req` - SmsPreRequest containing the phone number
+    ///
+    /// # Returns
+    /// * `Result<SmsPreResponse>` - Lifetime of the code and delay before a resend
+    pub async fn sms_pre(state: AppState, ip: &str, req: SmsPreRequest) -> Result<SmsPreResponse> {
+        let phone = state.sms.normalize(&req.phone)?;
+        let issued = state.verify_codes.issue(
+            &mut state.get_redis_client()?,
+            SMS_LOGIN_SCENE,
+            &phone,
+            ip,
+        )?;
+        if let Err(err) = state
+            .sms
+            .send_code(&phone, &issued.code, issued.expires_in / 60)
+            .await
+        {
+            // an undelivered code must not hold back the next request
+            let mut conn = state.get_redis_client()?;
+            state
+                .verify_codes
+                .revoke(&mut conn, SMS_LOGIN_SCENE, &phone, ip, &issued)?;
+            return Err(err);
+        }
+        info!("sms login code sent to {}", phone);
+        ok!(SmsPreResponse {
+            expires_in: issued.expires_in,
+            resend_in: issued.resend_in,
+        })
+    }
+
+    /// Handles phone number + SMS code login, registering unknown numbers
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `client` - Device the user logs in from, recorded with the session
+    /// * `req` - SmsLoginRequest containing phone number and code
+    ///
+    /// # Returns
+    /// * `Result<SmsLoginResponse>` - Login response with the access token, or a two-factor
+    ///   challenge
+    pub async fn sms_login(
+        state: AppState,
+        client: ClientMeta,
+        req: SmsLoginRequest,
+    ) -> Result<SmsLoginResponse> {
+        let phone = state.sms.normalize(&req.phone)?;
+        let existing = repos::user::with_deleted()
+            .get_by_phone(&state.get_conn(), &phone)
+            .await?;
+        let failed = |reason| {
+            let user_id = existing.as_ref().map_or(0, |user| user.id);
+            let event = LoginEvent::new(user_id, LoginMethod::Sms, &client, false, reason);
+            login_event::record(&state, event);
+        };
+        let subjects = [Subject::Phone(&phone), Subject::Ip(&client.ip)];
+        let mut conn = state.get_redis_client()?;
+        state
+            .lockouts
+            .check(&mut conn, &subjects)
+            .inspect_err(|_| failed(LOCKED))?;
+        if let Err(err) = state
+            .verify_codes
+            .verify(&mut conn, SMS_LOGIN_SCENE, &phone, &req.code)
+        {
+            state.lockouts.fail(&mut conn, &subjects)?;
+            failed("invalid_code");
+            return Err(err);
+        }
+        state.lockouts.succeed(&mut conn, &subjects[0])?;
+
+        let mut reason = "";
+        let user = match existing {
+            Some(user) => user,
+            None => {
+                let now = chrono::Utc::now().timestamp();
+                let suffix = &phone[phone.len() - 4..];
+                let mut user = UserInfo::default();
+                user.set_name(format!("用户{}", suffix))
+                    .set_phone(phone.clone())
+                    .set_created_at(now)
+                    .set_updated_at(now);
+                repos::user::create(&state.get_conn(), &mut user).await?;
+                state.search.sync(&state.get_conn(), &[user.id]).await;
+                info!("register user {} by sms", user.id);
+                reason = REGISTERED;
+                user
+            }
+        };
+
+        let resp: SmsLoginResponse =
+            finish_login(&state, &user, &client, LoginMethod::Sms, reason).await?;
+        ok!(resp)
+    }
+
+    /// Mails a password reset link when the email belongs to an account
+    ///
+    /// The response does not tell whether the account exists, the mail is sent in the background
+    /// so the response time does not either.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `ip` - Client IP, logged with the request
+    /// * `req` - ForgotPasswordRequest containing the email address
+    ///
+    /// # Returns
+    /// * `Result<ForgotPasswordResponse>` - Lifetime of the link
+    pub async fn forgot_password(
+        state: AppState,
+        ip: &str,
+        req: ForgotPasswordRequest,
+    ) -> Result<ForgotPasswordResponse> {
+        let email = req.email.trim().to_lowercase();
+        let resp = ForgotPasswordResponse {
+            expires_in: state.reset_tokens.ttl_secs(),
+        };
+        let Some(user) = repos::user::get_by_email(&state.get_conn(), &email).await? else {
+            info!("password reset for unknown email {} from {}", email, ip);
+            return ok!(resp);
+        };
+        let Some(token) = state
+            .reset_tokens
+            .issue(&mut state.get_redis_client()?, user.id)?
+        else {
+            info!("password reset for user {} too frequent, from {}", user.id, ip);
+            return ok!(resp);
+        };
+
+        let body = format!(
+            "Open the link below to choose a new password. It expires in {} minutes and can be used once.\n\n{}\n\nIf you did not request it, please ignore this mail.",
+            resp.expires_in / 60,
+            state.reset_tokens.reset_link(&token)
+        );
+        let mail = state.mail.clone();
+        tokio::spawn(async move {
+            // failures are logged by the mail client
+            let _ = mail.send_text(&email, "Reset your password", &body).await;
+        });
+        info!("password reset mail for user {} from {}", user.id, ip);
+        ok!(resp)
+    }
+
+    /// Sets a new password with a reset token, then signs the user out on every device
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - ResetPasswordRequest containing the token and the new password
+    ///
+    /// # Returns
+    /// * `Result<ResetPasswordResponse>` - Response indicating success, `ErrResetTokenInvalid`
+    ///   when the token is unknown, expired or already used
+    pub async fn reset_password(
+        state: AppState,
+        req: ResetPasswordRequest,
+    ) -> Result<ResetPasswordResponse> {
+        let mut conn = state.get_redis_client()?;
+        let user_id = state.reset_tokens.consume(&mut conn, &req.token)?;
+        let hash = state.passwords.hash(&req.password).await?;
+        let now = chrono::Utc::now().timestamp();
+        // the token is spent, a change of the profile winning the race is read again once
+        let mut reset = None;
+        for _ in 0..2 {
+            let mut user = repos::user::get_by_id(&state.get_conn(), user_id).await?;
+            user.set_salt(String::new())
+                .set_password(hash.clone())
+                .set_updated_at(now);
+            if repos::user::update(&state.get_conn(), &user).await? {
+                reset = Some(user);
+                break;
+            }
+        }
+        let user = reset.ok_or_else(|| errors::ErrVersionConflict.clone())?;
+
+        let revoked = revoke_user_sessions(&mut conn, user.id, state.jwt.access_ttl_secs())?;
+        info!("user {} reset password, {} sessions revoked", user.id, revoked);
+        ok!(ResetPasswordResponse::default())
+    }
+
     /// Retrieves user information by user ID
     ///
     /// # Arguments
@@ -133,6 +588,428 @@ impl UserService {
         let mut user = UserInfo::random();
         info!("create random user {user:?}");
         repos::user::create(&state.get_conn(), &mut user).await?;
+        state.search.sync(&state.get_conn(), &[user.id]).await;
         ok!(user)
     }
+
+    /// Lists users page by page, or the users whose nickname or signature matches `q`
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - UsersListRequest containing the page and the search text
+    ///
+    /// # Returns
+    /// * `Result<UsersListResponse>` - One page of users, most recent or most relevant first
+    pub async fn list(state: AppState, req: UsersListRequest) -> Result<UsersListResponse> {
+        let conn = state.get_conn();
+        let (page_no, page_size) = (req.page_no as u32, req.page_size as u32);
+        let (list, total) = match req.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            Some(q) => {
+                let offset = (page_no - 1) * page_size;
+                let (ranked, total) = ranked_users(&state, q, offset, page_size).await?;
+                (ranked.into_iter().map(|(user, _)| user).collect(), total)
+            }
+            None => (
+                repos::user::list(&conn, page_no, page_size).await?,
+                repos::user::count(&conn).await?,
+            ),
+        };
+        ok!(Page::new(list, total, req.page_no, req.page_size))
+    }
+
+    /// Searches the nicknames and signatures of the users, most relevant first
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing the search index
+    /// * `req` - SearchUsersRequest containing the search text and the page
+    ///
+    /// # Returns
+    /// * `Result<SearchUsersResponse>` - One page of matching users with their relevance and
+    ///   the highlighted parts of their fields
+    pub async fn search(state: AppState, req: SearchUsersRequest) -> Result<SearchUsersResponse> {
+        let q = req.q.trim();
+        let offset = ((req.page_no - 1) * req.page_size) as u32;
+        let (ranked, total) = ranked_users(&state, q, offset, req.page_size as u32).await?;
+        debug!("search users {:?} found {}", q, total);
+        let list = ranked
+            .into_iter()
+            .map(|(user, score)| UserSearchHit {
+                highlight: SearchHighlight {
+                    nick_name: search::highlight(&user.nick_name, q),
+                    signature: search::highlight(&user.signature, q),
+                },
+                score,
+                user,
+            })
+            .collect();
+        ok!(Page::new(list, total, req.page_no, req.page_size))
+    }
+
+    /// Lists the soft-deleted users page by page, most recent first
+    ///
+    /// Accounts whose owner requested the deletion stay listed with their `purge_at` until
+    /// the purge task erases them.
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - Page to list
+    ///
+    /// # Returns
+    /// * `Result<DeletedUsersListResponse>` - One page of deleted users
+    pub async fn list_deleted(
+        state: AppState,
+        req: DeletedUsersListRequest,
+    ) -> Result<DeletedUsersListResponse> {
+        let conn = state.get_conn();
+        let trash = repos::user::only_deleted();
+        let list = trash
+            .list(&conn, req.page_no as u32, req.page_size as u32)
+            .await?;
+        let total = trash.count(&conn).await?;
+        ok!(Page::new(list, total, req.page_no, req.page_size))
+    }
+
+    /// Restores a soft-deleted user, cancelling a pending deletion
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated administrator
+    /// * `req` - ByUserIdRequest containing the user ID
+    ///
+    /// # Returns
+    /// * `Result<RestoreUserResponse>` - The restored user, `ErrDbRowNotFound` when the user
+    ///   does not exist or is not deleted
+    pub async fn restore(
+        state: AppState,
+        auth: AuthUser,
+        req: ByUserIdRequest,
+    ) -> Result<RestoreUserResponse> {
+        let conn = state.get_conn();
+        let now = chrono::Utc::now().timestamp();
+        if !repos::user::restore(&conn, req.id, now).await? {
+            return Err(errors::ErrDbRowNotFound.clone());
+        }
+        state.search.sync(&conn, &[req.id]).await;
+        info!("user {} restored user {}", auth.user_id, req.id);
+        ok!(repos::user::get_by_id(&conn, req.id).await?)
+    }
+
+    /// Lists users slice by slice after the position of `req.cursor`, or the users whose
+    /// nickname or signature matches `q`
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `req` - UsersListRequest containing the cursor, the slice size and the search text
+    ///
+    /// # Returns
+    /// * `Result<UsersCursorResponse>` - One slice of users, most recent or most relevant first
+    pub async fn list_by_cursor(
+        state: AppState,
+        req: UsersListRequest,
+    ) -> Result<UsersCursorResponse> {
+        let conn = state.get_conn();
+        if let Some(q) = req.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            // results ranked by relevance resume after the relevance and id of the last hit
+            let scope = format!("users-search\0{}", q);
+            let after = match req.cursor.as_deref().unwrap_or_default() {
+                "" => None,
+                cursor => {
+                    let (score, id) = state.cursors.decode_ranked(&scope, cursor)?;
+                    Some(SearchHit { id, score })
+                }
+            };
+            let mut hits = state
+                .search
+                .search_after(q, after, req.page_size as u32 + 1)
+                .await?;
+            // users deleted since they were indexed shorten the slice, not the search
+            let has_more = hits.len() > req.page_size;
+            hits.truncate(req.page_size);
+            let next_cursor = hits
+                .last()
+                .filter(|_| has_more)
+                .map(|hit| state.cursors.encode_ranked(&scope, hit.score, hit.id));
+            let ranked = users_of_hits(&state, &hits).await?;
+            return ok!(CursorPage {
+                list: ranked.into_iter().map(|(user, _)| user).collect(),
+                next_cursor,
+                has_more,
+            });
+        }
+
+        // one more row tells whether another slice follows
+        let limit = req.page_size as u32 + 1;
+        // a cursor only resumes the listing it was issued for
+        let scope = "users\0";
+        let before_id = match req.cursor.as_deref().unwrap_or_default() {
+            "" => i64::MAX,
+            cursor => state.cursors.decode(scope, cursor)?,
+        };
+        let rows = repos::user::list_before(&conn, before_id, limit).await?;
+        ok!(CursorPage::new(rows, req.page_size, |user| { state.cursors.encode(scope, user.id) }))
+    }
+
+    /// Changes the fields of the caller's profile present in the request
+    ///
+    /// # Arguments
+    /// * `state` - Application state containing database connections
+    /// * `auth` - Authenticated caller
+    /// * `if_match` - Versions of the profile the change was made on
+    /// * `req` - UpdateProfileRequest with the fields to change or reset
+    ///
+    /// # Returns
+    /// * `Result<UpdateProfileResponse>` - The updated user, `ErrVersionConflict` when the
+    ///   profile changed since the client read it
+    pub async fn update_me(
+        state: AppState,
+        auth: AuthUser,
+        if_match: IfMatch,
+        req: UpdateProfileRequest,
+    ) -> Result<UpdateProfileResponse> {
+        let conn = state.get_conn();
+        let mut user = repos::user::get_by_id(&conn, auth.user_id).await?;
+        let version = if_match.check(user.version)?;
+        let fields: Vec<ProfileField> = [
+            req.nick_name.into_update().map(ProfileField::NickName),
+            req.signature.into_update().map(ProfileField::Signature),
+            req.age.into_update().map(ProfileField::Age),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if fields.is_empty() {
+            return ok!(user);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        // another update may have won the race since the profile was read
+        if !repos::user::update_profile(&conn, user.id, version, &fields, now).await? {
+            return Err(errors::ErrVersionConflict.clone());
+        }
+        state.search.sync(&conn, &[user.id]).await;
+        for field in &fields {
+            field.apply(&mut user);
+        }
+        user.set_updated_at(now);
+        user.version += 1;
+        info!("user {} updated profile {:?}", user.id, fields);
+        ok!(user)
+    }
+}
+
+/// Verification code scene of the email binding, codes live under `bind_email_{email}`
+const BIND_EMAIL_SCENE: &str = "bind_email";
+const REGISTER_EMAIL_SCENE: &str = "register_email";
+
+/// Login event note of a login that registered the account
+pub(crate) const REGISTERED: &str = "registered";
+
+/// Login event reason of an attempt refused while the account or the IP is locked
+pub(crate) const LOCKED: &str = "locked";
+
+/// Verification code scene of the SMS login, codes live under `sms_login_{phone}`
+const SMS_LOGIN_SCENE: &str = "sms_login";
+
+/// Mails a validation code of `scene`, withdrawing it when the mail cannot be sent
+async fn send_email_code(
+    state: &AppState,
+    scene: &str,
+    email: &str,
+    ip: &str,
+) -> core::result::Result<IssuedCode, AppError> {
+    let issued = state
+        .verify_codes
+        .issue(&mut state.get_redis_client()?, scene, email, ip)?;
+    let body = format!(
+        "Your verification code is {}. It expires in {} minutes.\n\nIf you did not request it, please ignore this mail.",
+        issued.code,
+        issued.expires_in / 60
+    );
+    if let Err(err) = state
+        .mail
+        .send_text(email, "Verify your email address", &body)
+        .await
+    {
+        // an undelivered code must not hold back the next request
+        let mut conn = state.get_redis_client()?;
+        state
+            .verify_codes
+            .revoke(&mut conn, scene, email, ip, &issued)?;
+        return Err(err);
+    }
+    Ok(issued)
+}
+
+/// Fails with `ErrEmailRegistered` when the email already belongs to another account
+async fn ensure_email_available(
+    state: &AppState,
+    user_id: i64,
+    email: &str,
+) -> core::result::Result<(), AppError> {
+    match repos::user::with_deleted()
+        .get_by_email(&state.get_conn(), email)
+        .await?
+    {
+        Some(owner) if owner.id != user_id => Err(errors::ErrEmailRegistered.clone()),
+        _ => Ok(()),
+    }
+}
+
+/// Ends the first step of a login, with the tokens or with a challenge when the account has
+/// two-factor authentication, and records the login event with the `reason` note
+pub(crate) async fn finish_login(
+    state: &AppState,
+    user: &UserInfo,
+    client: &ClientMeta,
+    method: LoginMethod,
+    reason: &str,
+) -> core::result::Result<LoginResponse, AppError> {
+    ensure_not_deleted(user)?;
+    let totp = repos::totp::get(&state.get_conn(), user.id).await?;
+    if totp.is_some_and(|totp| totp.is_enabled()) {
+        let challenge = state
+            .two_factor
+            .issue_challenge(&mut state.get_redis_client()?, user.id)?;
+        info!("user {} login requires two-factor", user.id);
+        // only the second step starting the session counts as a successful login
+        let event = LoginEvent::new(user.id, method, client, false, "two_factor_required");
+        login_event::record(state, event);
+        return Ok(LoginResponse {
+            tokens: None,
+            two_factor: Some(TwoFactorChallenge {
+                challenge,
+                expires_in: state.two_factor.conf().challenge_ttl_secs,
+            }),
+        });
+    }
+    let tokens = start_session(state, user, client).await?;
+    login_event::record(state, LoginEvent::new(user.id, method, client, true, reason));
+    Ok(LoginResponse {
+        tokens: Some(tokens),
+        two_factor: None,
+    })
+}
+
+/// Issues the tokens of a successful login, cancelling a pending deletion of the account
+pub(crate) async fn start_session(
+    state: &AppState,
+    user: &UserInfo,
+    client: &ClientMeta,
+) -> core::result::Result<TokenPair, AppError> {
+    ensure_not_deleted(user)?;
+    if user.purge_at > 0 {
+        let now = chrono::Utc::now().timestamp();
+        if repos::user::cancel_deletion(&state.get_conn(), user.id, now).await? {
+            state.search.sync(&state.get_conn(), &[user.id]).await;
+            info!("user {} logged in, deletion cancelled", user.id);
+        }
+    }
+    TokenService::issue(state, user.id, client).await
+}
+
+/// Fails with `ErrAccountDeleted` for accounts deleted by an administrator
+///
+/// Logins look accounts up `with_deleted()`: an account whose owner requested its deletion has a
+/// `purge_at` and logging in within the grace period cancels the deletion.
+fn ensure_not_deleted(user: &UserInfo) -> core::result::Result<(), AppError> {
+    if user.deleted_at > 0 && user.purge_at == 0 {
+        info!("deleted user {} refused login", user.id);
+        return Err(errors::ErrAccountDeleted.clone());
+    }
+    Ok(())
+}
+
+/// Users matching `q` in the order of the search index, with their relevance
+///
+/// # Returns
+/// * `Result<(Vec<(UserInfo, f64)>, i64), AppError>` - Users of the page and number of matches
+async fn ranked_users(
+    state: &AppState,
+    q: &str,
+    offset: u32,
+    limit: u32,
+) -> core::result::Result<(Vec<(UserInfo, f64)>, i64), AppError> {
+    let hits = state.search.search(q, offset, limit).await?;
+    let ranked = users_of_hits(state, &hits.hits).await?;
+    Ok((ranked, hits.total as i64))
+}
+
+/// Users of search hits in the order of the hits, with their relevance
+///
+/// Users are read from the table, a user deleted since the index found it is left out.
+async fn users_of_hits(
+    state: &AppState,
+    hits: &[SearchHit],
+) -> core::result::Result<Vec<(UserInfo, f64)>, AppError> {
+    let ids: Vec<i64> = hits.iter().map(|hit| hit.id).collect();
+    let mut users: HashMap<i64, UserInfo> = repos::user::list_by_ids(&state.get_conn(), &ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+    Ok(hits
+        .iter()
+        .filter_map(|hit| users.remove(&hit.id).map(|user| (user, hit.score)))
+        .collect())
+}
+
+/// Redis key of the encrypted WeChat `session_key` of a user
+pub(crate) fn wechat_session_key(user_id: i64) -> String {
+    format!("wechat_session_key_{}", user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::AppConf;
+    use crate::core::state::test_state;
+
+    #[tokio::test]
+    #[ignore = "needs the MySQL and Redis of etc/config.toml"]
+    async fn test_sms_login_two_factor() {
+        let conf = AppConf::from_path("etc/config.toml").unwrap();
+        let mut state = test_state();
+        state.db_conn = conf.mysql.init_conn().await.unwrap();
+        state.redis_pool = conf.redis.init_pool().await.unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let phone = state
+            .sms
+            .normalize(&format!("139{:08}", now % 100_000_000))
+            .unwrap();
+        let mut user = UserInfo::default();
+        user.set_name("两步验证用户".to_string())
+            .set_phone(phone.clone())
+            .set_created_at(now)
+            .set_updated_at(now);
+        repos::user::create(&state.get_conn(), &mut user)
+            .await
+            .unwrap();
+        repos::totp::save_pending(&state.get_conn(), user.id, "secret", now)
+            .await
+            .unwrap();
+        assert!(
+            repos::totp::enable(&state.get_conn(), user.id, 0, &[], now)
+                .await
+                .unwrap()
+        );
+
+        let mut conn = state.get_redis_client().unwrap();
+        let issued = state
+            .verify_codes
+            .issue(&mut conn, SMS_LOGIN_SCENE, &phone, "192.0.2.1")
+            .unwrap();
+        let client = ClientMeta {
+            ip: "192.0.2.1".to_string(),
+            user_agent: String::new(),
+            device: String::new(),
+        };
+        let req = SmsLoginRequest {
+            phone: phone.clone(),
+            code: issued.code,
+        };
+        let resp = UserService::sms_login(state, client, req).await.unwrap().0;
+        assert!(resp.tokens.is_none());
+        assert!(resp.two_factor.is_some());
+    }
 }
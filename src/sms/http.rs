@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::sms::SmsConf;
+use crate::sms::SmsSender;
+
+/// Provider posting codes as JSON to an HTTP gateway
+///
+/// The request is `POST {http_url}` with the body
+/// `{"phone": "+86..", "template_id": "..", "params": {"code": "..", "minutes": 10}}` and, when
+/// `http_api_key` is set, an `Authorization: Bearer <key>` header. Any 2xx status is a success.
+pub struct HttpSms {
+    agent: ureq::Agent,
+    url: String,
+    api_key: String,
+    template_id: String,
+}
+
+impl HttpSms {
+    /// Creates the provider with the configured gateway and timeout
+    pub fn new(conf: &SmsConf, timeout: Duration) -> anyhow::Result<HttpSms> {
+        if conf.http_url.is_empty() {
+            return Err(anyhow::anyhow!("http_url must not be empty for the http sms provider"));
+        }
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .http_status_as_error(false)
+            .build()
+            .into();
+        Ok(HttpSms {
+            agent,
+            url: conf.http_url.clone(),
+            api_key: conf.http_api_key.clone(),
+            template_id: conf.template_id.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl SmsSender for HttpSms {
+    async fn send_code(&self, phone: &str, code: &str, ttl_minutes: i64) -> anyhow::Result<()> {
+        let agent = self.agent.clone();
+        let url = self.url.clone();
+        let api_key = self.api_key.clone();
+        let payload = json!({
+            "phone": phone,
+            "template_id": self.template_id,
+            "params": {"code": code, "minutes": ttl_minutes},
+        });
+
+        tokio::task::spawn_blocking(move || {
+            let mut request = agent.post(&url);
+            if !api_key.is_empty() {
+                request = request.header("Authorization", format!("Bearer {}", api_key));
+            }
+            let mut resp = request.send_json(&payload)?;
+            if !resp.status().is_success() {
+                let body = resp.body_mut().read_to_string().unwrap_or_default();
+                return Err(anyhow::anyhow!("sms gateway answered {} {}", resp.status(), body));
+            }
+            Ok(())
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use axum::Json;
+    use axum::Router;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use serde_json::Value;
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+
+    async fn gateway(
+        State(received): State<Received>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> StatusCode {
+        let auth = headers
+            .get("Authorization")
+            .map(|value| value.to_str().unwrap().to_string());
+        let failing = body["phone"] == "+8613900000000";
+        received.lock().unwrap().push((auth, body));
+        if failing {
+            StatusCode::BAD_GATEWAY
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_provider() {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/sms", post(gateway))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let conf = SmsConf {
+            provider: "http".to_string(),
+            http_url: format!("http://{}/sms", addr),
+            http_api_key: "key".to_string(),
+            template_id: "SMS_1".to_string(),
+            ..Default::default()
+        };
+        let sms = HttpSms::new(&conf, Duration::from_secs(5)).unwrap();
+        sms.send_code("+8613812345678", "123456", 10).await.unwrap();
+        assert!(sms.send_code("+8613900000000", "123456", 10).await.is_err());
+
+        let received = received.lock().unwrap();
+        let (auth, body) = &received[0];
+        assert_eq!(auth.as_deref(), Some("Bearer key"));
+        assert_eq!(body["phone"], "+8613812345678");
+        assert_eq!(body["template_id"], "SMS_1");
+        assert_eq!(body["params"]["code"], "123456");
+    }
+}
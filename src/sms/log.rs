@@ -0,0 +1,15 @@
+use async_trait::async_trait;
+use tracing::info;
+
+use crate::sms::SmsSender;
+
+/// Development provider writing codes to the application log instead of sending them
+pub struct LogSms;
+
+#[async_trait]
+impl SmsSender for LogSms {
+    async fn send_code(&self, phone: &str, code: &str, ttl_minutes: i64) -> anyhow::Result<()> {
+        info!("sms to {} code {} valid for {} minutes", phone, code, ttl_minutes);
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+pub mod http;
+pub mod log;
+pub mod phone;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use derivative::Derivative;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+
+use crate::core::rest::AppError;
+use crate::errors;
+use crate::sms::http::HttpSms;
+use crate::sms::log::LogSms;
+
+/// SMS configuration
+#[derive(Derivative, Deserialize, SmartDefault, Clone)]
+#[derivative(Debug)]
+#[serde(default)]
+pub struct SmsConf {
+    /// Provider, either "log" or "http"
+    /// - log: only writes the code to the application log, for development
+    /// - http: posts the code to the gateway at `http_url`
+    #[default("log")]
+    pub provider: String,
+
+    /// Country calling code added to numbers typed without one
+    #[default("86")]
+    pub default_country_code: String,
+
+    /// Maximum time spent sending one message, in seconds
+    #[default(5)]
+    pub timeout_secs: u64,
+
+    /// URL of the HTTP gateway
+    pub http_url: String,
+
+    /// Bearer token of the HTTP gateway
+    ///
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub http_api_key: String,
+
+    /// Template of the verification message registered at the provider
+    pub template_id: String,
+}
+
+impl SmsConf {
+    /// Builds the SMS client of the configured provider
+    ///
+    /// # Returns
+    /// - `Ok(SmsClient)` when the provider is known and its settings are valid
+    /// - `Err(anyhow::Error)` if the provider is unknown or misconfigured
+    pub fn build(&self) -> anyhow::Result<SmsClient> {
+        let timeout = Duration::from_secs(self.timeout_secs);
+        let sender: Arc<dyn SmsSender> = match self.provider.as_str() {
+            "log" => Arc::new(LogSms),
+            "http" => Arc::new(HttpSms::new(self, timeout)?),
+            other => return Err(anyhow::anyhow!("unsupported sms provider {}", other)),
+        };
+        Ok(SmsClient {
+            sender,
+            timeout,
+            default_country_code: self.default_country_code.clone(),
+        })
+    }
+}
+
+/// SMS provider sending verification codes
+#[async_trait]
+pub trait SmsSender: Send + Sync {
+    /// Sends a verification code to a normalized (E.164) phone number
+    async fn send_code(&self, phone: &str, code: &str, ttl_minutes: i64) -> anyhow::Result<()>;
+}
+
+/// SMS client shared by the whole application
+///
+/// Bounds every send by the configured timeout. Cloning is cheap.
+#[derive(Clone)]
+pub struct SmsClient {
+    sender: Arc<dyn SmsSender>,
+    timeout: Duration,
+    default_country_code: String,
+}
+
+impl SmsClient {
+    /// Validates and normalizes a phone number with the configured default country code
+    ///
+    /// # Returns
+    /// * `Result<String, AppError>` - E.164 number, `ErrPhoneInvalid` when it cannot be valid
+    pub fn normalize(&self, phone: &str) -> Result<String, AppError> {
+        phone::normalize_phone(phone, &self.default_country_code)
+    }
+
+    /// Country code of the numbers typed without one, e.g. `86`
+    pub fn default_country_code(&self) -> &str {
+        &self.default_country_code
+    }
+
+    /// Sends a verification code
+    ///
+    /// # Arguments
+    /// * `phone` - Normalized phone number
+    /// * `code` - Verification code
+    /// * `ttl_minutes` - Lifetime of the code shown in the message
+    ///
+    /// # Returns
+    /// * `Result<(), AppError>` - `ErrSmsTimeout` when the provider is too slow and
+    ///   `ErrSmsDelivery` for any other failure
+    pub async fn send_code(
+        &self,
+        phone: &str,
+        code: &str,
+        ttl_minutes: i64,
+    ) -> Result<(), AppError> {
+        match tokio::time::timeout(self.timeout, self.sender.send_code(phone, code, ttl_minutes))
+            .await
+        {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => {
+                error!("send sms to {} error {}", phone, err);
+                Err(errors::ErrSmsDelivery.clone())
+            }
+            Err(_) => {
+                error!("send sms to {} timeout after {:?}", phone, self.timeout);
+                Err(errors::ErrSmsTimeout.clone())
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// Normalizes a phone number to E.164 (`+<country code><national number>`)
+///
+/// Spaces, dashes, dots and parentheses are ignored and a `00` international prefix is read as
+/// `+`. Numbers without a country code get `default_country_code`. Mainland China (+86) numbers
+/// must be 11 digits mobiles starting with 1, other countries are only checked against the E.164
+/// length limits.
+///
+/// # Arguments
+/// * `input` - Phone number typed by the user
+/// * `default_country_code` - Country calling code used for national numbers, e.g. "86"
+///
+/// # Returns
+/// * `Result<String, AppError>` - The normalized number, `ErrPhoneInvalid` if it cannot be valid
+pub fn normalize_phone(input: &str, default_country_code: &str) -> Result<String, AppError> {
+    let compact: String = input
+        .chars()
+        .filter(|ch| !matches!(ch, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let international = if let Some(rest) = compact.strip_prefix('+') {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix("00") {
+        rest.to_string()
+    } else {
+        let national = compact.trim_start_matches('0');
+        format!("{}{}", default_country_code, national)
+    };
+
+    if international.is_empty() || !international.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(errors::ErrPhoneInvalid.clone());
+    }
+    if let Some(national) = international.strip_prefix("86") {
+        let bytes = national.as_bytes();
+        let mobile = bytes.len() == 11 && bytes[0] == b'1' && (b'3'..=b'9').contains(&bytes[1]);
+        if !mobile {
+            return Err(errors::ErrPhoneInvalid.clone());
+        }
+    } else if !(8..=15).contains(&international.len()) || international.starts_with('0') {
+        return Err(errors::ErrPhoneInvalid.clone());
+    }
+    Ok(format!("+{}", international))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_cn_phone() {
+        for input in [
+            "13812345678",
+            "+86 138 1234 5678",
+            "0086-138-1234-5678",
+            "+86(138)12345678",
+        ] {
+            assert_eq!(normalize_phone(input, "86").unwrap(), "+8613812345678");
+        }
+        assert!(normalize_phone("12812345678", "86").is_err());
+        assert!(normalize_phone("1381234567", "86").is_err());
+        assert!(normalize_phone("+86 010 1234 5678", "86").is_err());
+    }
+
+    #[test]
+    fn test_normalize_other_phone() {
+        assert_eq!(normalize_phone("+1 (415) 555-2671", "86").unwrap(), "+14155552671");
+        assert_eq!(normalize_phone("020 7946 0018", "44").unwrap(), "+442079460018");
+        assert!(normalize_phone("+1 415 abc", "86").is_err());
+        assert!(normalize_phone("+1234", "86").is_err());
+        assert!(normalize_phone("", "86").is_err());
+    }
+}
@@ -1,9 +1,19 @@
+use std::net::SocketAddr;
+
 use tracing::info;
 use tracing_appender::non_blocking::WorkerGuard;
 
 use crate::conf::AppConf;
+use crate::core::lockout::Lockouts;
+use crate::core::reset_token::ResetTokens;
 use crate::core::state::AppState;
+use crate::core::totp::TwoFactor;
+use crate::core::verify_code::VerifyCodes;
+use crate::oidc::Oidc;
 use crate::routers;
+use crate::search;
+use crate::services::account;
+use crate::wechat::WeChatClient;
 
 /// Server context that holds application configuration and state
 ///
@@ -14,6 +24,8 @@ pub struct ServeContext {
     cfg: AppConf,
     /// Application state instance containing database connections and other shared resources
     app_state: AppState,
+    /// Keeps the non-blocking log writer alive for the lifetime of the server
+    #[allow(unused)]
     work_guard: WorkerGuard,
 }
 
@@ -34,11 +46,96 @@ impl ServeContext {
             .init_pool()
             .await
             .map_err(|err| anyhow::anyhow!("build redis client error {}", err))?;
-        let wechat = cfg.wechat.clone();
+        // build access token keys
+        let jwt = cfg
+            .jwt
+            .build_keys()
+            .map_err(|err| anyhow::anyhow!("build jwt keys error {}", err))?;
+
+        // build password hasher
+        let passwords = cfg
+            .password
+            .build()
+            .map_err(|err| anyhow::anyhow!("build password hasher error {}", err))?;
+
+        // build data cipher
+        let cipher = cfg
+            .crypto
+            .build()
+            .map_err(|err| anyhow::anyhow!("build data cipher error {}", err))?;
+
+        // build cursor signer
+        let cursors = cfg
+            .cursor
+            .build()
+            .map_err(|err| anyhow::anyhow!("build cursor signer error {}", err))?;
+
+        // build mail client
+        let mail = cfg
+            .mail
+            .build()
+            .map_err(|err| anyhow::anyhow!("build mail client error {}", err))?;
+
+        // build sms client
+        let sms = cfg
+            .sms
+            .build()
+            .map_err(|err| anyhow::anyhow!("build sms client error {}", err))?;
+
+        // build blob store
+        let blobs = cfg
+            .blob
+            .build()
+            .map_err(|err| anyhow::anyhow!("build blob store error {}", err))?;
+
+        // build search index
+        let search = cfg
+            .search
+            .build(&db_conn)
+            .map_err(|err| anyhow::anyhow!("build search index error {}", err))?;
+
+        // parse trusted proxies
+        let proxies = cfg
+            .client_ip
+            .build()
+            .map_err(|err| anyhow::anyhow!("build trusted proxies error {}", err))?;
+
+        let wechat = WeChatClient::new(cfg.wechat.clone());
+        let verify_codes = VerifyCodes::new(cfg.verify_code.clone());
+        let reset_tokens = ResetTokens::new(cfg.password_reset.clone());
+        let two_factor = TwoFactor::new(cfg.totp.clone());
+        let lockouts = Lockouts::new(cfg.lockout.clone());
+        let oidc = Oidc::new(cfg.oidc.clone());
+        let account = cfg.account.clone();
+        let login_event = cfg.login_event.clone();
+        let bulk = cfg.bulk.clone();
+        let avatar = cfg.avatar.clone();
         let res = ServeContext {
             work_guard: guard,
             cfg,
-            app_state: AppState::new(db_conn.clone(), redis_client.clone(), wechat),
+            app_state: AppState::new(
+                db_conn.clone(),
+                redis_client.clone(),
+                wechat,
+                jwt,
+                passwords,
+                cipher,
+                verify_codes,
+                reset_tokens,
+                two_factor,
+                lockouts,
+                cursors,
+                oidc,
+                mail,
+                sms,
+                account,
+                login_event,
+                bulk,
+                blobs,
+                avatar,
+                search,
+                proxies,
+            ),
         };
         Ok(res)
     }
@@ -51,7 +148,8 @@ impl ServeContext {
     /// 1. Initializes the logging system
     /// 2. Creates the application router with the app state
     /// 3. Builds the HTTP listener
-    /// 4. Starts serving HTTP requests
+    /// 4. Starts the background tasks
+    /// 5. Starts serving HTTP requests
     ///
     /// # Returns
     /// - `Ok(())` if the server starts successfully
@@ -60,7 +158,13 @@ impl ServeContext {
         // Create application router
         let app = routers::app_routers(self.app_state.clone());
         let listener = self.cfg.http.build_listener().await?;
-        axum::serve::serve(listener, app).await?;
+        // erase accounts whose deletion grace period is over
+        account::spawn_purge(self.app_state.clone());
+        // fill an in-process search index from the table
+        search::spawn_rebuild(self.app_state.search.clone(), self.app_state.get_conn());
+        // keep the peer address for `ClientIp`
+        axum::serve::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
         println!("Server started successfully");
         info!("Server started successfully");
         Ok(())
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+
+use crate::core::session::SessionRecord;
+use crate::models::api_key::ApiKey;
+use crate::models::identity::UserIdentity;
+use crate::models::login_event::LoginEvent;
+use crate::models::rbac::Role;
+use crate::models::user::UserInfo;
+
+/// Response structure for an account deletion request
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct DeleteAccountResponse {
+    /// Timestamp when the account is erased (Unix timestamp), logging in before cancels it
+    pub purge_at: i64,
+}
+
+/// Format of a personal data export
+#[derive(Debug, Deserialize, SmartDefault, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// A single JSON document
+    #[default]
+    Json,
+    /// A ZIP archive with one JSON file per kind of data
+    Zip,
+}
+
+/// Query parameters of a personal data export
+#[derive(Debug, Deserialize, SmartDefault)]
+pub struct ExportRequest {
+    /// Format of the archive, `json` by default
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Everything stored about a user, returned by `/user/me/export`
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    /// Timestamp of the export (Unix timestamp)
+    pub exported_at: i64,
+    /// Profile of the user, without password hashes
+    pub user: UserInfo,
+    /// Roles granted to the user
+    pub roles: Vec<Role>,
+    /// API keys acting for the user, without key hashes
+    pub api_keys: Vec<ApiKey>,
+    /// Provider accounts linked to the user
+    pub identities: Vec<UserIdentity>,
+    /// Logged-in devices
+    pub sessions: Vec<SessionRecord>,
+    /// Login history
+    pub logins: Vec<LoginEvent>,
+}
@@ -0,0 +1,28 @@
+use serde::Serialize;
+
+/// Response of `POST /user/me/avatar`, sent with the `ETag` of the updated profile
+#[derive(Debug, Serialize)]
+pub struct UploadAvatarResponse {
+    /// URL of the stored picture, now the avatar of the user
+    pub avatar: String,
+    /// Hex SHA-256 of the stored picture, which the file is named after
+    pub sha256: String,
+    /// Size of the stored picture in bytes
+    pub size: u64,
+    /// Type of the picture recognized from its content
+    pub content_type: String,
+    /// Square thumbnails of the picture, smallest first
+    pub variants: Vec<AvatarVariant>,
+    /// Version of the profile after the update
+    #[serde(skip_serializing)]
+    pub version: i64,
+}
+
+/// Square thumbnail generated from an uploaded avatar
+#[derive(Debug, Serialize)]
+pub struct AvatarVariant {
+    /// Width and height in pixels
+    pub size: u32,
+    /// URL of the stored thumbnail
+    pub url: String,
+}
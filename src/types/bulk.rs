@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+use crate::models::user::UserInfo;
+
+/// Format of a bulk import or export
+#[derive(Debug, Deserialize, SmartDefault, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkFormat {
+    /// Comma separated values with a header line
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl BulkFormat {
+    /// MIME type of the format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "text/csv; charset=utf-8",
+            BulkFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// File extension of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "csv",
+            BulkFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// Guesses the format from a file extension, `.jsonl` being NDJSON too
+    pub fn from_path(path: &Path) -> Option<BulkFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(BulkFormat::Csv),
+            "ndjson" | "jsonl" => Some(BulkFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for BulkFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(BulkFormat::Csv),
+            "ndjson" | "jsonl" => Ok(BulkFormat::Ndjson),
+            other => Err(format!("unknown format {}, expected csv or ndjson", other)),
+        }
+    }
+}
+
+/// Query parameters of a bulk user import
+#[derive(Debug, Deserialize, SmartDefault)]
+pub struct ImportUsersRequest {
+    /// Format of the request body, `csv` by default
+    #[serde(default)]
+    pub format: BulkFormat,
+    /// Only validates the rows and reports the errors, nothing is inserted
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// One user of an import file
+///
+/// Empty CSV fields are missing values. Users need at least one way to log in: an email, a phone
+/// number or a WeChat Open ID. Passwords are imported as the hash of the old system, Argon2 PHC
+/// strings or legacy salted digests, never in clear.
+#[derive(Debug, Deserialize, Validate, SmartDefault)]
+pub struct UserImportRow {
+    /// Display name
+    #[validate(length(min = 1, max = 50))]
+    pub nick_name: String,
+
+    /// URL of the profile picture
+    #[validate(length(max = 500))]
+    pub avatar: Option<String>,
+
+    /// Personal signature or bio
+    #[validate(length(max = 500))]
+    pub signature: Option<String>,
+
+    /// Age (0-150)
+    #[validate(range(max = 150))]
+    pub age: Option<u8>,
+
+    /// Phone number, the configured default country code is assumed when none is given
+    #[validate(length(min = 5, max = 32))]
+    pub phone: Option<String>,
+
+    /// Email address
+    #[validate(email)]
+    pub email: Option<String>,
+
+    /// WeChat Open ID
+    #[validate(length(max = 100))]
+    pub wx_open_id: Option<String>,
+
+    /// Password hash of the old system
+    #[validate(length(max = 255))]
+    pub password_hash: Option<String>,
+
+    /// Salt of a legacy password hash
+    #[validate(length(max = 32))]
+    pub salt: Option<String>,
+
+    /// Timestamp when the user registered in the old system (Unix timestamp), now by default
+    #[validate(range(min = 0))]
+    pub created_at: Option<i64>,
+}
+
+/// Validation error of one row of an import
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// Line of the row in the file, 1 for the first line
+    pub line: u64,
+    /// What is wrong with the row
+    pub message: String,
+}
+
+/// Outcome of a bulk user import
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct ImportReport {
+    /// Rows read
+    pub total: u64,
+    /// Users inserted, 0 for a dry run
+    pub imported: u64,
+    /// Rows rejected
+    pub failed: u64,
+    /// Whether nothing was inserted
+    pub dry_run: bool,
+    /// Errors of the first rejected rows, `failed` counts them all
+    pub errors: Vec<RowError>,
+    /// Whether the import stopped before the end of the input
+    pub truncated: bool,
+}
+
+/// Query parameters of a bulk user export
+#[derive(Debug, Deserialize, Validate, SmartDefault)]
+pub struct ExportUsersRequest {
+    /// Format of the export, `csv` by default
+    #[serde(default)]
+    pub format: BulkFormat,
+    /// Part of the nickname the users must contain
+    #[validate(length(max = 64))]
+    pub q: Option<String>,
+    /// Only users registered at or after this time (Unix timestamp)
+    pub created_from: Option<i64>,
+    /// Only users registered before this time (Unix timestamp)
+    pub created_to: Option<i64>,
+    /// Also exports soft-deleted users
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+/// One user of an export, without credentials
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct UserRecord {
+    pub id: i64,
+    pub nick_name: String,
+    pub avatar: String,
+    pub signature: String,
+    pub age: u8,
+    pub phone: String,
+    pub email: Option<String>,
+    pub wx_open_id: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub deleted_at: i64,
+}
+
+impl UserRecord {
+    /// CSV header of the records, in field order
+    pub const COLUMNS: [&'static str; 11] = [
+        "id",
+        "nick_name",
+        "avatar",
+        "signature",
+        "age",
+        "phone",
+        "email",
+        "wx_open_id",
+        "created_at",
+        "updated_at",
+        "deleted_at",
+    ];
+}
+
+impl From<UserInfo> for UserRecord {
+    fn from(user: UserInfo) -> Self {
+        UserRecord {
+            id: user.id,
+            nick_name: user.nick_name,
+            avatar: user.avatar,
+            signature: user.signature,
+            age: user.age,
+            phone: user.phone,
+            email: user.email,
+            wx_open_id: user.wx_open_id,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            deleted_at: user.deleted_at,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+/// Query parameters of an unlock
+#[derive(Debug, Deserialize, Validate, SmartDefault)]
+pub struct UnlockRequest {
+    /// Client IP to unlock along with the account
+    #[validate(ip)]
+    pub ip: Option<String>,
+}
+
+/// Response structure for an unlock
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct UnlockResponse {
+    /// Whether the account or the IP was delayed or locked
+    pub unlocked: bool,
+}
@@ -0,0 +1,7 @@
+use crate::core::page::Page;
+use crate::models::login_event::LoginEvent;
+use crate::types::user::Paginator;
+
+pub type LoginEventListRequest = Paginator;
+
+pub type LoginEventListResponse = Page<LoginEvent>;
@@ -1,2 +1,12 @@
+pub mod account;
+pub mod avatar;
+pub mod bulk;
 pub mod foo;
+pub mod lockout;
+pub mod login_event;
+pub mod oidc;
+pub mod rbac;
+pub mod session;
+pub mod token;
+pub mod two_factor;
 pub mod user;
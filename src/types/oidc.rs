@@ -0,0 +1,69 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+use crate::models::identity::UserIdentity;
+use crate::types::user::LoginResponse;
+
+/// Path parameters addressing a login provider
+#[derive(Debug, Deserialize)]
+pub struct ProviderPath {
+    /// Name of the provider in the configuration
+    pub provider: String,
+}
+
+/// Path parameters addressing a linked identity
+#[derive(Debug, Deserialize)]
+pub struct IdentityIdPath {
+    /// Id of the identity
+    pub id: i64,
+}
+
+/// Configured login providers
+#[derive(Debug, Serialize)]
+pub struct OidcProvidersResponse {
+    /// Names of the providers, to use in `/user/oidc/{provider}/...`
+    pub providers: Vec<String>,
+}
+
+/// Start of a provider login
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorizeResponse {
+    /// URL of the provider login page the browser must be sent to
+    pub authorize_url: String,
+    /// Value the provider sends back to the redirect URI along with the code
+    pub state: String,
+    /// Lifetime of the login in seconds
+    pub expires_in: i64,
+}
+
+/// Code and state received by the redirect URI
+#[derive(Debug, Deserialize, Validate)]
+pub struct OidcCallbackRequest {
+    /// Authorization code
+    #[validate(length(min = 1, max = 2048))]
+    pub code: String,
+
+    /// State returned by the authorize step
+    #[validate(length(min = 1, max = 128))]
+    pub state: String,
+}
+
+/// End of a provider login or link
+#[derive(Debug, Serialize)]
+pub struct OidcCallbackResponse {
+    /// Tokens or two-factor challenge of a login, absent when an identity was linked
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub login: Option<LoginResponse>,
+    /// Identity used by the login, or newly linked
+    pub identity: UserIdentity,
+}
+
+pub type IdentityListResponse = Vec<UserIdentity>;
+
+/// Response structure for identity unlinking
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct UnlinkIdentityResponse {
+    // Response placeholder for identity unlinking
+}
@@ -0,0 +1,40 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+use crate::models::rbac::Role;
+
+/// Path parameters addressing a user
+#[derive(Debug, Deserialize)]
+pub struct UserIdPath {
+    /// Id of the user
+    pub id: i64,
+}
+
+/// Path parameters addressing a role of a user
+#[derive(Debug, Deserialize)]
+pub struct UserRolePath {
+    /// Id of the user
+    pub id: i64,
+    /// Name of the role
+    pub role: String,
+}
+
+/// Request structure for assigning a role to a user
+#[derive(Debug, Deserialize, Validate)]
+pub struct AssignRoleRequest {
+    /// Name of the role, e.g. `admin`
+    #[validate(length(min = 1, max = 64))]
+    pub role: String,
+}
+
+pub type RoleListResponse = Vec<Role>;
+
+pub type UserRolesResponse = Vec<Role>;
+
+/// Response structure for role assignment and removal
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct UpdateRoleResponse {
+    // Response placeholder for role assignment
+}
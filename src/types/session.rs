@@ -0,0 +1,37 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+
+/// Path parameters addressing a session
+#[derive(Debug, Deserialize)]
+pub struct SessionIdPath {
+    /// Id of the session
+    pub id: String,
+}
+
+/// A logged-in device of the caller
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    /// Id of the session, used to revoke it
+    pub id: String,
+    /// Device name
+    pub device: String,
+    /// IP address of the login
+    pub ip: String,
+    /// User agent of the login
+    pub user_agent: String,
+    /// Timestamp of the login (Unix timestamp)
+    pub created_at: i64,
+    /// Timestamp of the last request, updated at most once a minute (Unix timestamp)
+    pub last_seen_at: i64,
+    /// Whether this is the session of the current access token
+    pub current: bool,
+}
+
+pub type SessionListResponse = Vec<SessionInfo>;
+
+/// Response structure for session revocation
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct RevokeSessionResponse {
+    // Response placeholder for session revocation
+}
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+/// Access and refresh token pair returned by every login
+#[derive(Serialize, SmartDefault, Debug)]
+pub struct TokenPair {
+    /// Access token, sent back as `Authorization: Bearer <auth>`
+    pub auth: String,
+    /// Refresh token, exchanged at `/user/token/refresh` for a new pair
+    ///
+    /// It can be used only once, replaying it revokes the whole session.
+    pub refresh_token: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+}
+
+/// Refresh token request structure
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshTokenRequest {
+    /// Refresh token returned by the last login or refresh
+    #[validate(length(min = 1, max = 128))]
+    pub refresh_token: String,
+}
+
+pub type RefreshTokenResponse = TokenPair;
+
+/// Logout response structure
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct LogoutResponse {
+    // Response placeholder for logout
+}
@@ -0,0 +1,61 @@
+use serde::Deserialize;
+use serde::Serialize;
+use smart_default::SmartDefault;
+use validator::Validate;
+
+use crate::types::token::TokenPair;
+
+/// Response structure after starting a TOTP enrollment
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct EnrollTotpResponse {
+    /// Base32 secret, for authenticator apps that cannot scan QR codes
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code
+    pub otpauth_uri: String,
+}
+
+/// Request structure carrying a TOTP or recovery code of the caller
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorCodeRequest {
+    /// 6 digit TOTP code, or a recovery code where accepted
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+/// Response structure carrying freshly generated recovery codes
+///
+/// The codes are shown only once, only their hashes are stored.
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct RecoveryCodesResponse {
+    /// One-time codes replacing the authenticator when it is lost
+    pub recovery_codes: Vec<String>,
+}
+
+/// Response structure after disabling two-factor authentication
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct DisableTotpResponse {
+    // Response placeholder for disabling two-factor authentication
+}
+
+/// Second step required by a password login
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct TwoFactorChallenge {
+    /// Challenge to send to `/user/2fa/login` with a TOTP or recovery code
+    pub challenge: String,
+    /// Seconds until the challenge expires
+    pub expires_in: i64,
+}
+
+/// Request structure for the second step of a login
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorLoginRequest {
+    /// Challenge returned by the password step
+    #[validate(length(min = 1, max = 128))]
+    pub challenge: String,
+
+    /// 6 digit TOTP code or a recovery code
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+pub type TwoFactorLoginResponse = TokenPair;
@@ -3,7 +3,13 @@ use serde::Serialize;
 use smart_default::SmartDefault;
 use validator::Validate;
 
+use crate::core::page::CursorPage;
+use crate::core::page::Page;
+use crate::core::patch::Patch;
+use crate::core::patch::not_null;
 use crate::models::user::UserInfo;
+use crate::types::token::TokenPair;
+use crate::types::two_factor::TwoFactorChallenge;
 
 /// WeChat mini program login request
 #[allow(unused)]
@@ -17,14 +23,6 @@ pub struct WxMiniLoginRequest {
     pub code: String,
 }
 
-/// WeChat mini program login response
-#[allow(unused)]
-#[derive(Serialize, SmartDefault)]
-pub struct WxMiniLoginResponse {
-    /// Authentication token for login
-    pub auth: String,
-}
-
 /// Email login request structure
 #[derive(Debug, Deserialize, Validate)]
 pub struct EmailLoginRequest {
@@ -33,11 +31,67 @@ pub struct EmailLoginRequest {
     pub email: String,
 
     /// User's password
-    #[validate(length(min = 6, max = 20))]
+    #[validate(length(min = 6, max = 64))]
     pub password: String,
 }
 
-pub type EmailLoginResponse = WxMiniLoginResponse;
+/// Response of login methods subject to two-factor authentication
+///
+/// Accounts with two-factor authentication get a `two_factor` challenge instead of tokens.
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct LoginResponse {
+    /// Access and refresh tokens, when the login is complete
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<TokenPair>,
+    /// Challenge of the second step, when a TOTP code is required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_factor: Option<TwoFactorChallenge>,
+}
+
+/// Email login response
+pub type EmailLoginResponse = LoginResponse;
+
+/// WeChat mini program login response
+pub type WxMiniLoginResponse = LoginResponse;
+
+/// Email registration request structure
+#[derive(Debug, Deserialize, Validate)]
+pub struct EmailRegisterRequest {
+    /// User's email address, used as login name
+    #[validate(email)]
+    pub email: String,
+
+    /// Validation code received by email after calling `/user/email/register/pre`
+    #[validate(length(min = 1, max = 10))]
+    pub valid_code: String,
+
+    /// User's password
+    #[validate(length(min = 8, max = 64))]
+    pub password: String,
+
+    /// Display name, defaults to the local part of the email
+    #[validate(length(min = 1, max = 50))]
+    pub nick_name: Option<String>,
+}
+
+pub type EmailRegisterResponse = TokenPair;
+
+/// Request structure for sending the validation code of an email registration
+#[derive(Debug, Deserialize, Validate)]
+pub struct PreEmailRegisterRequest {
+    /// Email address the new account is registered with
+    #[validate(email)]
+    pub email: String,
+}
+
+/// Response structure after sending the validation code of an email registration
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct PreEmailRegisterResponse {
+    /// Seconds until the validation code expires
+    pub expires_in: i64,
+    /// Seconds until another validation code can be requested
+    pub resend_in: i64,
+}
 
 /// Request structure for binding email to user account
 #[derive(Debug, Deserialize, Validate)]
@@ -46,7 +100,7 @@ pub struct BindEmailRequest {
     #[validate(email)]
     pub email: String,
 
-    /// Validation code for email binding
+    /// Validation code received by email after calling `/user/email/pre`
     #[validate(length(min = 1, max = 10))]
     pub valid_code: String,
 }
@@ -68,23 +122,182 @@ pub struct PreBindEmailRequest {
 /// Pre-bind email response after sending validation code
 #[derive(Debug, Serialize, SmartDefault)]
 pub struct PreBindEmailResponse {
-    // TODO: Add response fields for pre-bind email
+    /// Seconds until the validation code expires
+    pub expires_in: i64,
+    /// Seconds until another validation code can be requested
+    pub resend_in: i64,
+}
+
+/// Request structure for sending an SMS login code
+#[derive(Debug, Deserialize, Validate)]
+pub struct SmsPreRequest {
+    /// Phone number, `+86` is assumed when no country code is given
+    #[validate(length(min = 5, max = 32))]
+    pub phone: String,
 }
 
+/// Response structure after sending an SMS login code
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct SmsPreResponse {
+    /// Seconds until the code expires
+    pub expires_in: i64,
+    /// Seconds until another code can be requested
+    pub resend_in: i64,
+}
+
+/// Request structure for logging in with phone number and SMS code
+#[derive(Debug, Deserialize, Validate)]
+pub struct SmsLoginRequest {
+    /// Phone number the code was sent to
+    #[validate(length(min = 5, max = 32))]
+    pub phone: String,
+
+    /// Code received by SMS after calling `/user/sms/pre`
+    #[validate(length(min = 1, max = 10))]
+    pub code: String,
+}
+
+pub type SmsLoginResponse = LoginResponse;
+
+/// Request structure for requesting a password reset mail
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    /// Email address of the account
+    #[validate(email)]
+    pub email: String,
+}
+
+/// Response structure after requesting a password reset mail
+///
+/// The response is the same whether the email belongs to an account or not.
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct ForgotPasswordResponse {
+    /// Seconds the link in the mail stays valid
+    pub expires_in: i64,
+}
+
+/// Request structure for choosing a new password with a reset token
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    /// Token received by mail after calling `/user/password/forgot`
+    #[validate(length(min = 1, max = 128))]
+    pub token: String,
+
+    /// New password
+    #[validate(length(min = 8, max = 64))]
+    pub password: String,
+}
+
+/// Response structure after a password reset
+#[derive(Debug, Serialize, SmartDefault)]
+pub struct ResetPasswordResponse {
+    // Response placeholder for password reset
+}
+
+/// Request structure for changing the caller's profile
+///
+/// Fields left out are unchanged, `null` resets a field to its default. Email, phone, password and
+/// avatar have their own flows, sending them is an error.
+#[derive(Debug, Default, Deserialize, Validate)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateProfileRequest {
+    /// Display name, cannot be reset
+    #[validate(length(min = 1, max = 50), custom(function = "not_null"))]
+    pub nick_name: Patch<String>,
+
+    /// Personal signature or bio
+    #[validate(length(max = 500))]
+    pub signature: Patch<String>,
+
+    /// Age (0-150)
+    #[validate(range(max = 150))]
+    pub age: Patch<u8>,
+}
+
+pub type UpdateProfileResponse = UserInfo;
+
 /// Pagination structure for list requests
 #[derive(Debug, Validate, Deserialize)]
 pub struct Paginator {
     /// Number of items per page (1-50)
     #[validate(range(min = 1, max = 50))]
     pub page_size: usize,
-    /// Current page number (starts from 1)
-    #[validate(range(min = 1))]
+    /// Current page number (1-10000)
+    #[validate(range(min = 1, max = 10000))]
     pub page_no: usize,
 }
 
-pub type UsersListRequest = Paginator;
+/// Query parameters of the user listing
+#[derive(Debug, Validate, Deserialize)]
+pub struct UsersListRequest {
+    /// Number of items per page (1-50)
+    #[validate(range(min = 1, max = 50))]
+    pub page_size: usize,
+    /// Current page number (1-10000), ignored in cursor mode
+    #[serde(default = "first_page")]
+    #[validate(range(min = 1, max = 10000))]
+    pub page_no: usize,
+    /// Text searched in nicknames and signatures, users are then ranked by relevance; lists
+    /// every user when empty
+    #[validate(length(max = 64))]
+    pub q: Option<String>,
+    /// Cursor mode: `next_cursor` of the previous slice, empty for the first slice
+    #[validate(length(max = 64))]
+    pub cursor: Option<String>,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+pub type UsersListResponse = Page<UserInfo>;
+
+pub type UsersCursorResponse = CursorPage<UserInfo>;
+
+/// Query parameters of the user search
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchUsersRequest {
+    /// Text searched in nicknames and signatures
+    #[validate(length(min = 1, max = 64))]
+    pub q: String,
+    /// Number of items per page (1-50)
+    #[validate(range(min = 1, max = 50))]
+    pub page_size: usize,
+    /// Current page number (1-10000)
+    #[serde(default = "first_page")]
+    #[validate(range(min = 1, max = 10000))]
+    pub page_no: usize,
+}
+
+/// Fields of a found user containing the searched text, HTML-escaped with the matching parts
+/// wrapped in `<em>` tags
+#[derive(Debug, Default, Serialize)]
+pub struct SearchHighlight {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nick_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// A user matching a search
+#[derive(Debug, Serialize)]
+pub struct UserSearchHit {
+    #[serde(flatten)]
+    pub user: UserInfo,
+    /// Relevance of the user, only comparable within one search
+    pub score: f64,
+    pub highlight: SearchHighlight,
+}
+
+pub type SearchUsersResponse = Page<UserSearchHit>;
+
 pub type BooksListRequest = Paginator;
 
+/// Query parameters of the listing of deleted users
+pub type DeletedUsersListRequest = Paginator;
+
+pub type DeletedUsersListResponse = Page<UserInfo>;
+
 /// Request structure for getting user by ID
 #[derive(Deserialize, Debug)]
 pub struct ByUserIdRequest {
@@ -94,6 +307,8 @@ pub struct ByUserIdRequest {
 
 pub type ByUserIdResponse = UserInfo;
 
+pub type RestoreUserResponse = UserInfo;
+
 /// Request structure for getting random user
 #[derive(Deserialize, Debug)]
 pub struct RandomUserRequest {
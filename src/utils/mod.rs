@@ -41,6 +41,42 @@ pub fn gen_valid_code(len: usize) -> String {
     code
 }
 
+/// 生成指定字节数的随机十六进制令牌
+/// Generates a random hex token from the given number of random bytes
+///
+/// # Arguments
+/// * `bytes` - 随机字节数 / Number of random bytes, the token is twice as long
+///
+/// # Returns
+/// * `String` - 十六进制令牌 / Lower case hex token
+pub fn gen_token(bytes: usize) -> String {
+    (0..bytes)
+        .map(|_| format!("{:02x}", random::<u8>()))
+        .collect()
+}
+
+/// 计算字符串的 SHA256 十六进制摘要
+/// Returns the hex SHA256 digest of a string, used to store tokens without their plain value
+pub fn sha256_hex(data: &str) -> String {
+    let mut hasher = HashAlgorithm::SHA256.hasher();
+    hasher.update(data.as_bytes());
+    hasher.finalize()
+}
+
+/// 按 RFC 3986 对 URL 组件进行百分号编码，仅保留非保留字符
+/// Percent-encodes a URL component, keeping only RFC 3986 unreserved characters
+pub fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum HashAlgorithm {
     MD5,
@@ -141,20 +177,28 @@ async fn test_gen_valid_code() {
     println!("{valid_code:?}");
 }
 
+#[tokio::test]
+async fn test_gen_token() {
+    let token = gen_token(32);
+    assert_eq!(token.len(), 64);
+    assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(token, gen_token(32));
+}
+
 #[tokio::test]
 async fn test_file_digest() {
     use manifest_dir_macros::file_path;
     let path = Path::new(file_path!("tests/index.txt"));
-    let digest = file_digest(&path, HashAlgorithm::SHA1).unwrap();
+    let digest = file_digest(path, HashAlgorithm::SHA1).unwrap();
     println!("sha1:{}", digest);
     assert_eq!(digest, "65aed31e2af181f131e7091301b97d0b8379bdf3");
-    let digest = file_digest(&path, HashAlgorithm::SHA512).unwrap();
+    let digest = file_digest(path, HashAlgorithm::SHA512).unwrap();
     println!("sha256:{}", digest);
     assert_eq!(
         digest,
         "d074479576ba7b335c9178bfdd85a9f0f32084faa823b83922d638c3fe3dcca7ac836783fbf6f14cf253c915b46ce7d9974814c28063cad74e987131652dcf7f"
     );
-    let digest = file_digest(&path, HashAlgorithm::MD5).unwrap();
+    let digest = file_digest(path, HashAlgorithm::MD5).unwrap();
     println!("md5:{}", digest);
     assert_eq!(digest, "deab55458837fbe4ec2a6e61690fe998");
 }
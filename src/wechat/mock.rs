@@ -0,0 +1,95 @@
+//! Local stand-in for the WeChat API, used by tests and `examples/mock_wechat_server.rs`
+//!
+//! `GET /sns/jscode2session` answers like WeChat does:
+//! - a wrong `appid`/`secret` returns errcode `40013`/`40125`
+//! - codes starting with `invalid` return `40029`, `used` returns `40163`, `limit` returns `45011`,
+//!   `risk` returns `40226` and `busy` returns `-1`
+//! - any other code logs in as `mock_openid_{code}`, so the same code always maps to the same user
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Json;
+use axum::Router;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::routing::get;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::utils;
+
+#[derive(Clone)]
+struct MockState {
+    appid: Arc<String>,
+    secret: Arc<String>,
+}
+
+#[derive(Deserialize)]
+struct Code2SessionQuery {
+    #[serde(default)]
+    appid: String,
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    js_code: String,
+}
+
+/// Builds the mock WeChat API router accepting the given credentials
+pub fn router(appid: &str, secret: &str) -> Router {
+    Router::new()
+        .route("/sns/jscode2session", get(code2session))
+        .with_state(MockState {
+            appid: Arc::new(appid.to_string()),
+            secret: Arc::new(secret.to_string()),
+        })
+}
+
+/// Serves the mock WeChat API on a random local port in the background
+///
+/// # Returns
+/// * `anyhow::Result<SocketAddr>` - The address to use as `wechat.base_url`
+pub async fn spawn(appid: &str, secret: &str) -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let app = router(appid, secret);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(addr)
+}
+
+async fn code2session(
+    State(state): State<MockState>,
+    Query(query): Query<Code2SessionQuery>,
+) -> Json<Value> {
+    if query.appid != *state.appid {
+        return Json(json!({"errcode": 40013, "errmsg": "invalid appid"}));
+    }
+    if query.secret != *state.secret {
+        return Json(json!({"errcode": 40125, "errmsg": "invalid appsecret"}));
+    }
+
+    let code = query.js_code.as_str();
+    let errcode = if code.is_empty() || code.starts_with("invalid") {
+        Some((40029, "invalid code"))
+    } else if code.starts_with("used") {
+        Some((40163, "code been used"))
+    } else if code.starts_with("limit") {
+        Some((45011, "api minute-quota reach limit"))
+    } else if code.starts_with("risk") {
+        Some((40226, "high risk user"))
+    } else if code.starts_with("busy") {
+        Some((-1, "system error"))
+    } else {
+        None
+    };
+    if let Some((errcode, errmsg)) = errcode {
+        return Json(json!({"errcode": errcode, "errmsg": errmsg}));
+    }
+
+    Json(json!({
+        "openid": format!("mock_openid_{}", code),
+        "session_key": &utils::sha256_hex(code)[..24],
+    }))
+}
@@ -0,0 +1,245 @@
+pub mod mock;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use derivative::Derivative;
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use tracing::error;
+use tracing::warn;
+
+use crate::core::rest::AppError;
+use crate::errors;
+
+/// WeChat mini program configuration
+#[derive(Deserialize, Derivative, SmartDefault, Clone)]
+#[derivative(Debug)]
+pub struct WeChatConf {
+    /// Mini program AppID
+    pub appid: String,
+
+    /// Mini program AppSecret
+    ///
+    /// This field is ignored in Debug implementation for security reasons
+    #[derivative(Debug = "ignore")]
+    pub secret: String,
+
+    /// Base URL of the WeChat API
+    ///
+    /// Point it at the mock server (`cargo run --example mock_wechat_server`) to test the login
+    /// flow offline.
+    #[default("https://api.weixin.qq.com")]
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+
+    /// Timeout of a WeChat API call in seconds
+    #[default(5)]
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// How long the encrypted `session_key` is kept in Redis, in seconds
+    #[default(259200)]
+    #[serde(default = "default_session_key_ttl_secs")]
+    pub session_key_ttl_secs: u64,
+}
+
+fn default_base_url() -> String {
+    WeChatConf::default().base_url
+}
+
+fn default_timeout_secs() -> u64 {
+    WeChatConf::default().timeout_secs
+}
+
+fn default_session_key_ttl_secs() -> u64 {
+    WeChatConf::default().session_key_ttl_secs
+}
+
+/// Result of a successful `jscode2session` call
+#[derive(Deserialize, Derivative)]
+#[derivative(Debug)]
+pub struct Code2Session {
+    /// User's unique identifier inside this mini program
+    pub openid: String,
+    /// Key used to decrypt data returned by `wx.getUserProfile` and friends
+    #[derivative(Debug = "ignore")]
+    pub session_key: String,
+    /// User's identifier across the apps of the same WeChat Open Platform account
+    pub unionid: Option<String>,
+}
+
+/// Raw `jscode2session` response, WeChat answers HTTP 200 with an `errcode` on failure
+#[derive(Deserialize)]
+struct RawCode2Session {
+    #[serde(default)]
+    openid: String,
+    #[serde(default)]
+    session_key: String,
+    unionid: Option<String>,
+    #[serde(default)]
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+}
+
+/// WeChat API client
+///
+/// Calls are made with a blocking HTTP agent on the blocking thread pool, so a slow WeChat API
+/// never stalls the async workers. Cloning is cheap.
+#[derive(Clone)]
+pub struct WeChatClient {
+    conf: Arc<WeChatConf>,
+    agent: ureq::Agent,
+}
+
+impl WeChatClient {
+    /// Creates a client with the configured timeout
+    pub fn new(conf: WeChatConf) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(conf.timeout_secs)))
+            .http_status_as_error(false)
+            .build()
+            .into();
+        WeChatClient {
+            conf: Arc::new(conf),
+            agent,
+        }
+    }
+
+    /// Returns the configuration of this client
+    pub fn conf(&self) -> &WeChatConf {
+        &self.conf
+    }
+
+    /// Exchanges a `wx.login()` code for the user's openid and session key
+    ///
+    /// # Arguments
+    /// * `js_code` - Code obtained by the mini program from `wx.login()`
+    ///
+    /// # Returns
+    /// * `Result<Code2Session, AppError>` - The session, or the `AppError` matching WeChat's
+    ///   `errcode`
+    pub async fn code2session(&self, js_code: &str) -> Result<Code2Session, AppError> {
+        let url = format!("{}/sns/jscode2session", self.conf.base_url.trim_end_matches('/'));
+        let agent = self.agent.clone();
+        let appid = self.conf.appid.clone();
+        let secret = self.conf.secret.clone();
+        let js_code = js_code.to_string();
+
+        let raw = tokio::task::spawn_blocking(move || {
+            agent
+                .get(&url)
+                .query("appid", &appid)
+                .query("secret", &secret)
+                .query("js_code", &js_code)
+                .query("grant_type", "authorization_code")
+                .call()
+                .map_err(|err| {
+                    error!("call wechat api error {}", err);
+                    errors::ErrWechatLogin.clone()
+                })?
+                .body_mut()
+                .read_json::<RawCode2Session>()
+                .map_err(|err| {
+                    error!("unmarshal wechat response {}", err);
+                    errors::ErrUnmarshalJSON.clone()
+                })
+        })
+        .await
+        .map_err(|err| {
+            error!("wechat api task error {}", err);
+            errors::ErrWechatLogin.clone()
+        })??;
+
+        if raw.errcode != 0 {
+            warn!("wechat code2session errcode {} errmsg {}", raw.errcode, raw.errmsg);
+            return Err(map_errcode(raw.errcode));
+        }
+        if raw.openid.is_empty() {
+            error!("wechat code2session returned an empty openid");
+            return Err(errors::ErrWechatLogin.clone());
+        }
+        Ok(Code2Session {
+            openid: raw.openid,
+            session_key: raw.session_key,
+            unionid: raw.unionid,
+        })
+    }
+}
+
+/// Maps a WeChat `errcode` to an application error
+fn map_errcode(errcode: i64) -> AppError {
+    match errcode {
+        // invalid code, code been used
+        40029 | 40163 => errors::ErrWechatInvalidCode.clone(),
+        // api minute-quota reach limit
+        45011 => errors::ErrWechatRateLimited.clone(),
+        // high risk user
+        40226 => errors::ErrWechatRiskUser.clone(),
+        // system busy
+        -1 => errors::ErrWechatBusy.clone(),
+        // invalid appid, invalid appsecret
+        40013 | 40125 => {
+            error!("wechat appid or secret is misconfigured");
+            errors::ErrWechatConfig.clone()
+        }
+        _ => errors::ErrWechatLogin.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    use super::*;
+
+    async fn client() -> WeChatClient {
+        let addr = mock::spawn("mock_appid", "mock_secret").await.unwrap();
+        WeChatClient::new(WeChatConf {
+            appid: "mock_appid".to_string(),
+            secret: "mock_secret".to_string(),
+            base_url: format!("http://{}", addr),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_code2session() {
+        let client = client().await;
+        let session = client.code2session("abc").await.unwrap();
+        assert_eq!(session.openid, "mock_openid_abc");
+        assert!(!session.session_key.is_empty());
+
+        // the same code always maps to the same user
+        let again = client.code2session("abc").await.unwrap();
+        assert_eq!(again.openid, session.openid);
+    }
+
+    #[tokio::test]
+    async fn test_code2session_errcode() {
+        let client = client().await;
+        let status = |err: AppError| err.into_response().status();
+
+        let err = client.code2session("invalid_code").await.unwrap_err();
+        assert_eq!(status(err), StatusCode::BAD_REQUEST);
+        let err = client.code2session("limit").await.unwrap_err();
+        assert_eq!(status(err), StatusCode::TOO_MANY_REQUESTS);
+        let err = client.code2session("busy").await.unwrap_err();
+        assert_eq!(status(err), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_code2session_wrong_secret() {
+        let addr = mock::spawn("mock_appid", "mock_secret").await.unwrap();
+        let client = WeChatClient::new(WeChatConf {
+            appid: "mock_appid".to_string(),
+            secret: "wrong".to_string(),
+            base_url: format!("http://{}", addr),
+            ..Default::default()
+        });
+        let err = client.code2session("abc").await.unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}